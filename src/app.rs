@@ -1,388 +1,524 @@
 use crate::{
+    arpeggiator::{ArpMode, ArpSettings},
     audio,
-    oscillator::{Oscillator, Waveform, ADSR},
+    effects::{
+        CompressorSettings, ConvolutionSettings, DelaySettings, DistortionCurve,
+        DistortionPlacement, DistortionSettings, EffectChain, EffectKind, EffectSlot, EqSettings,
+        ImpulseResponse, Kernel, ModulationSettings, ModulationType, ReverbSettings, EQ_BANDS,
+    },
+    filter::{Filter, FilterRouting, FilterType, Routing},
+    modulation::{Destination, Lfo, LfoWaveform, ModSlot, ModSource},
+    mpe::Mpe,
+    oscillator::{CrossMod, CrossModulation, FilterSlot, Oscillator, Waveform, ADSR},
+    patch::Patch,
+    sample::{PlayMode, Sample},
+    sequencer::{LockParameter, SequencerSettings, Step, MAX_STEPS, STEP_COUNTS},
+    sf2::{PresetInfo, SoundFont},
+    sfz::SfzInstrument,
     styling,
+    tempo::NoteDivision,
+    util::note_name,
+    voicing::{GlideCurve, NotePriority, VoiceMode, VoicingSettings},
 };
 use crossbeam_queue::SegQueue;
 use iced::{
-    image, pick_list, Alignment, Column, Container, Element, Image, Length, PickList, Row, Sandbox,
-    Text,
+    button, image, pick_list, scrollable, text_input, Alignment, Button, Checkbox, Column,
+    Container, Element, Image, Length, PickList, Row, Sandbox, Scrollable, Text, TextInput,
 };
 use iced_audio::{knob, FloatRange, FreqRange, Knob, Normal};
-use log::info;
+use log::{error, info};
 use midi_msg::MidiMsg;
 use midir::MidiInputConnection;
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+const MAX_OSCILLATORS: usize = 4;
+const MOD_SLOTS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page {
+    Synth,
+    /// Tempo, MPE, voice mode and arpeggiator, which shape how the synth is played.
+    Performance,
+    Matrix,
+    Sequencer,
+}
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    DetuneOsc1(Normal),
-    WaveformOsc1Selected(Waveform),
-    AttackOsc1(Normal),
-    DecayOsc1(Normal),
-    SustainOsc1(Normal),
-    ReleaseOsc1(Normal),
-    DetuneOsc2(Normal),
-    WaveformOsc2Selected(Waveform),
-    AttackOsc2(Normal),
-    DecayOsc2(Normal),
-    SustainOsc2(Normal),
-    ReleaseOsc2(Normal),
-    CutoffF1(Normal),
-    ResonanceF1(Normal),
-    CutoffF2(Normal),
-    ResonanceF2(Normal),
+    Detune(usize, Normal),
+    WaveformSelected(usize, Waveform),
+    Attack(usize, Normal),
+    Decay(usize, Normal),
+    Sustain(usize, Normal),
+    Release(usize, Normal),
+    FilterSelected(usize, FilterSlot),
+    FilePathChanged(usize, String),
+    LoadFile(usize),
+    PresetSelected(usize, PresetInfo),
+    SampleRoot(usize, Normal),
+    PlayModeSelected(usize, PlayMode),
+    SampleStart(usize, Normal),
+    SampleEnd(usize, Normal),
+    LoopStart(usize, Normal),
+    LoopEnd(usize, Normal),
+    Crossfade(usize, Normal),
+    AddOscillator,
+    RemoveOscillator(usize),
+    CrossModSelected(CrossModulation),
+    FmIndex(Normal),
+    RoutingSelected(FilterRouting),
+    Balance(Normal),
+    FilterTypeSelected(usize, FilterType),
+    Cutoff(usize, Normal),
+    Resonance(usize, Normal),
+    FilterAttack(usize, Normal),
+    FilterDecay(usize, Normal),
+    FilterSustain(usize, Normal),
+    FilterRelease(usize, Normal),
+    EnvelopeAmount(usize, Normal),
+    VelocityAmount(usize, Normal),
+    KeyTracking(usize, Normal),
+    Drive(usize, Normal),
+    LfoWaveformSelected(usize, LfoWaveform),
+    LfoRate(usize, Normal),
+    LfoDepth(usize, Normal),
+    LfoPhase(usize, Normal),
+    LfoRetrigger(usize, bool),
+    LfoDestinationSelected(usize, Destination),
+    ShowPage(Page),
+    ModSourceSelected(usize, ModSource),
+    ModDestinationSelected(usize, Destination),
+    ModAmount(usize, Normal),
+    MpeToggled(bool),
+    PatchPathChanged(String),
+    SavePatch,
+    LoadPatch,
+    VoiceModeSelected(VoiceMode),
+    NotePrioritySelected(NotePriority),
+    Glide(Normal),
+    GlideCurveSelected(GlideCurve),
+    Tempo(Normal),
+    ArpToggled(bool),
+    ArpLatchToggled(bool),
+    ArpModeSelected(ArpMode),
+    ArpRateSelected(NoteDivision),
+    ArpOctaves(Normal),
+    ArpGate(Normal),
+    ArpSwing(Normal),
+    SequencerPlayToggled(bool),
+    SequencerLengthSelected(usize),
+    SequencerRateSelected(NoteDivision),
+    SequencerLockParameterSelected(LockParameter),
+    SequencerStepSelected(usize),
+    StepActiveToggled(bool),
+    StepTieToggled(bool),
+    StepNote(Normal),
+    StepVelocity(Normal),
+    StepGate(Normal),
+    StepLockToggled(bool),
+    StepLock(Normal),
+    DistortionPlacementSelected(DistortionPlacement),
+    DistortionCurveSelected(DistortionCurve),
+    DistortionDrive(Normal),
+    DistortionBits(Normal),
+    DistortionDownsample(Normal),
+    DistortionMix(Normal),
+    ModulationTypeSelected(ModulationType),
+    ModulationRate(Normal),
+    ModulationDepth(Normal),
+    ModulationFeedback(Normal),
+    ModulationMix(Normal),
+    DelaySyncToggled(bool),
+    DelayTime(Normal),
+    DelayDivisionSelected(NoteDivision),
+    DelayFeedback(Normal),
+    DelayHighCut(Normal),
+    DelayPingPongToggled(bool),
+    DelayMix(Normal),
+    EffectMoved(usize, usize),
+    /// Picks up a slot, or puts it back when it is the one already picked up.
+    EffectPicked(usize),
+    /// Moves the slot that was picked up to another place in the chain.
+    EffectDropped(usize),
+    EffectBypassToggled(usize, bool),
+    ImpulsePathChanged(String),
+    LoadImpulse,
+    ConvolutionPreDelay(Normal),
+    ConvolutionHighCut(Normal),
+    ConvolutionMix(Normal),
+    EqFrequency(usize, Normal),
+    EqGain(usize, Normal),
+    EqQ(usize, Normal),
+    CompressorThreshold(Normal),
+    CompressorRatio(Normal),
+    CompressorAttack(Normal),
+    CompressorRelease(Normal),
+    CompressorMakeup(Normal),
+    CompressorKnee(Normal),
+    ReverbSize(Normal),
+    ReverbDamping(Normal),
+    ReverbPreDelay(Normal),
+    ReverbWidth(Normal),
+    ReverbMix(Normal),
 }
 
-pub struct App {
-    _midi_msgs: Arc<SegQueue<MidiMsg>>,
-    _connection: Option<MidiInputConnection<()>>,
-    osc1: Arc<RwLock<Oscillator>>,
-    osc2: Arc<RwLock<Oscillator>>,
-    f1: Arc<RwLock<(f64, f64)>>,
-    f2: Arc<RwLock<(f64, f64)>>,
-
-    // ui from here on out
-    detune_range: FloatRange,
-    adsr_range: FloatRange,
-    freq_range: FreqRange,
-    resonance_range: FloatRange,
-
-    // osc1
-    osc1_detune_state: knob::State,
-    osc1_detune_label: String,
-    osc1_waveform_state: pick_list::State<Waveform>,
-    osc1_waveform_selected: Option<Waveform>,
-    osc1_waveform_label: String,
-    osc1_attack_state: knob::State,
-    osc1_attack_label: String,
-    osc1_decay_state: knob::State,
-    osc1_decay_label: String,
-    osc1_sustain_state: knob::State,
-    osc1_sustain_label: String,
-    osc1_release_state: knob::State,
-    osc1_release_label: String,
-
-    // osc2
-    osc2_detune_state: knob::State,
-    osc2_detune_label: String,
-    osc2_waveform_state: pick_list::State<Waveform>,
-    osc2_waveform_selected: Option<Waveform>,
-    osc2_waveform_label: String,
-    osc2_attack_state: knob::State,
-    osc2_attack_label: String,
-    osc2_decay_state: knob::State,
-    osc2_decay_label: String,
-    osc2_sustain_state: knob::State,
-    osc2_sustain_label: String,
-    osc2_release_state: knob::State,
-    osc2_release_label: String,
-
-    // f1
-    f1_cutoff_state: knob::State,
-    f1_cutoff_label: String,
-    f1_resonance_state: knob::State,
-    f1_resonance_label: String,
-
-    // f2
-    f2_cutoff_state: knob::State,
-    f2_cutoff_label: String,
-    f2_resonance_state: knob::State,
-    f2_resonance_label: String,
+struct OscillatorRanges {
+    detune: FloatRange,
+    adsr: FloatRange,
+    root: FloatRange,
+    position: FloatRange,
+    crossfade: FloatRange,
 }
 
-impl Sandbox for App {
-    type Message = Message;
-
-    fn title(&self) -> String {
-        "Hacksynth".to_string()
-    }
+struct OscillatorPanel {
+    detune_state: knob::State,
+    detune_label: String,
+    waveform_state: pick_list::State<Waveform>,
+    waveform_selected: Option<Waveform>,
+    waveform_label: String,
+    attack_state: knob::State,
+    attack_label: String,
+    decay_state: knob::State,
+    decay_label: String,
+    sustain_state: knob::State,
+    sustain_label: String,
+    release_state: knob::State,
+    release_label: String,
+    filter_state: pick_list::State<FilterSlot>,
+    filter_selected: Option<FilterSlot>,
+    filter_label: String,
+    remove_state: button::State,
 
-    fn new() -> App {
-        let osc1 = Arc::new(RwLock::new(Oscillator::new(
-            crate::oscillator::Waveform::Square,
-            ADSR(0.11, 0.14, 0.47, 0.63),
-            0.0,
-        )));
-        let osc2 = Arc::new(RwLock::new(Oscillator::new(
-            crate::oscillator::Waveform::Sine,
-            ADSR(0.11, 0.14, 0.47, 0.63),
-            0.0,
-        )));
-
-        let f1 = Arc::new(RwLock::new((20000.0, 1.0)));
-        let f2 = Arc::new(RwLock::new((20000.0, 1.0)));
+    // sample and instrument sources
+    file_path: String,
+    file_path_state: text_input::State,
+    load_file_state: button::State,
+    sample_label: String,
+    instrument_label: String,
+    soundfont_label: String,
+    presets: Vec<PresetInfo>,
+    preset_state: pick_list::State<PresetInfo>,
+    preset_selected: Option<PresetInfo>,
+    preset_label: String,
+    root_state: knob::State,
+    root_label: String,
+    play_mode_state: pick_list::State<PlayMode>,
+    play_mode_selected: Option<PlayMode>,
+    play_mode_label: String,
+    start_state: knob::State,
+    start_label: String,
+    end_state: knob::State,
+    end_label: String,
+    loop_start_state: knob::State,
+    loop_start_label: String,
+    loop_end_state: knob::State,
+    loop_end_label: String,
+    crossfade_state: knob::State,
+    crossfade_label: String,
+}
 
-        let _midi_msgs = Arc::new(SegQueue::new());
-        // This has to be retained to ensure the connection is not dropped
-        let _connection = audio::run_midi(_midi_msgs.clone()).ok();
-        audio::setup_output(
-            _midi_msgs.clone(),
-            vec![osc1.clone(), osc2.clone()],
-            vec![f1.clone(), f2.clone()],
-        );
-
-        let detune_range = FloatRange::new(-100.0, 100.0);
-        let adsr_range = FloatRange::new(0.0, 1.0);
-        let freq_range = FreqRange::default();
-        let resonance_range = FloatRange::new(0.0, 100.0);
-
-        // osc1 state
-        let osc1_detune_state =
-            knob::State::new(detune_range.normal_param(osc1.read().unwrap().detune, 0.0));
-        let osc1_detune_label = format!("Detune\n{} Hz", osc1.read().unwrap().detune);
-        let osc1_waveform_state = pick_list::State::default();
-        let osc1_waveform_selected = Some(osc1.read().unwrap().waveform);
-        let osc1_waveform_label = format!("Waveform");
-        let osc1_attack_state =
-            knob::State::new(adsr_range.normal_param(osc1.read().unwrap().adsr.0 as f32, 0.0));
-        let osc1_attack_label = format!("Attack\n{} s", osc1.read().unwrap().adsr.0 as f32);
-        let osc1_decay_state =
-            knob::State::new(adsr_range.normal_param(osc1.read().unwrap().adsr.1 as f32, 0.0));
-        let osc1_decay_label = format!("Decay\n{} s", osc1.read().unwrap().adsr.1 as f32);
-        let osc1_sustain_state =
-            knob::State::new(adsr_range.normal_param(osc1.read().unwrap().adsr.2 as f32, 0.0));
-        let osc1_sustain_label = format!("Sustain\n{} s", osc1.read().unwrap().adsr.2 as f32);
-        let osc1_release_state =
-            knob::State::new(adsr_range.normal_param(osc1.read().unwrap().adsr.3 as f32, 0.0));
-        let osc1_release_label = format!("Release\n{} s", osc1.read().unwrap().adsr.3 as f32);
-
-        // osc2 state
-        let osc2_detune_state =
-            knob::State::new(detune_range.normal_param(osc2.read().unwrap().detune, 0.0));
-        let osc2_detune_label = format!("Detune\n{} Hz", osc2.read().unwrap().detune);
-        let osc2_waveform_state = pick_list::State::default();
-        let osc2_waveform_selected = Some(osc2.read().unwrap().waveform);
-        let osc2_waveform_label = format!("Waveform");
-        let osc2_attack_state =
-            knob::State::new(adsr_range.normal_param(osc2.read().unwrap().adsr.0 as f32, 0.0));
-        let osc2_attack_label = format!("Attack\n{} s", osc2.read().unwrap().adsr.0 as f32);
-        let osc2_decay_state =
-            knob::State::new(adsr_range.normal_param(osc2.read().unwrap().adsr.1 as f32, 0.0));
-        let osc2_decay_label = format!("Decay\n{} s", osc2.read().unwrap().adsr.1 as f32);
-        let osc2_sustain_state =
-            knob::State::new(adsr_range.normal_param(osc2.read().unwrap().adsr.2 as f32, 0.0));
-        let osc2_sustain_label = format!("Sustain\n{} s", osc2.read().unwrap().adsr.2 as f32);
-        let osc2_release_state =
-            knob::State::new(adsr_range.normal_param(osc2.read().unwrap().adsr.3 as f32, 0.0));
-        let osc2_release_label = format!("Release\n{} s", osc2.read().unwrap().adsr.3 as f32);
-
-        // f1 state
-        let f1_cutoff_state = knob::State::new(freq_range.default_normal_param());
-        let f1_cutoff_label = format!("Cutoff\n 20000.00 Hz");
-        let f1_resonance_state = knob::State::new(resonance_range.default_normal_param());
-        let f1_resonance_label = format!("Resonance\n 0.00 %");
-
-        // f2 state
-        let f2_cutoff_state = knob::State::new(freq_range.default_normal_param());
-        let f2_cutoff_label = format!("Cutoff\n 20000.00 Hz");
-        let f2_resonance_state = knob::State::new(resonance_range.default_normal_param());
-        let f2_resonance_label = format!("Resonance\n 0.00 %");
-
-        App {
-            _midi_msgs,
-            _connection,
-            osc1,
-            osc2,
-            f1,
-            f2,
+impl OscillatorPanel {
+    fn new(oscillator: &Oscillator, ranges: &OscillatorRanges) -> OscillatorPanel {
+        let detune_range = &ranges.detune;
+        let adsr_range = &ranges.adsr;
+        let settings = &oscillator.sample_settings;
 
-            // ui from here on out
-            detune_range,
-            adsr_range,
-            freq_range,
-            resonance_range,
-
-            // osc1 state
-            osc1_detune_state,
-            osc1_detune_label,
-            osc1_waveform_state,
-            osc1_waveform_selected,
-            osc1_waveform_label,
-            osc1_attack_state,
-            osc1_attack_label,
-            osc1_decay_state,
-            osc1_decay_label,
-            osc1_sustain_state,
-            osc1_sustain_label,
-            osc1_release_state,
-            osc1_release_label,
-
-            // osc2 state
-            osc2_detune_state,
-            osc2_detune_label,
-            osc2_waveform_state,
-            osc2_waveform_selected,
-            osc2_waveform_label,
-            osc2_attack_state,
-            osc2_attack_label,
-            osc2_decay_state,
-            osc2_decay_label,
-            osc2_sustain_state,
-            osc2_sustain_label,
-            osc2_release_state,
-            osc2_release_label,
-
-            // f1 state
-            f1_cutoff_state,
-            f1_cutoff_label,
-            f1_resonance_state,
-            f1_resonance_label,
-
-            // f2 state
-            f2_cutoff_state,
-            f2_cutoff_label,
-            f2_resonance_state,
-            f2_resonance_label,
-        }
-    }
+        OscillatorPanel {
+            detune_state: knob::State::new(detune_range.normal_param(oscillator.detune, 0.0)),
+            detune_label: format!("Detune\n{} Hz", oscillator.detune),
+            waveform_state: pick_list::State::default(),
+            waveform_selected: Some(oscillator.waveform),
+            waveform_label: format!("Waveform"),
+            attack_state: knob::State::new(adsr_range.normal_param(oscillator.adsr.0 as f32, 0.0)),
+            attack_label: format!("Attack\n{} s", oscillator.adsr.0 as f32),
+            decay_state: knob::State::new(adsr_range.normal_param(oscillator.adsr.1 as f32, 0.0)),
+            decay_label: format!("Decay\n{} s", oscillator.adsr.1 as f32),
+            sustain_state: knob::State::new(adsr_range.normal_param(oscillator.adsr.2 as f32, 0.0)),
+            sustain_label: format!("Sustain\n{} s", oscillator.adsr.2 as f32),
+            release_state: knob::State::new(adsr_range.normal_param(oscillator.adsr.3 as f32, 0.0)),
+            release_label: format!("Release\n{} s", oscillator.adsr.3 as f32),
+            filter_state: pick_list::State::default(),
+            filter_selected: Some(oscillator.filter),
+            filter_label: format!("Filter"),
+            remove_state: button::State::default(),
 
-    fn update(&mut self, event: Message) {
-        match event {
-            Message::DetuneOsc1(normal) => {
-                let value = self.detune_range.unmap_to_value(normal);
-                self.osc1.write().unwrap().detune = value;
-                self.osc1_detune_label = format!("Detune\n{:+.1} Hz", value);
-                info!("detune osc1: {value} Hz")
-            }
-            Message::WaveformOsc1Selected(waveform) => {
-                self.osc1_waveform_selected = Some(waveform);
-                self.osc1.write().unwrap().waveform = waveform;
-            }
-            Message::AttackOsc1(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc1.write().unwrap().adsr.0 = value as f64;
-                self.osc1_attack_label = format!("Attack\n{:.2} s", value);
-                info!("attack osc1: {value} s")
-            }
-            Message::DecayOsc1(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc1.write().unwrap().adsr.1 = value as f64;
-                self.osc1_decay_label = format!("Decay\n{:.2} s", value);
-                info!("decay osc1: {value} s")
-            }
-            Message::SustainOsc1(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc1.write().unwrap().adsr.2 = value as f64;
-                self.osc1_sustain_label = format!("Sustain\n{:.2} s", value);
-                info!("sustain osc1: {value} s")
-            }
-            Message::ReleaseOsc1(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc1.write().unwrap().adsr.3 = value as f64;
-                self.osc1_release_label = format!("Release\n{:.2} s", value);
-                info!("release osc1: {value} s")
-            }
-            Message::DetuneOsc2(normal) => {
-                let value = self.detune_range.unmap_to_value(normal);
-                self.osc2.write().unwrap().detune = value;
-                self.osc2_detune_label = format!("Detune\n{:+.1} Hz", value);
-                info!("detune osc2: {value} Hz")
-            }
-            Message::WaveformOsc2Selected(waveform) => {
-                self.osc2_waveform_selected = Some(waveform);
-                self.osc2.write().unwrap().waveform = waveform;
-            }
-            Message::AttackOsc2(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc2.write().unwrap().adsr.0 = value as f64;
-                self.osc2_attack_label = format!("Attack\n{:.2} s", value);
-                info!("attack osc2: {value} s")
-            }
-            Message::DecayOsc2(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc2.write().unwrap().adsr.1 = value as f64;
-                self.osc2_decay_label = format!("Decay\n{:.2} s", value);
-                info!("decay osc2: {value} s")
-            }
-            Message::SustainOsc2(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc2.write().unwrap().adsr.2 = value as f64;
-                self.osc2_sustain_label = format!("Sustain\n{:.2} s", value);
-                info!("sustain osc2: {value} s")
-            }
-            Message::ReleaseOsc2(normal) => {
-                let value = self.adsr_range.unmap_to_value(normal);
-                self.osc2.write().unwrap().adsr.3 = value as f64;
-                self.osc2_release_label = format!("Release\n{:.2} s", value);
-                info!("release osc2: {value} s")
-            }
-            Message::CutoffF1(normal) => {
-                let value = self.freq_range.unmap_to_value(normal);
-                self.f1.write().unwrap().0 = value as f64;
-                self.f1_cutoff_label = format!("Cutoff\n{:.2} Hz", value);
-                info!("cutoff f1: {value} Hz")
-            }
-            Message::ResonanceF1(normal) => {
-                let value = self.resonance_range.unmap_to_value(normal);
-                self.f1.write().unwrap().1 = value as f64;
-                self.f1_resonance_label = format!("Resonance\n{:.2} %", value);
-                info!("resonance f1: {value} %")
-            }
-            Message::CutoffF2(normal) => {
-                let value = self.freq_range.unmap_to_value(normal);
-                self.f2.write().unwrap().0 = value as f64;
-                self.f2_cutoff_label = format!("Cutoff\n{:.2} Hz", value);
-                info!("cutoff f2: {value} Hz")
-            }
-            Message::ResonanceF2(normal) => {
-                let value = self.resonance_range.unmap_to_value(normal);
-                self.f2.write().unwrap().1 = value as f64;
-                self.f2_resonance_label = format!("Resonance\n{:.2} %", value);
-                info!("resonance f2: {value} %")
-            }
+            file_path: String::new(),
+            file_path_state: text_input::State::default(),
+            load_file_state: button::State::default(),
+            sample_label: match &oscillator.sample {
+                Some(sample) => format!("Sample\n{}", sample.name),
+                None => format!("Sample\nnone"),
+            },
+            instrument_label: match &oscillator.instrument {
+                Some(instrument) => format!(
+                    "Instrument\n{} ({} regions)",
+                    instrument.name,
+                    instrument.regions.len()
+                ),
+                None => format!("Instrument\nnone"),
+            },
+            soundfont_label: match &oscillator.soundfont {
+                Some(soundfont) => format!("SoundFont\n{}", soundfont.name),
+                None => format!("SoundFont\nnone"),
+            },
+            presets: oscillator
+                .soundfont
+                .as_ref()
+                .map(|s| s.presets.clone())
+                .unwrap_or_default(),
+            preset_state: pick_list::State::default(),
+            preset_selected: oscillator
+                .soundfont
+                .as_ref()
+                .and_then(|s| s.presets.get(oscillator.preset).cloned()),
+            preset_label: format!("Preset"),
+            root_state: knob::State::new(ranges.root.normal_param(settings.root as f32, 60.0)),
+            root_label: format!("Root Key\n{}", settings.root),
+            play_mode_state: pick_list::State::default(),
+            play_mode_selected: Some(settings.mode),
+            play_mode_label: format!("Mode"),
+            start_state: knob::State::new(ranges.position.normal_param(settings.start as f32, 0.0)),
+            start_label: format!("Start\n{:.0} %", settings.start * 100.0),
+            end_state: knob::State::new(ranges.position.normal_param(settings.end as f32, 1.0)),
+            end_label: format!("End\n{:.0} %", settings.end * 100.0),
+            loop_start_state: knob::State::new(
+                ranges
+                    .position
+                    .normal_param(settings.loop_start as f32, 0.0),
+            ),
+            loop_start_label: format!("Loop Start\n{:.0} %", settings.loop_start * 100.0),
+            loop_end_state: knob::State::new(
+                ranges.position.normal_param(settings.loop_end as f32, 1.0),
+            ),
+            loop_end_label: format!("Loop End\n{:.0} %", settings.loop_end * 100.0),
+            crossfade_state: knob::State::new(
+                ranges
+                    .crossfade
+                    .normal_param(settings.crossfade as f32, 0.0),
+            ),
+            crossfade_label: format!("Crossfade\n{:.0} ms", settings.crossfade * 1000.0),
         }
     }
 
-    fn view(&mut self) -> Element<Message> {
-        let osc1_detune = Knob::new(
-            &mut self.osc1_detune_state,
-            Message::DetuneOsc1,
+    fn view(&mut self, index: usize, removable: bool) -> Element<Message> {
+        let detune = Knob::new(
+            &mut self.detune_state,
+            move |normal| Message::Detune(index, normal),
             || None,
             || None,
         );
 
-        let osc1_waveform = PickList::new(
-            &mut self.osc1_waveform_state,
+        let waveform = PickList::new(
+            &mut self.waveform_state,
             &Waveform::ALL[..],
-            self.osc1_waveform_selected,
-            Message::WaveformOsc1Selected,
+            self.waveform_selected,
+            move |waveform| Message::WaveformSelected(index, waveform),
         );
 
-        let osc1_attack = Knob::new(
-            &mut self.osc1_attack_state,
-            Message::AttackOsc1,
+        let attack = Knob::new(
+            &mut self.attack_state,
+            move |normal| Message::Attack(index, normal),
             || None,
             || None,
         );
 
-        let osc1_decay = Knob::new(
-            &mut self.osc1_decay_state,
-            Message::DecayOsc1,
+        let decay = Knob::new(
+            &mut self.decay_state,
+            move |normal| Message::Decay(index, normal),
             || None,
             || None,
         );
 
-        let osc1_sustain = Knob::new(
-            &mut self.osc1_sustain_state,
-            Message::SustainOsc1,
+        let sustain = Knob::new(
+            &mut self.sustain_state,
+            move |normal| Message::Sustain(index, normal),
             || None,
             || None,
         );
 
-        let osc1_release = Knob::new(
-            &mut self.osc1_release_state,
-            Message::ReleaseOsc1,
+        let release = Knob::new(
+            &mut self.release_state,
+            move |normal| Message::Release(index, normal),
             || None,
             || None,
         );
 
-        let osc1 = Container::new(
-            Column::new()
-                .align_items(Alignment::Center)
-                .spacing(5)
-                .padding(5)
-                .push(Text::new("Oscillator 1").size(12))
+        let filter = PickList::new(
+            &mut self.filter_state,
+            &FilterSlot::ALL[..],
+            self.filter_selected,
+            move |slot| Message::FilterSelected(index, slot),
+        );
+
+        let mut remove = Button::new(&mut self.remove_state, Text::new("Remove").size(12));
+        if removable {
+            remove = remove.on_press(Message::RemoveOscillator(index));
+        }
+
+        let mut panel = Column::new()
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(5)
+            .push(Text::new(format!("Oscillator {}", index + 1)).size(12))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.detune_label).size(12))
+                            .push(detune),
+                    )
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.waveform_label).size(12))
+                            .push(waveform),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.attack_label).size(12))
+                            .push(attack),
+                    )
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.decay_label).size(12))
+                            .push(decay),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.sustain_label).size(12))
+                            .push(sustain),
+                    )
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.release_label).size(12))
+                            .push(release),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.filter_label).size(12))
+                            .push(filter),
+                    )
+                    .push(remove),
+            );
+
+        let (file_label, placeholder) = match self.waveform_selected {
+            Some(Waveform::Sample) => (Some(&self.sample_label), "path/to/sample.wav"),
+            Some(Waveform::Sfz) => (Some(&self.instrument_label), "path/to/instrument.sfz"),
+            Some(Waveform::SoundFont) => (Some(&self.soundfont_label), "path/to/bank.sf2"),
+            _ => (None, ""),
+        };
+        if let Some(file_label) = file_label {
+            let file_path = TextInput::new(
+                &mut self.file_path_state,
+                placeholder,
+                &self.file_path,
+                move |path| Message::FilePathChanged(index, path),
+            )
+            .size(12)
+            .padding(3)
+            .on_submit(Message::LoadFile(index));
+
+            let load_file = Button::new(&mut self.load_file_state, Text::new("Load").size(12))
+                .on_press(Message::LoadFile(index));
+
+            panel = panel.push(Text::new(file_label).size(12)).push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(file_path)
+                    .push(load_file),
+            );
+        }
+
+        if self.waveform_selected == Some(Waveform::SoundFont) {
+            let preset = PickList::new(
+                &mut self.preset_state,
+                self.presets.clone(),
+                self.preset_selected.clone(),
+                move |preset| Message::PresetSelected(index, preset),
+            )
+            .text_size(12);
+
+            panel = panel.push(
+                Column::new()
+                    .push(Text::new(&self.preset_label).size(12))
+                    .push(preset),
+            );
+        }
+
+        if self.waveform_selected == Some(Waveform::Sample) {
+            let root = Knob::new(
+                &mut self.root_state,
+                move |normal| Message::SampleRoot(index, normal),
+                || None,
+                || None,
+            );
+
+            let play_mode = PickList::new(
+                &mut self.play_mode_state,
+                &PlayMode::ALL[..],
+                self.play_mode_selected,
+                move |mode| Message::PlayModeSelected(index, mode),
+            );
+
+            let start = Knob::new(
+                &mut self.start_state,
+                move |normal| Message::SampleStart(index, normal),
+                || None,
+                || None,
+            );
+
+            let end = Knob::new(
+                &mut self.end_state,
+                move |normal| Message::SampleEnd(index, normal),
+                || None,
+                || None,
+            );
+
+            let loop_start = Knob::new(
+                &mut self.loop_start_state,
+                move |normal| Message::LoopStart(index, normal),
+                || None,
+                || None,
+            );
+
+            let loop_end = Knob::new(
+                &mut self.loop_end_state,
+                move |normal| Message::LoopEnd(index, normal),
+                || None,
+                || None,
+            );
+
+            let crossfade = Knob::new(
+                &mut self.crossfade_state,
+                move |normal| Message::Crossfade(index, normal),
+                || None,
+                || None,
+            );
+
+            panel = panel
                 .push(
                     Row::new()
                         .spacing(8)
@@ -390,13 +526,13 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_detune_label).size(12))
-                                .push(osc1_detune),
+                                .push(Text::new(&self.root_label).size(12))
+                                .push(root),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_waveform_label).size(12))
-                                .push(osc1_waveform),
+                                .push(Text::new(&self.play_mode_label).size(12))
+                                .push(play_mode),
                         ),
                 )
                 .push(
@@ -406,13 +542,13 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_attack_label).size(12))
-                                .push(osc1_attack),
+                                .push(Text::new(&self.start_label).size(12))
+                                .push(start),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_decay_label).size(12))
-                                .push(osc1_decay),
+                                .push(Text::new(&self.end_label).size(12))
+                                .push(end),
                         ),
                 )
                 .push(
@@ -422,67 +558,236 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_sustain_label).size(12))
-                                .push(osc1_sustain),
+                                .push(Text::new(&self.loop_start_label).size(12))
+                                .push(loop_start),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc1_release_label).size(12))
-                                .push(osc1_release),
+                                .push(Text::new(&self.loop_end_label).size(12))
+                                .push(loop_end),
                         ),
-                ),
-        )
-        .style(styling::GroupContainer)
-        .width(Length::Fill);
+                )
+                .push(
+                    Column::new()
+                        .push(Text::new(&self.crossfade_label).size(12))
+                        .push(crossfade),
+                );
+        }
+
+        Container::new(panel)
+            .style(styling::GroupContainer)
+            .width(Length::Fill)
+            .into()
+    }
+}
+
+struct FilterRanges {
+    cutoff: FreqRange,
+    resonance: FloatRange,
+    adsr: FloatRange,
+    amount: FloatRange,
+    velocity: FloatRange,
+    key_tracking: FloatRange,
+    drive: FloatRange,
+}
+
+struct FilterPanel {
+    type_state: pick_list::State<FilterType>,
+    type_selected: Option<FilterType>,
+    type_label: String,
+    cutoff_state: knob::State,
+    cutoff_label: String,
+    resonance_state: knob::State,
+    resonance_label: String,
+
+    // envelope
+    attack_state: knob::State,
+    attack_label: String,
+    decay_state: knob::State,
+    decay_label: String,
+    sustain_state: knob::State,
+    sustain_label: String,
+    release_state: knob::State,
+    release_label: String,
+    amount_state: knob::State,
+    amount_label: String,
+    velocity_state: knob::State,
+    velocity_label: String,
+    key_tracking_state: knob::State,
+    key_tracking_label: String,
+    drive_state: knob::State,
+    drive_label: String,
+}
+
+impl FilterPanel {
+    fn new(filter: &Filter, ranges: &FilterRanges) -> FilterPanel {
+        let adsr_range = &ranges.adsr;
+
+        FilterPanel {
+            type_state: pick_list::State::default(),
+            type_selected: Some(filter.filter_type),
+            type_label: format!("Type"),
+            cutoff_state: knob::State::new(
+                ranges.cutoff.normal_param(filter.cutoff as f32, 20000.0),
+            ),
+            cutoff_label: format!("Cutoff\n{:.2} Hz", filter.cutoff),
+            resonance_state: knob::State::new(
+                ranges.resonance.normal_param(filter.resonance as f32, 0.0),
+            ),
+            resonance_label: format!("Resonance\n{:.2} %", filter.resonance),
 
-        let osc2_detune = Knob::new(
-            &mut self.osc2_detune_state,
-            Message::DetuneOsc2,
+            attack_state: knob::State::new(adsr_range.normal_param(filter.envelope.0 as f32, 0.0)),
+            attack_label: format!("Attack\n{:.2} s", filter.envelope.0),
+            decay_state: knob::State::new(adsr_range.normal_param(filter.envelope.1 as f32, 0.0)),
+            decay_label: format!("Decay\n{:.2} s", filter.envelope.1),
+            sustain_state: knob::State::new(adsr_range.normal_param(filter.envelope.2 as f32, 0.0)),
+            sustain_label: format!("Sustain\n{:.2}", filter.envelope.2),
+            release_state: knob::State::new(adsr_range.normal_param(filter.envelope.3 as f32, 0.0)),
+            release_label: format!("Release\n{:.2} s", filter.envelope.3),
+            amount_state: knob::State::new(
+                ranges
+                    .amount
+                    .normal_param(filter.envelope_amount as f32, 0.0),
+            ),
+            amount_label: format!("Env Amount\n{:+.2} oct", filter.envelope_amount),
+            velocity_state: knob::State::new(
+                ranges
+                    .velocity
+                    .normal_param(filter.velocity_amount as f32, 0.0),
+            ),
+            velocity_label: format!("Velocity\n{:.0} %", filter.velocity_amount * 100.0),
+            key_tracking_state: knob::State::new(
+                ranges
+                    .key_tracking
+                    .normal_param(filter.key_tracking as f32, 0.0),
+            ),
+            key_tracking_label: format!("Key Track\n{:.0} %", filter.key_tracking * 100.0),
+            drive_state: knob::State::new(ranges.drive.normal_param(filter.drive as f32, 0.0)),
+            drive_label: format!("Drive\n{:.0} %", filter.drive * 100.0),
+        }
+    }
+
+    fn view(&mut self, index: usize) -> Element<Message> {
+        let filter_type = PickList::new(
+            &mut self.type_state,
+            &FilterType::ALL[..],
+            self.type_selected,
+            move |filter_type| Message::FilterTypeSelected(index, filter_type),
+        );
+
+        let cutoff = Knob::new(
+            &mut self.cutoff_state,
+            move |normal| Message::Cutoff(index, normal),
             || None,
             || None,
         );
 
-        let osc2_waveform = PickList::new(
-            &mut self.osc2_waveform_state,
-            &Waveform::ALL[..],
-            self.osc2_waveform_selected,
-            Message::WaveformOsc2Selected,
+        let resonance = Knob::new(
+            &mut self.resonance_state,
+            move |normal| Message::Resonance(index, normal),
+            || None,
+            || None,
+        );
+
+        let attack = Knob::new(
+            &mut self.attack_state,
+            move |normal| Message::FilterAttack(index, normal),
+            || None,
+            || None,
+        );
+
+        let decay = Knob::new(
+            &mut self.decay_state,
+            move |normal| Message::FilterDecay(index, normal),
+            || None,
+            || None,
+        );
+
+        let sustain = Knob::new(
+            &mut self.sustain_state,
+            move |normal| Message::FilterSustain(index, normal),
+            || None,
+            || None,
+        );
+
+        let release = Knob::new(
+            &mut self.release_state,
+            move |normal| Message::FilterRelease(index, normal),
+            || None,
+            || None,
         );
 
-        let osc2_attack = Knob::new(
-            &mut self.osc2_attack_state,
-            Message::AttackOsc2,
+        let amount = Knob::new(
+            &mut self.amount_state,
+            move |normal| Message::EnvelopeAmount(index, normal),
             || None,
             || None,
         );
 
-        let osc2_decay = Knob::new(
-            &mut self.osc2_decay_state,
-            Message::DecayOsc2,
+        let velocity = Knob::new(
+            &mut self.velocity_state,
+            move |normal| Message::VelocityAmount(index, normal),
             || None,
             || None,
         );
 
-        let osc2_sustain = Knob::new(
-            &mut self.osc2_sustain_state,
-            Message::SustainOsc2,
+        let key_tracking = Knob::new(
+            &mut self.key_tracking_state,
+            move |normal| Message::KeyTracking(index, normal),
             || None,
             || None,
         );
 
-        let osc2_release = Knob::new(
-            &mut self.osc2_release_state,
-            Message::ReleaseOsc2,
+        let drive = Knob::new(
+            &mut self.drive_state,
+            move |normal| Message::Drive(index, normal),
             || None,
             || None,
         );
 
-        let osc2 = Container::new(
+        Container::new(
             Column::new()
                 .align_items(Alignment::Center)
                 .spacing(5)
                 .padding(5)
-                .push(Text::new("Oscillator 2").size(12))
+                .push(Text::new(format!("Filter {}", index + 1)).size(12))
+                .push(
+                    Column::new()
+                        .push(Text::new(&self.type_label).size(12))
+                        .push(filter_type),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.cutoff_label).size(12))
+                                .push(cutoff),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.resonance_label).size(12))
+                                .push(resonance),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.attack_label).size(12))
+                                .push(attack),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.decay_label).size(12))
+                                .push(decay),
+                        ),
+                )
                 .push(
                     Row::new()
                         .spacing(8)
@@ -490,13 +795,13 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_detune_label).size(12))
-                                .push(osc2_detune),
+                                .push(Text::new(&self.sustain_label).size(12))
+                                .push(sustain),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_waveform_label).size(12))
-                                .push(osc2_waveform),
+                                .push(Text::new(&self.release_label).size(12))
+                                .push(release),
                         ),
                 )
                 .push(
@@ -506,13 +811,13 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_attack_label).size(12))
-                                .push(osc2_attack),
+                                .push(Text::new(&self.amount_label).size(12))
+                                .push(amount),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_decay_label).size(12))
-                                .push(osc2_decay),
+                                .push(Text::new(&self.velocity_label).size(12))
+                                .push(velocity),
                         ),
                 )
                 .push(
@@ -522,62 +827,121 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_sustain_label).size(12))
-                                .push(osc2_sustain),
+                                .push(Text::new(&self.key_tracking_label).size(12))
+                                .push(key_tracking),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.osc2_release_label).size(12))
-                                .push(osc2_release),
+                                .push(Text::new(&self.drive_label).size(12))
+                                .push(drive),
                         ),
                 ),
         )
         .style(styling::GroupContainer)
-        .width(Length::Fill);
+        .width(Length::Fill)
+        .into()
+    }
+}
 
-        let oscillators_container = Container::new(Container::new(
-            Row::new()
-                .align_items(Alignment::Start)
-                .push(Image::new(image::Handle::from_path(format!(
-                    "{}/assets/oscillators_text.png",
-                    env!("CARGO_MANIFEST_DIR")
-                ))))
-                .push(
-                    Column::new()
-                        .spacing(20)
-                        .padding(20)
-                        .align_items(Alignment::Start)
-                        .push(osc1)
-                        .push(osc2),
-                ),
-        ))
-        .align_x(iced::alignment::Horizontal::Left)
-        .width(Length::Units(214))
-        .height(Length::Units(465))
-        .max_width(214)
-        .max_height(465)
-        .style(styling::OscillatorsContainer);
+struct LfoRanges {
+    rate: FreqRange,
+    depth: FloatRange,
+    phase: FloatRange,
+}
+
+struct LfoPanel {
+    waveform_state: pick_list::State<LfoWaveform>,
+    waveform_selected: Option<LfoWaveform>,
+    waveform_label: String,
+    destination_state: pick_list::State<Destination>,
+    destination_selected: Option<Destination>,
+    destination_label: String,
+    rate_state: knob::State,
+    rate_label: String,
+    depth_state: knob::State,
+    depth_label: String,
+    phase_state: knob::State,
+    phase_label: String,
+    retrigger: bool,
+}
+
+impl LfoPanel {
+    fn new(lfo: &Lfo, ranges: &LfoRanges) -> LfoPanel {
+        LfoPanel {
+            waveform_state: pick_list::State::default(),
+            waveform_selected: Some(lfo.waveform),
+            waveform_label: format!("Waveform"),
+            destination_state: pick_list::State::default(),
+            destination_selected: Some(lfo.destination),
+            destination_label: format!("Destination"),
+            rate_state: knob::State::new(ranges.rate.normal_param(lfo.rate as f32, 5.0)),
+            rate_label: format!("Rate\n{:.2} Hz", lfo.rate),
+            depth_state: knob::State::new(ranges.depth.normal_param(lfo.depth as f32, 0.0)),
+            depth_label: format!("Depth\n{:.0} %", lfo.depth * 100.0),
+            phase_state: knob::State::new(ranges.phase.normal_param(lfo.phase as f32, 0.0)),
+            phase_label: format!("Phase\n{:.0}°", lfo.phase * 360.0),
+            retrigger: lfo.retrigger,
+        }
+    }
+
+    fn view(&mut self, index: usize) -> Element<Message> {
+        let waveform = PickList::new(
+            &mut self.waveform_state,
+            &LfoWaveform::ALL[..],
+            self.waveform_selected,
+            move |waveform| Message::LfoWaveformSelected(index, waveform),
+        );
+
+        let destination = PickList::new(
+            &mut self.destination_state,
+            &Destination::ALL[..],
+            self.destination_selected,
+            move |destination| Message::LfoDestinationSelected(index, destination),
+        );
+
+        let rate = Knob::new(
+            &mut self.rate_state,
+            move |normal| Message::LfoRate(index, normal),
+            || None,
+            || None,
+        );
 
-        let f1_cutoff = Knob::new(
-            &mut self.f1_cutoff_state,
-            Message::CutoffF1,
+        let depth = Knob::new(
+            &mut self.depth_state,
+            move |normal| Message::LfoDepth(index, normal),
             || None,
             || None,
         );
 
-        let f1_resonance = Knob::new(
-            &mut self.f1_resonance_state,
-            Message::ResonanceF1,
+        let phase = Knob::new(
+            &mut self.phase_state,
+            move |normal| Message::LfoPhase(index, normal),
             || None,
             || None,
         );
 
-        let f1 = Container::new(
+        let retrigger = Checkbox::new(self.retrigger, "Retrigger", move |retrigger| {
+            Message::LfoRetrigger(index, retrigger)
+        })
+        .size(12)
+        .text_size(12);
+
+        Container::new(
             Column::new()
                 .align_items(Alignment::Center)
                 .spacing(5)
                 .padding(5)
-                .push(Text::new("Filter 1").size(12))
+                .push(Text::new(format!("LFO {}", index + 1)).size(12))
+                .push(
+                    Column::new()
+                        .push(Text::new(&self.waveform_label).size(12))
+                        .push(waveform),
+                )
+                .push(
+                    Column::new()
+                        .push(Text::new(&self.destination_label).size(12))
+                        .push(destination),
+                )
                 .push(
                     Row::new()
                         .spacing(8)
@@ -585,74 +949,2803 @@ impl Sandbox for App {
                         .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.f1_cutoff_label).size(12))
-                                .push(f1_cutoff),
+                                .push(Text::new(&self.rate_label).size(12))
+                                .push(rate),
                         )
                         .push(
                             Column::new()
-                                .push(Text::new(&self.f1_resonance_label).size(12))
-                                .push(f1_resonance),
+                                .push(Text::new(&self.depth_label).size(12))
+                                .push(depth),
                         ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.phase_label).size(12))
+                                .push(phase),
+                        )
+                        .push(retrigger),
                 ),
         )
         .style(styling::GroupContainer)
-        .width(Length::Fill);
+        .width(Length::Fill)
+        .into()
+    }
+}
 
-        let f2_cutoff = Knob::new(
-            &mut self.f2_cutoff_state,
-            Message::CutoffF2,
-            || None,
-            || None,
-        );
+struct ModSlotPanel {
+    source_state: pick_list::State<ModSource>,
+    source_selected: Option<ModSource>,
+    destination_state: pick_list::State<Destination>,
+    destination_selected: Option<Destination>,
+    amount_state: knob::State,
+    amount_label: String,
+}
+
+impl ModSlotPanel {
+    fn new(slot: &ModSlot, amount_range: &FloatRange) -> ModSlotPanel {
+        ModSlotPanel {
+            source_state: pick_list::State::default(),
+            source_selected: Some(slot.source),
+            destination_state: pick_list::State::default(),
+            destination_selected: Some(slot.destination),
+            amount_state: knob::State::new(amount_range.normal_param(slot.amount as f32, 0.0)),
+            amount_label: format!("{:+.0} %", slot.amount * 100.0),
+        }
+    }
+
+    fn view(&mut self, index: usize) -> Element<Message> {
+        let source = PickList::new(
+            &mut self.source_state,
+            &ModSource::ALL[..],
+            self.source_selected,
+            move |source| Message::ModSourceSelected(index, source),
+        )
+        .text_size(12);
 
-        let f2_resonance = Knob::new(
-            &mut self.f2_resonance_state,
-            Message::ResonanceF2,
+        let destination = PickList::new(
+            &mut self.destination_state,
+            &Destination::ALL[..],
+            self.destination_selected,
+            move |destination| Message::ModDestinationSelected(index, destination),
+        )
+        .text_size(12);
+
+        let amount = Knob::new(
+            &mut self.amount_state,
+            move |normal| Message::ModAmount(index, normal),
             || None,
             || None,
         );
 
-        let f2 = Container::new(
+        Container::new(
+            Row::new()
+                .spacing(20)
+                .padding(5)
+                .width(Length::Fill)
+                .align_items(Alignment::Center)
+                .push(Text::new(format!("{}", index + 1)).size(12))
+                .push(
+                    Column::new()
+                        .width(Length::Units(150))
+                        .push(Text::new("Source").size(12))
+                        .push(source),
+                )
+                .push(Text::new(">").size(12))
+                .push(
+                    Column::new()
+                        .width(Length::Units(150))
+                        .push(Text::new("Destination").size(12))
+                        .push(destination),
+                )
+                .push(
+                    Column::new()
+                        .push(Text::new(format!("Amount\n{}", self.amount_label)).size(12))
+                        .push(amount),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct VoicingPanel {
+    mode_state: pick_list::State<VoiceMode>,
+    mode_selected: Option<VoiceMode>,
+    priority_state: pick_list::State<NotePriority>,
+    priority_selected: Option<NotePriority>,
+    glide_state: knob::State,
+    glide_label: String,
+    curve_state: pick_list::State<GlideCurve>,
+    curve_selected: Option<GlideCurve>,
+}
+
+impl VoicingPanel {
+    fn new(voicing: &VoicingSettings, glide_range: &FloatRange) -> VoicingPanel {
+        VoicingPanel {
+            mode_state: pick_list::State::default(),
+            mode_selected: Some(voicing.mode),
+            priority_state: pick_list::State::default(),
+            priority_selected: Some(voicing.priority),
+            glide_state: knob::State::new(glide_range.normal_param(voicing.glide as f32, 0.0)),
+            glide_label: format!("Glide\n{:.2} s", voicing.glide),
+            curve_state: pick_list::State::default(),
+            curve_selected: Some(voicing.glide_curve),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let mode = PickList::new(
+            &mut self.mode_state,
+            &VoiceMode::ALL[..],
+            self.mode_selected,
+            Message::VoiceModeSelected,
+        )
+        .text_size(12);
+        let priority = PickList::new(
+            &mut self.priority_state,
+            &NotePriority::ALL[..],
+            self.priority_selected,
+            Message::NotePrioritySelected,
+        )
+        .text_size(12);
+        let glide = Knob::new(&mut self.glide_state, Message::Glide, || None, || None);
+        let curve = PickList::new(
+            &mut self.curve_state,
+            &GlideCurve::ALL[..],
+            self.curve_selected,
+            Message::GlideCurveSelected,
+        )
+        .text_size(12);
+
+        Container::new(
             Column::new()
                 .align_items(Alignment::Center)
                 .spacing(5)
                 .padding(5)
-                .push(Text::new("Filter 2").size(12))
+                .push(Text::new("Voices").size(12))
                 .push(
                     Row::new()
                         .spacing(8)
                         .width(Length::Fill)
                         .align_items(Alignment::Center)
+                        .push(Column::new().push(Text::new("Mode").size(12)).push(mode))
                         .push(
                             Column::new()
-                                .push(Text::new(&self.f2_cutoff_label).size(12))
-                                .push(f2_cutoff),
-                        )
+                                .push(Text::new("Priority").size(12))
+                                .push(priority),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
                         .push(
                             Column::new()
-                                .push(Text::new(&self.f2_resonance_label).size(12))
-                                .push(f2_resonance),
-                        ),
+                                .push(Text::new(&self.glide_label).size(12))
+                                .push(glide),
+                        )
+                        .push(Column::new().push(Text::new("Curve").size(12)).push(curve)),
                 ),
         )
         .style(styling::GroupContainer)
-        .width(Length::Fill);
+        .width(Length::Fill)
+        .into()
+    }
+}
 
-        let filters_container = Container::new(Container::new(
-            Row::new()
+struct ArpPanel {
+    enabled: bool,
+    latch: bool,
+    mode_state: pick_list::State<ArpMode>,
+    mode_selected: Option<ArpMode>,
+    rate_state: pick_list::State<NoteDivision>,
+    rate_selected: Option<NoteDivision>,
+    octaves_state: knob::State,
+    octaves_label: String,
+    gate_state: knob::State,
+    gate_label: String,
+    swing_state: knob::State,
+    swing_label: String,
+}
+
+impl ArpPanel {
+    fn new(arp: &ArpSettings, octaves_range: &FloatRange, unit_range: &FloatRange) -> ArpPanel {
+        ArpPanel {
+            enabled: arp.enabled,
+            latch: arp.latch,
+            mode_state: pick_list::State::default(),
+            mode_selected: Some(arp.mode),
+            rate_state: pick_list::State::default(),
+            rate_selected: Some(arp.rate),
+            octaves_state: knob::State::new(octaves_range.normal_param(arp.octaves as f32, 1.0)),
+            octaves_label: format!("Octaves\n{}", arp.octaves),
+            gate_state: knob::State::new(unit_range.normal_param(arp.gate as f32, 0.5)),
+            gate_label: format!("Gate\n{:.0} %", arp.gate * 100.0),
+            swing_state: knob::State::new(unit_range.normal_param(arp.swing as f32, 0.0)),
+            swing_label: format!("Swing\n{:.0} %", arp.swing * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let mode = PickList::new(
+            &mut self.mode_state,
+            &ArpMode::ALL[..],
+            self.mode_selected,
+            Message::ArpModeSelected,
+        )
+        .text_size(12);
+        let rate = PickList::new(
+            &mut self.rate_state,
+            &NoteDivision::ALL[..],
+            self.rate_selected,
+            Message::ArpRateSelected,
+        )
+        .text_size(12);
+        let octaves = Knob::new(
+            &mut self.octaves_state,
+            Message::ArpOctaves,
+            || None,
+            || None,
+        );
+        let gate = Knob::new(&mut self.gate_state, Message::ArpGate, || None, || None);
+        let swing = Knob::new(&mut self.swing_state, Message::ArpSwing, || None, || None);
+
+        Container::new(
+            Column::new()
                 .align_items(Alignment::Center)
-                .push(Image::new(image::Handle::from_path(format!(
-                    "{}/assets/filters_text.png",
-                    env!("CARGO_MANIFEST_DIR")
-                ))))
+                .spacing(5)
+                .padding(5)
+                .push(Text::new("Arpeggiator").size(12))
                 .push(
-                    Column::new()
-                        .spacing(20)
-                        .padding(20)
-                        .align_items(Alignment::Start)
-                        .push(f1)
-                        .push(f2),
-                ),
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Checkbox::new(self.enabled, "On", Message::ArpToggled)
+                                .size(12)
+                                .text_size(12),
+                        )
+                        .push(
+                            Checkbox::new(self.latch, "Latch", Message::ArpLatchToggled)
+                                .size(12)
+                                .text_size(12),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(Column::new().push(Text::new("Mode").size(12)).push(mode))
+                        .push(Column::new().push(Text::new("Rate").size(12)).push(rate)),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.octaves_label).size(12))
+                                .push(octaves),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.gate_label).size(12))
+                                .push(gate),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.swing_label).size(12))
+                                .push(swing),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct SequencerRanges {
+    note: FloatRange,
+    velocity: FloatRange,
+    gate: FloatRange,
+    /// Position of the lock knob, which the locked parameter maps to its own units.
+    lock: FloatRange,
+}
+
+/// Grid of steps, with the controls of the selected step below it.
+struct SequencerPanel {
+    playing: bool,
+    length_state: pick_list::State<usize>,
+    length_selected: Option<usize>,
+    rate_state: pick_list::State<NoteDivision>,
+    rate_selected: Option<NoteDivision>,
+    step_states: Vec<button::State>,
+    /// Copy of the steps for drawing the grid.
+    steps: [Step; MAX_STEPS],
+    selected: usize,
+    note_state: knob::State,
+    note_label: String,
+    velocity_state: knob::State,
+    velocity_label: String,
+    gate_state: knob::State,
+    gate_label: String,
+    /// Parameter whose lock on the selected step is shown.
+    lock_parameter_state: pick_list::State<LockParameter>,
+    lock_parameter_selected: Option<LockParameter>,
+    lock_state: knob::State,
+    lock_label: String,
+}
+
+impl SequencerPanel {
+    fn new(sequencer: &SequencerSettings, ranges: &SequencerRanges) -> SequencerPanel {
+        let mut panel = SequencerPanel {
+            playing: sequencer.playing,
+            length_state: pick_list::State::default(),
+            length_selected: Some(sequencer.length),
+            rate_state: pick_list::State::default(),
+            rate_selected: Some(sequencer.rate),
+            step_states: (0..MAX_STEPS).map(|_| button::State::default()).collect(),
+            steps: sequencer.steps,
+            selected: 0,
+            note_state: knob::State::default(),
+            note_label: String::new(),
+            velocity_state: knob::State::default(),
+            velocity_label: String::new(),
+            gate_state: knob::State::default(),
+            gate_label: String::new(),
+            lock_parameter_state: pick_list::State::default(),
+            lock_parameter_selected: Some(LockParameter::default()),
+            lock_state: knob::State::default(),
+            lock_label: String::new(),
+        };
+        panel.select(0, ranges);
+        panel
+    }
+
+    /// Points the step controls at another step.
+    fn select(&mut self, index: usize, ranges: &SequencerRanges) {
+        let step = self.steps[index];
+        self.selected = index;
+        self.note_state = knob::State::new(ranges.note.normal_param(step.note as f32, 48.0));
+        self.note_label = format!("Note\n{}", note_name(step.note));
+        self.velocity_state =
+            knob::State::new(ranges.velocity.normal_param(step.velocity as f32, 100.0));
+        self.velocity_label = format!("Velocity\n{}", step.velocity);
+        self.gate_state = knob::State::new(ranges.gate.normal_param(step.gate as f32, 0.5));
+        self.gate_label = format!("Gate\n{:.0} %", step.gate * 100.0);
+    }
+
+    /// Shows the lock of the selected parameter on the selected step. An unlocked knob sits at
+    /// the value of the patch, where a new lock starts.
+    fn show_lock(&mut self, patch: f64, ranges: &SequencerRanges) {
+        let parameter = self.lock_parameter_selected.unwrap_or_default();
+        let lock = self.steps[self.selected].locks[parameter as usize];
+        let normal = parameter.normal(lock.unwrap_or(patch)) as f32;
+        self.lock_state = knob::State::new(ranges.lock.normal_param(normal, normal));
+        self.lock_label = lock_label(parameter, lock);
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let length = self.length_selected.unwrap_or(MAX_STEPS);
+        let selected = self.selected;
+        let steps = self.steps;
+
+        let mut grid = Column::new().spacing(4);
+        let mut row = Row::new().spacing(2);
+        for (i, state) in self.step_states.iter_mut().enumerate().take(length) {
+            let step = &steps[i];
+            let mut name = if !step.active {
+                "-".to_string()
+            } else if step.tie {
+                "~".to_string()
+            } else {
+                note_name(step.note)
+            };
+            if step.locks.iter().any(Option::is_some) {
+                name.push('*');
+            }
+            let label = if i == selected {
+                format!("[{}]", name)
+            } else {
+                name
+            };
+            row = row.push(
+                Button::new(state, Text::new(label).size(10))
+                    .width(Length::Units(34))
+                    .on_press(Message::SequencerStepSelected(i)),
+            );
+            if i % 16 == 15 {
+                grid = grid.push(row);
+                row = Row::new().spacing(2);
+            }
+        }
+
+        let length_picker = PickList::new(
+            &mut self.length_state,
+            &STEP_COUNTS[..],
+            self.length_selected,
+            Message::SequencerLengthSelected,
+        )
+        .text_size(12);
+        let rate = PickList::new(
+            &mut self.rate_state,
+            &NoteDivision::ALL[..],
+            self.rate_selected,
+            Message::SequencerRateSelected,
+        )
+        .text_size(12);
+        let header = Row::new()
+            .spacing(8)
+            .align_items(Alignment::Center)
+            .push(
+                Checkbox::new(self.playing, "Play", Message::SequencerPlayToggled)
+                    .size(12)
+                    .text_size(12),
+            )
+            .push(
+                Column::new()
+                    .push(Text::new("Steps").size(12))
+                    .push(length_picker),
+            )
+            .push(Column::new().push(Text::new("Rate").size(12)).push(rate));
+
+        let step = &steps[selected];
+        let note = Knob::new(&mut self.note_state, Message::StepNote, || None, || None);
+        let velocity = Knob::new(
+            &mut self.velocity_state,
+            Message::StepVelocity,
+            || None,
+            || None,
+        );
+        let gate = Knob::new(&mut self.gate_state, Message::StepGate, || None, || None);
+        let parameter = self.lock_parameter_selected.unwrap_or_default();
+        let lock_parameter = PickList::new(
+            &mut self.lock_parameter_state,
+            &LockParameter::ALL[..],
+            self.lock_parameter_selected,
+            Message::SequencerLockParameterSelected,
+        )
+        .text_size(12);
+        let lock = Knob::new(&mut self.lock_state, Message::StepLock, || None, || None);
+        let editor = Row::new()
+            .spacing(12)
+            .align_items(Alignment::Center)
+            .push(Text::new(format!("Step {}", selected + 1)).size(12))
+            .push(
+                Column::new()
+                    .spacing(5)
+                    .push(
+                        Checkbox::new(step.active, "On", Message::StepActiveToggled)
+                            .size(12)
+                            .text_size(12),
+                    )
+                    .push(
+                        Checkbox::new(step.tie, "Tie", Message::StepTieToggled)
+                            .size(12)
+                            .text_size(12),
+                    ),
+            )
+            .push(
+                Column::new()
+                    .push(Text::new(&self.note_label).size(12))
+                    .push(note),
+            )
+            .push(
+                Column::new()
+                    .push(Text::new(&self.velocity_label).size(12))
+                    .push(velocity),
+            )
+            .push(
+                Column::new()
+                    .push(Text::new(&self.gate_label).size(12))
+                    .push(gate),
+            )
+            .push(
+                Column::new()
+                    .spacing(5)
+                    .push(
+                        Checkbox::new(
+                            step.locks[parameter as usize].is_some(),
+                            "Lock",
+                            Message::StepLockToggled,
+                        )
+                        .size(12)
+                        .text_size(12),
+                    )
+                    .push(lock_parameter),
+            )
+            .push(
+                Column::new()
+                    .push(Text::new(&self.lock_label).size(12))
+                    .push(lock),
+            );
+
+        Container::new(
+            Column::new()
+                .spacing(15)
+                .padding(5)
+                .push(header)
+                .push(grid)
+                .push(editor),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+fn lock_label(parameter: LockParameter, lock: Option<f64>) -> String {
+    let value = match lock {
+        Some(value) => value,
+        None => return "Lock\noff".to_string(),
+    };
+    match parameter {
+        LockParameter::Cutoff1 | LockParameter::Cutoff2 => format!("Lock\n{:.0} Hz", value),
+        LockParameter::Resonance1 | LockParameter::Resonance2 => format!("Lock\n{:.0} %", value),
+        LockParameter::EnvelopeAmount1 | LockParameter::EnvelopeAmount2 => {
+            format!("Lock\n{:+.1} oct", value)
+        }
+        LockParameter::Drive1
+        | LockParameter::Drive2
+        | LockParameter::FilterBalance
+        | LockParameter::FmIndex => format!("Lock\n{:.2}", value),
+    }
+}
+
+/// Header of a slot in the effect chain, above the panel of its effect.
+struct EffectSlotPanel {
+    kind: EffectKind,
+    bypass: bool,
+    up_state: button::State,
+    down_state: button::State,
+    move_state: button::State,
+}
+
+impl EffectSlotPanel {
+    fn new(slot: &EffectSlot) -> EffectSlotPanel {
+        EffectSlotPanel {
+            kind: slot.kind,
+            bypass: slot.bypass,
+            up_state: button::State::default(),
+            down_state: button::State::default(),
+            move_state: button::State::default(),
+        }
+    }
+
+    fn view<'a>(
+        &'a mut self,
+        index: usize,
+        count: usize,
+        picked: Option<usize>,
+        panel: Element<'a, Message>,
+    ) -> Element<'a, Message> {
+        let mut up = Button::new(&mut self.up_state, Text::new("Up").size(12));
+        if index > 0 {
+            up = up.on_press(Message::EffectMoved(index, index - 1));
+        }
+        let mut down = Button::new(&mut self.down_state, Text::new("Down").size(12));
+        if index + 1 < count {
+            down = down.on_press(Message::EffectMoved(index, index + 1));
+        }
+        // While a slot is picked up, every other slot is a place to drop it
+        let (label, message) = match picked {
+            None => ("Move", Message::EffectPicked(index)),
+            Some(from) if from == index => ("Cancel", Message::EffectPicked(index)),
+            Some(_) => ("Here", Message::EffectDropped(index)),
+        };
+        let move_button =
+            Button::new(&mut self.move_state, Text::new(label).size(12)).on_press(message);
+
+        Column::new()
+            .spacing(5)
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(self.kind.to_string()).size(12))
+                    .push(up)
+                    .push(down),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Checkbox::new(self.bypass, "Bypass", move |bypass| {
+                            Message::EffectBypassToggled(index, bypass)
+                        })
+                        .size(12)
+                        .text_size(12),
+                    )
+                    .push(move_button),
+            )
+            .push(panel)
+            .into()
+    }
+}
+
+struct DistortionPanel {
+    placement_state: pick_list::State<DistortionPlacement>,
+    placement_selected: Option<DistortionPlacement>,
+    curve_state: pick_list::State<DistortionCurve>,
+    curve_selected: Option<DistortionCurve>,
+    drive_state: knob::State,
+    drive_label: String,
+    bits_state: knob::State,
+    bits_label: String,
+    downsample_state: knob::State,
+    downsample_label: String,
+    mix_state: knob::State,
+    mix_label: String,
+}
+
+impl DistortionPanel {
+    fn new(
+        distortion: &DistortionSettings,
+        unit_range: &FloatRange,
+        bits_range: &FloatRange,
+        downsample_range: &FloatRange,
+    ) -> DistortionPanel {
+        DistortionPanel {
+            placement_state: pick_list::State::default(),
+            placement_selected: Some(distortion.placement),
+            curve_state: pick_list::State::default(),
+            curve_selected: Some(distortion.curve),
+            drive_state: knob::State::new(unit_range.normal_param(distortion.drive as f32, 0.0)),
+            drive_label: format!("Drive\n{:.0} %", distortion.drive * 100.0),
+            bits_state: knob::State::new(bits_range.normal_param(distortion.bits as f32, 16.0)),
+            bits_label: format!("Bits\n{:.1}", distortion.bits),
+            downsample_state: knob::State::new(
+                downsample_range.normal_param(distortion.downsample as f32, 1.0),
+            ),
+            downsample_label: format!("Downsample\n{:.1}x", distortion.downsample),
+            mix_state: knob::State::new(unit_range.normal_param(distortion.mix as f32, 0.0)),
+            mix_label: format!("Mix\n{:.0} %", distortion.mix * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let placement = PickList::new(
+            &mut self.placement_state,
+            &DistortionPlacement::ALL[..],
+            self.placement_selected,
+            Message::DistortionPlacementSelected,
+        )
+        .text_size(12);
+        let curve = PickList::new(
+            &mut self.curve_state,
+            &DistortionCurve::ALL[..],
+            self.curve_selected,
+            Message::DistortionCurveSelected,
+        )
+        .text_size(12);
+        let drive = Knob::new(
+            &mut self.drive_state,
+            Message::DistortionDrive,
+            || None,
+            || None,
+        );
+        let bits = Knob::new(
+            &mut self.bits_state,
+            Message::DistortionBits,
+            || None,
+            || None,
+        );
+        let downsample = Knob::new(
+            &mut self.downsample_state,
+            Message::DistortionDownsample,
+            || None,
+            || None,
+        );
+        let mix = Knob::new(
+            &mut self.mix_state,
+            Message::DistortionMix,
+            || None,
+            || None,
+        );
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(curve)
+                        .push(placement),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.drive_label).size(12))
+                                .push(drive),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.mix_label).size(12))
+                                .push(mix),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.bits_label).size(12))
+                                .push(bits),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.downsample_label).size(12))
+                                .push(downsample),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct ModulationPanel {
+    type_state: pick_list::State<ModulationType>,
+    type_selected: Option<ModulationType>,
+    rate_state: knob::State,
+    rate_label: String,
+    depth_state: knob::State,
+    depth_label: String,
+    feedback_state: knob::State,
+    feedback_label: String,
+    mix_state: knob::State,
+    mix_label: String,
+}
+
+impl ModulationPanel {
+    fn new(
+        modulation: &ModulationSettings,
+        rate_range: &FreqRange,
+        unit_range: &FloatRange,
+    ) -> ModulationPanel {
+        ModulationPanel {
+            type_state: pick_list::State::default(),
+            type_selected: Some(modulation.modulation_type),
+            rate_state: knob::State::new(rate_range.normal_param(modulation.rate as f32, 0.8)),
+            rate_label: format!("Rate\n{:.2} Hz", modulation.rate),
+            depth_state: knob::State::new(unit_range.normal_param(modulation.depth as f32, 0.5)),
+            depth_label: format!("Depth\n{:.0} %", modulation.depth * 100.0),
+            feedback_state: knob::State::new(
+                unit_range.normal_param(modulation.feedback as f32, 0.0),
+            ),
+            feedback_label: format!("Feedback\n{:.0} %", modulation.feedback * 100.0),
+            mix_state: knob::State::new(unit_range.normal_param(modulation.mix as f32, 0.0)),
+            mix_label: format!("Mix\n{:.0} %", modulation.mix * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let modulation_type = PickList::new(
+            &mut self.type_state,
+            &ModulationType::ALL[..],
+            self.type_selected,
+            Message::ModulationTypeSelected,
+        )
+        .text_size(12);
+        let rate = Knob::new(
+            &mut self.rate_state,
+            Message::ModulationRate,
+            || None,
+            || None,
+        );
+        let depth = Knob::new(
+            &mut self.depth_state,
+            Message::ModulationDepth,
+            || None,
+            || None,
+        );
+        let feedback = Knob::new(
+            &mut self.feedback_state,
+            Message::ModulationFeedback,
+            || None,
+            || None,
+        );
+        let mix = Knob::new(
+            &mut self.mix_state,
+            Message::ModulationMix,
+            || None,
+            || None,
+        );
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(modulation_type)
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.rate_label).size(12))
+                                .push(rate),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.depth_label).size(12))
+                                .push(depth),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.feedback_label).size(12))
+                                .push(feedback),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.mix_label).size(12))
+                                .push(mix),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct DelayPanel {
+    sync: bool,
+    time_state: knob::State,
+    time_label: String,
+    division_state: pick_list::State<NoteDivision>,
+    division_selected: Option<NoteDivision>,
+    feedback_state: knob::State,
+    feedback_label: String,
+    high_cut_state: knob::State,
+    high_cut_label: String,
+    ping_pong: bool,
+    mix_state: knob::State,
+    mix_label: String,
+}
+
+impl DelayPanel {
+    fn new(
+        delay: &DelaySettings,
+        time_range: &FloatRange,
+        unit_range: &FloatRange,
+        high_cut_range: &FreqRange,
+    ) -> DelayPanel {
+        DelayPanel {
+            sync: delay.sync,
+            time_state: knob::State::new(time_range.normal_param(delay.time as f32, 0.3)),
+            time_label: format!("Time\n{:.0} ms", delay.time * 1000.0),
+            division_state: pick_list::State::default(),
+            division_selected: Some(delay.division),
+            feedback_state: knob::State::new(unit_range.normal_param(delay.feedback as f32, 0.4)),
+            feedback_label: format!("Feedback\n{:.0} %", delay.feedback * 100.0),
+            high_cut_state: knob::State::new(
+                high_cut_range.normal_param(delay.high_cut as f32, 8000.0),
+            ),
+            high_cut_label: format!("High Cut\n{:.0} Hz", delay.high_cut),
+            ping_pong: delay.ping_pong,
+            mix_state: knob::State::new(unit_range.normal_param(delay.mix as f32, 0.0)),
+            mix_label: format!("Mix\n{:.0} %", delay.mix * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        // A synced delay is set as a note length instead of a time
+        let time: Element<Message> = if self.sync {
+            Column::new()
+                .push(Text::new("Division").size(12))
+                .push(
+                    PickList::new(
+                        &mut self.division_state,
+                        &NoteDivision::ALL[..],
+                        self.division_selected,
+                        Message::DelayDivisionSelected,
+                    )
+                    .text_size(12),
+                )
+                .into()
+        } else {
+            Column::new()
+                .push(Text::new(&self.time_label).size(12))
+                .push(Knob::new(
+                    &mut self.time_state,
+                    Message::DelayTime,
+                    || None,
+                    || None,
+                ))
+                .into()
+        };
+        let feedback = Knob::new(
+            &mut self.feedback_state,
+            Message::DelayFeedback,
+            || None,
+            || None,
+        );
+        let high_cut = Knob::new(
+            &mut self.high_cut_state,
+            Message::DelayHighCut,
+            || None,
+            || None,
+        );
+        let mix = Knob::new(&mut self.mix_state, Message::DelayMix, || None, || None);
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Checkbox::new(self.sync, "Sync", Message::DelaySyncToggled)
+                                .size(12)
+                                .text_size(12),
+                        )
+                        .push(
+                            Checkbox::new(
+                                self.ping_pong,
+                                "Ping-Pong",
+                                Message::DelayPingPongToggled,
+                            )
+                            .size(12)
+                            .text_size(12),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(time)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.feedback_label).size(12))
+                                .push(feedback),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.high_cut_label).size(12))
+                                .push(high_cut),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.mix_label).size(12))
+                                .push(mix),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct ReverbPanel {
+    size_state: knob::State,
+    size_label: String,
+    damping_state: knob::State,
+    damping_label: String,
+    pre_delay_state: knob::State,
+    pre_delay_label: String,
+    width_state: knob::State,
+    width_label: String,
+    mix_state: knob::State,
+    mix_label: String,
+}
+
+impl ReverbPanel {
+    fn new(
+        reverb: &ReverbSettings,
+        unit_range: &FloatRange,
+        pre_delay_range: &FloatRange,
+    ) -> ReverbPanel {
+        ReverbPanel {
+            size_state: knob::State::new(unit_range.normal_param(reverb.size as f32, 0.5)),
+            size_label: format!("Size\n{:.0} %", reverb.size * 100.0),
+            damping_state: knob::State::new(unit_range.normal_param(reverb.damping as f32, 0.5)),
+            damping_label: format!("Damping\n{:.0} %", reverb.damping * 100.0),
+            pre_delay_state: knob::State::new(
+                pre_delay_range.normal_param(reverb.pre_delay as f32, 0.0),
+            ),
+            pre_delay_label: format!("Pre-Delay\n{:.0} ms", reverb.pre_delay * 1000.0),
+            width_state: knob::State::new(unit_range.normal_param(reverb.width as f32, 1.0)),
+            width_label: format!("Width\n{:.0} %", reverb.width * 100.0),
+            mix_state: knob::State::new(unit_range.normal_param(reverb.mix as f32, 0.0)),
+            mix_label: format!("Mix\n{:.0} %", reverb.mix * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let size = Knob::new(&mut self.size_state, Message::ReverbSize, || None, || None);
+        let damping = Knob::new(
+            &mut self.damping_state,
+            Message::ReverbDamping,
+            || None,
+            || None,
+        );
+        let pre_delay = Knob::new(
+            &mut self.pre_delay_state,
+            Message::ReverbPreDelay,
+            || None,
+            || None,
+        );
+        let width = Knob::new(
+            &mut self.width_state,
+            Message::ReverbWidth,
+            || None,
+            || None,
+        );
+        let mix = Knob::new(&mut self.mix_state, Message::ReverbMix, || None, || None);
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.size_label).size(12))
+                                .push(size),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.damping_label).size(12))
+                                .push(damping),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.pre_delay_label).size(12))
+                                .push(pre_delay),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.width_label).size(12))
+                                .push(width),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.mix_label).size(12))
+                                .push(mix),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct ConvolutionPanel {
+    impulse_label: String,
+    file_path: String,
+    file_path_state: text_input::State,
+    load_file_state: button::State,
+    pre_delay_state: knob::State,
+    pre_delay_label: String,
+    high_cut_state: knob::State,
+    high_cut_label: String,
+    mix_state: knob::State,
+    mix_label: String,
+}
+
+impl ConvolutionPanel {
+    fn new(
+        convolution: &ConvolutionSettings,
+        unit_range: &FloatRange,
+        pre_delay_range: &FloatRange,
+        high_cut_range: &FreqRange,
+    ) -> ConvolutionPanel {
+        ConvolutionPanel {
+            impulse_label: match &convolution.kernel {
+                Some(kernel) => format!("Impulse\n{}", kernel.name),
+                None => format!("Impulse\nnone"),
+            },
+            file_path: String::new(),
+            file_path_state: text_input::State::default(),
+            load_file_state: button::State::default(),
+            pre_delay_state: knob::State::new(
+                pre_delay_range.normal_param(convolution.pre_delay as f32, 0.0),
+            ),
+            pre_delay_label: format!("Pre-Delay\n{:.0} ms", convolution.pre_delay * 1000.0),
+            high_cut_state: knob::State::new(
+                high_cut_range.normal_param(convolution.high_cut as f32, 20000.0),
+            ),
+            high_cut_label: format!("High Cut\n{:.0} Hz", convolution.high_cut),
+            mix_state: knob::State::new(unit_range.normal_param(convolution.mix as f32, 0.0)),
+            mix_label: format!("Mix\n{:.0} %", convolution.mix * 100.0),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let file_path = TextInput::new(
+            &mut self.file_path_state,
+            "path/to/impulse.wav",
+            &self.file_path,
+            Message::ImpulsePathChanged,
+        )
+        .size(12)
+        .padding(3)
+        .on_submit(Message::LoadImpulse);
+        let load_file = Button::new(&mut self.load_file_state, Text::new("Load").size(12))
+            .on_press(Message::LoadImpulse);
+        let pre_delay = Knob::new(
+            &mut self.pre_delay_state,
+            Message::ConvolutionPreDelay,
+            || None,
+            || None,
+        );
+        let high_cut = Knob::new(
+            &mut self.high_cut_state,
+            Message::ConvolutionHighCut,
+            || None,
+            || None,
+        );
+        let mix = Knob::new(
+            &mut self.mix_state,
+            Message::ConvolutionMix,
+            || None,
+            || None,
+        );
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(Text::new(&self.impulse_label).size(12))
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(file_path)
+                        .push(load_file),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.pre_delay_label).size(12))
+                                .push(pre_delay),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.high_cut_label).size(12))
+                                .push(high_cut),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.mix_label).size(12))
+                                .push(mix),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+struct EqRanges {
+    frequency: FreqRange,
+    gain: FloatRange,
+    q: FloatRange,
+}
+
+struct EqBandPanel {
+    frequency_state: knob::State,
+    frequency_label: String,
+    gain_state: knob::State,
+    gain_label: String,
+    q_state: knob::State,
+    q_label: String,
+}
+
+impl EqBandPanel {
+    fn new(eq: &EqSettings, i: usize, ranges: &EqRanges) -> EqBandPanel {
+        let band = &eq.bands[i];
+        EqBandPanel {
+            frequency_state: knob::State::new(
+                ranges
+                    .frequency
+                    .normal_param(band.frequency as f32, band.frequency as f32),
+            ),
+            frequency_label: format!("Freq\n{:.0} Hz", band.frequency),
+            gain_state: knob::State::new(ranges.gain.normal_param(band.gain as f32, 0.0)),
+            gain_label: format!("Gain\n{:+.1} dB", band.gain),
+            q_state: knob::State::new(ranges.q.normal_param(band.q as f32, 0.7)),
+            q_label: format!("Q\n{:.2}", band.q),
+        }
+    }
+
+    fn view(&mut self, i: usize) -> Element<Message> {
+        let frequency = Knob::new(
+            &mut self.frequency_state,
+            move |normal| Message::EqFrequency(i, normal),
+            || None,
+            || None,
+        );
+        let gain = Knob::new(
+            &mut self.gain_state,
+            move |normal| Message::EqGain(i, normal),
+            || None,
+            || None,
+        );
+        let q = Knob::new(
+            &mut self.q_state,
+            move |normal| Message::EqQ(i, normal),
+            || None,
+            || None,
+        );
+
+        Column::new()
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .push(Text::new(format!("Band {}", i + 1)).size(12))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .width(Length::Fill)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.frequency_label).size(12))
+                            .push(frequency),
+                    )
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.gain_label).size(12))
+                            .push(gain),
+                    )
+                    .push(
+                        Column::new()
+                            .push(Text::new(&self.q_label).size(12))
+                            .push(q),
+                    ),
+            )
+            .into()
+    }
+}
+
+struct CompressorRanges {
+    threshold: FloatRange,
+    ratio: FloatRange,
+    attack: FloatRange,
+    release: FloatRange,
+    makeup: FloatRange,
+    knee: FloatRange,
+}
+
+struct CompressorPanel {
+    threshold_state: knob::State,
+    threshold_label: String,
+    ratio_state: knob::State,
+    ratio_label: String,
+    attack_state: knob::State,
+    attack_label: String,
+    release_state: knob::State,
+    release_label: String,
+    makeup_state: knob::State,
+    makeup_label: String,
+    knee_state: knob::State,
+    knee_label: String,
+}
+
+impl CompressorPanel {
+    fn new(compressor: &CompressorSettings, ranges: &CompressorRanges) -> CompressorPanel {
+        CompressorPanel {
+            threshold_state: knob::State::new(
+                ranges
+                    .threshold
+                    .normal_param(compressor.threshold as f32, 0.0),
+            ),
+            threshold_label: format!("Threshold\n{:.1} dB", compressor.threshold),
+            ratio_state: knob::State::new(ranges.ratio.normal_param(compressor.ratio as f32, 1.0)),
+            ratio_label: format!("Ratio\n{:.1}:1", compressor.ratio),
+            attack_state: knob::State::new(
+                ranges.attack.normal_param(compressor.attack as f32, 0.01),
+            ),
+            attack_label: format!("Attack\n{:.1} ms", compressor.attack * 1000.0),
+            release_state: knob::State::new(
+                ranges.release.normal_param(compressor.release as f32, 0.1),
+            ),
+            release_label: format!("Release\n{:.0} ms", compressor.release * 1000.0),
+            makeup_state: knob::State::new(
+                ranges.makeup.normal_param(compressor.makeup as f32, 0.0),
+            ),
+            makeup_label: format!("Makeup\n{:+.1} dB", compressor.makeup),
+            knee_state: knob::State::new(ranges.knee.normal_param(compressor.knee as f32, 6.0)),
+            knee_label: format!("Knee\n{:.1} dB", compressor.knee),
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let threshold = Knob::new(
+            &mut self.threshold_state,
+            Message::CompressorThreshold,
+            || None,
+            || None,
+        );
+        let ratio = Knob::new(
+            &mut self.ratio_state,
+            Message::CompressorRatio,
+            || None,
+            || None,
+        );
+        let attack = Knob::new(
+            &mut self.attack_state,
+            Message::CompressorAttack,
+            || None,
+            || None,
+        );
+        let release = Knob::new(
+            &mut self.release_state,
+            Message::CompressorRelease,
+            || None,
+            || None,
+        );
+        let makeup = Knob::new(
+            &mut self.makeup_state,
+            Message::CompressorMakeup,
+            || None,
+            || None,
+        );
+        let knee = Knob::new(
+            &mut self.knee_state,
+            Message::CompressorKnee,
+            || None,
+            || None,
+        );
+
+        Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.threshold_label).size(12))
+                                .push(threshold),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.ratio_label).size(12))
+                                .push(ratio),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.attack_label).size(12))
+                                .push(attack),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.release_label).size(12))
+                                .push(release),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.makeup_label).size(12))
+                                .push(makeup),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.knee_label).size(12))
+                                .push(knee),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill)
+        .into()
+    }
+}
+
+pub struct App {
+    _midi_msgs: Arc<SegQueue<MidiMsg>>,
+    _connection: Option<MidiInputConnection<()>>,
+    oscillators: Arc<RwLock<Vec<Oscillator>>>,
+    filters: Vec<Arc<RwLock<Filter>>>,
+    routing: Arc<RwLock<Routing>>,
+    cross_mod: Arc<RwLock<CrossMod>>,
+    lfos: Vec<Arc<RwLock<Lfo>>>,
+    matrix: Arc<RwLock<Vec<ModSlot>>>,
+    mpe: Arc<RwLock<Mpe>>,
+    distortion: Arc<RwLock<DistortionSettings>>,
+    modulation: Arc<RwLock<ModulationSettings>>,
+    delay: Arc<RwLock<DelaySettings>>,
+    reverb: Arc<RwLock<ReverbSettings>>,
+    convolution: Arc<RwLock<ConvolutionSettings>>,
+    eq: Arc<RwLock<EqSettings>>,
+    compressor: Arc<RwLock<CompressorSettings>>,
+    chain: Arc<RwLock<EffectChain>>,
+    arpeggiator: Arc<RwLock<ArpSettings>>,
+    sequencer: Arc<RwLock<SequencerSettings>>,
+    voicing: Arc<RwLock<VoicingSettings>>,
+    tempo: Arc<RwLock<f64>>,
+    /// Sample rate of the output, which loaded impulse responses are resampled to.
+    sample_rate: f64,
+
+    // ui from here on out
+    oscillator_ranges: OscillatorRanges,
+    filter_ranges: FilterRanges,
+    lfo_ranges: LfoRanges,
+    mod_amount_range: FloatRange,
+    unit_range: FloatRange,
+    pre_delay_range: FloatRange,
+    tempo_range: FloatRange,
+    octaves_range: FloatRange,
+    bits_range: FloatRange,
+    downsample_range: FloatRange,
+    modulation_rate_range: FreqRange,
+    delay_time_range: FloatRange,
+    high_cut_range: FreqRange,
+    eq_ranges: EqRanges,
+    compressor_ranges: CompressorRanges,
+    sequencer_ranges: SequencerRanges,
+    glide_range: FloatRange,
+    fm_index_range: FloatRange,
+    balance_range: FloatRange,
+
+    // oscillators
+    oscillator_panels: Vec<OscillatorPanel>,
+    oscillators_scroll_state: scrollable::State,
+    add_oscillator_state: button::State,
+
+    // cross modulation
+    cross_mod_state: pick_list::State<CrossModulation>,
+    cross_mod_selected: Option<CrossModulation>,
+    cross_mod_label: String,
+    fm_index_state: knob::State,
+    fm_index_label: String,
+
+    // routing
+    routing_state: pick_list::State<FilterRouting>,
+    routing_selected: Option<FilterRouting>,
+    routing_label: String,
+    balance_state: knob::State,
+    balance_label: String,
+
+    // filters
+    filter_panels: Vec<FilterPanel>,
+    filters_scroll_state: scrollable::State,
+
+    // lfos
+    lfo_panels: Vec<LfoPanel>,
+
+    // modulation matrix
+    page: Page,
+    performance_button_state: button::State,
+    matrix_button_state: button::State,
+    sequencer_button_state: button::State,
+    back_button_state: button::State,
+    mod_slot_panels: Vec<ModSlotPanel>,
+    matrix_scroll_state: scrollable::State,
+
+    // step sequencer
+    sequencer_panel: SequencerPanel,
+
+    // voice modes
+    voicing_panel: VoicingPanel,
+
+    // effects
+    effects_scroll_state: scrollable::State,
+    tempo_state: knob::State,
+    tempo_label: String,
+    arp_panel: ArpPanel,
+    effect_slot_panels: Vec<EffectSlotPanel>,
+    /// Slot that has been picked up to move to another place in the chain.
+    picked_effect: Option<usize>,
+    distortion_panel: DistortionPanel,
+    modulation_panel: ModulationPanel,
+    delay_panel: DelayPanel,
+    reverb_panel: ReverbPanel,
+    convolution_panel: ConvolutionPanel,
+    eq_panels: Vec<EqBandPanel>,
+    compressor_panel: CompressorPanel,
+
+    // patch file
+    patch_path: String,
+    patch_path_state: text_input::State,
+    save_patch_state: button::State,
+    load_patch_state: button::State,
+}
+
+impl Sandbox for App {
+    type Message = Message;
+
+    fn title(&self) -> String {
+        "Hacksynth".to_string()
+    }
+
+    fn new() -> App {
+        let oscillators = Arc::new(RwLock::new(vec![
+            Oscillator::new(
+                crate::oscillator::Waveform::Square,
+                ADSR(0.11, 0.14, 0.47, 0.63),
+                0.0,
+                FilterSlot::Filter1,
+            ),
+            Oscillator::new(
+                crate::oscillator::Waveform::Sine,
+                ADSR(0.11, 0.14, 0.47, 0.63),
+                0.0,
+                FilterSlot::Filter2,
+            ),
+        ]));
+
+        let filters = vec![
+            Arc::new(RwLock::new(Filter::default())),
+            Arc::new(RwLock::new(Filter::default())),
+        ];
+        let routing = Arc::new(RwLock::new(Routing::default()));
+        let cross_mod = Arc::new(RwLock::new(CrossMod {
+            mode: CrossModulation::Off,
+            fm_index: 1.0,
+        }));
+
+        let lfos = vec![
+            Arc::new(RwLock::new(Lfo::default())),
+            Arc::new(RwLock::new(Lfo {
+                destination: Destination::Cutoff,
+                ..Lfo::default()
+            })),
+        ];
+
+        let matrix = Arc::new(RwLock::new(vec![ModSlot::default(); MOD_SLOTS]));
+
+        let mpe = Arc::new(RwLock::new(Mpe::default()));
+        let distortion = Arc::new(RwLock::new(DistortionSettings::default()));
+        let modulation = Arc::new(RwLock::new(ModulationSettings::default()));
+        let delay = Arc::new(RwLock::new(DelaySettings::default()));
+        let reverb = Arc::new(RwLock::new(ReverbSettings::default()));
+        let convolution = Arc::new(RwLock::new(ConvolutionSettings::default()));
+        let eq = Arc::new(RwLock::new(EqSettings::default()));
+        let compressor = Arc::new(RwLock::new(CompressorSettings::default()));
+        let chain = Arc::new(RwLock::new(EffectChain::default()));
+        let arpeggiator = Arc::new(RwLock::new(ArpSettings::default()));
+        let sequencer = Arc::new(RwLock::new(SequencerSettings::default()));
+        let voicing = Arc::new(RwLock::new(VoicingSettings::default()));
+        let tempo = Arc::new(RwLock::new(120.0));
+
+        let _midi_msgs = Arc::new(SegQueue::new());
+        // This has to be retained to ensure the connection is not dropped
+        let _connection = audio::run_midi(_midi_msgs.clone()).ok();
+        let sample_rate = audio::setup_output(
+            _midi_msgs.clone(),
+            audio::Parameters {
+                oscillators: oscillators.clone(),
+                filters: filters.clone(),
+                routing: routing.clone(),
+                cross_mod: cross_mod.clone(),
+                lfos: lfos.clone(),
+                matrix: matrix.clone(),
+                controllers: audio::Controllers::default(),
+                mpe: mpe.clone(),
+                distortion: distortion.clone(),
+                modulation: modulation.clone(),
+                delay: delay.clone(),
+                reverb: reverb.clone(),
+                convolution: convolution.clone(),
+                eq: eq.clone(),
+                compressor: compressor.clone(),
+                chain: chain.clone(),
+                arpeggiator: arpeggiator.clone(),
+                sequencer: sequencer.clone(),
+                voicing: voicing.clone(),
+                tempo: tempo.clone(),
+                epoch: Instant::now(),
+            },
+        );
+
+        let oscillator_ranges = OscillatorRanges {
+            detune: FloatRange::new(-100.0, 100.0),
+            adsr: FloatRange::new(0.0, 1.0),
+            root: FloatRange::new(0.0, 127.0),
+            position: FloatRange::new(0.0, 1.0),
+            crossfade: FloatRange::new(0.0, 0.5),
+        };
+        let filter_ranges = FilterRanges {
+            cutoff: FreqRange::default(),
+            resonance: FloatRange::new(0.0, 100.0),
+            adsr: FloatRange::new(0.0, 1.0),
+            amount: FloatRange::new(-5.0, 5.0),
+            velocity: FloatRange::new(0.0, 1.0),
+            key_tracking: FloatRange::new(0.0, 1.0),
+            drive: FloatRange::new(0.0, 1.0),
+        };
+        let lfo_ranges = LfoRanges {
+            rate: FreqRange::new(0.05, 20.0),
+            depth: FloatRange::new(0.0, 1.0),
+            phase: FloatRange::new(0.0, 1.0),
+        };
+        let mod_amount_range = FloatRange::new(-1.0, 1.0);
+        let unit_range = FloatRange::new(0.0, 1.0);
+        let pre_delay_range = FloatRange::new(0.0, 0.25);
+        let tempo_range = FloatRange::new(40.0, 240.0);
+        let octaves_range = FloatRange::new(1.0, 4.0);
+        let bits_range = FloatRange::new(1.0, 16.0);
+        let downsample_range = FloatRange::new(1.0, 32.0);
+        let modulation_rate_range = FreqRange::new(0.05, 10.0);
+        let delay_time_range = FloatRange::new(0.01, 2.0);
+        let high_cut_range = FreqRange::new(200.0, 20000.0);
+        let eq_ranges = EqRanges {
+            frequency: FreqRange::new(20.0, 20000.0),
+            gain: FloatRange::new(-18.0, 18.0),
+            q: FloatRange::new(0.1, 10.0),
+        };
+        let compressor_ranges = CompressorRanges {
+            threshold: FloatRange::new(-60.0, 0.0),
+            ratio: FloatRange::new(1.0, 20.0),
+            attack: FloatRange::new(0.0001, 0.1),
+            release: FloatRange::new(0.01, 1.0),
+            makeup: FloatRange::new(0.0, 24.0),
+            knee: FloatRange::new(0.0, 24.0),
+        };
+        let sequencer_ranges = SequencerRanges {
+            note: FloatRange::new(0.0, 127.0),
+            velocity: FloatRange::new(1.0, 127.0),
+            gate: FloatRange::new(0.05, 1.0),
+            lock: FloatRange::new(0.0, 1.0),
+        };
+        let glide_range = FloatRange::new(0.0, 2.0);
+        let fm_index_range = FloatRange::new(0.0, 10.0);
+        let balance_range = FloatRange::new(0.0, 1.0);
+
+        // oscillators state
+        let oscillator_panels = oscillators
+            .read()
+            .unwrap()
+            .iter()
+            .map(|o| OscillatorPanel::new(o, &oscillator_ranges))
+            .collect();
+        let oscillators_scroll_state = scrollable::State::new();
+        let add_oscillator_state = button::State::default();
+
+        // cross modulation state
+        let cross_mod_state = pick_list::State::default();
+        let cross_mod_selected = Some(cross_mod.read().unwrap().mode);
+        let cross_mod_label = format!("Mode");
+        let fm_index_state = knob::State::new(
+            fm_index_range.normal_param(cross_mod.read().unwrap().fm_index as f32, 0.0),
+        );
+        let fm_index_label = format!("FM Index\n{:.2}", cross_mod.read().unwrap().fm_index);
+
+        // routing state
+        let routing_state = pick_list::State::default();
+        let routing_selected = Some(routing.read().unwrap().mode);
+        let routing_label = format!("Routing");
+        let balance_state = knob::State::new(
+            balance_range.normal_param(routing.read().unwrap().balance as f32, 0.5),
+        );
+        let balance_label = format!("Balance\n{:.2}", routing.read().unwrap().balance);
+
+        // filters state
+        let filter_panels = filters
+            .iter()
+            .map(|f| FilterPanel::new(&f.read().unwrap(), &filter_ranges))
+            .collect();
+        let filters_scroll_state = scrollable::State::new();
+
+        // lfos state
+        let lfo_panels = lfos
+            .iter()
+            .map(|l| LfoPanel::new(&l.read().unwrap(), &lfo_ranges))
+            .collect();
+
+        // modulation matrix state
+        let mod_slot_panels = matrix
+            .read()
+            .unwrap()
+            .iter()
+            .map(|slot| ModSlotPanel::new(slot, &mod_amount_range))
+            .collect();
+
+        // step sequencer state
+        let sequencer_panel = SequencerPanel::new(&sequencer.read().unwrap(), &sequencer_ranges);
+
+        // voice modes state
+        let voicing_panel = VoicingPanel::new(&voicing.read().unwrap(), &glide_range);
+
+        // effects state
+        let tempo_state =
+            knob::State::new(tempo_range.normal_param(*tempo.read().unwrap() as f32, 120.0));
+        let tempo_label = format!("Tempo\n{:.0} BPM", tempo.read().unwrap());
+        let arp_panel = ArpPanel::new(&arpeggiator.read().unwrap(), &octaves_range, &unit_range);
+        let effect_slot_panels = chain
+            .read()
+            .unwrap()
+            .slots
+            .iter()
+            .map(EffectSlotPanel::new)
+            .collect();
+        let distortion_panel = DistortionPanel::new(
+            &distortion.read().unwrap(),
+            &unit_range,
+            &bits_range,
+            &downsample_range,
+        );
+        let modulation_panel = ModulationPanel::new(
+            &modulation.read().unwrap(),
+            &modulation_rate_range,
+            &unit_range,
+        );
+        let delay_panel = DelayPanel::new(
+            &delay.read().unwrap(),
+            &delay_time_range,
+            &unit_range,
+            &high_cut_range,
+        );
+        let reverb_panel = ReverbPanel::new(&reverb.read().unwrap(), &unit_range, &pre_delay_range);
+        let convolution_panel = ConvolutionPanel::new(
+            &convolution.read().unwrap(),
+            &unit_range,
+            &pre_delay_range,
+            &high_cut_range,
+        );
+        let eq_panels = (0..EQ_BANDS)
+            .map(|i| EqBandPanel::new(&eq.read().unwrap(), i, &eq_ranges))
+            .collect();
+        let compressor_panel =
+            CompressorPanel::new(&compressor.read().unwrap(), &compressor_ranges);
+
+        let mut app = App {
+            _midi_msgs,
+            _connection,
+            oscillators,
+            filters,
+            routing,
+            cross_mod,
+            lfos,
+            matrix,
+            mpe,
+            distortion,
+            modulation,
+            delay,
+            reverb,
+            convolution,
+            eq,
+            compressor,
+            chain,
+            arpeggiator,
+            sequencer,
+            voicing,
+            tempo,
+            sample_rate,
+
+            // ui from here on out
+            oscillator_ranges,
+            filter_ranges,
+            lfo_ranges,
+            mod_amount_range,
+            unit_range,
+            pre_delay_range,
+            tempo_range,
+            octaves_range,
+            bits_range,
+            downsample_range,
+            modulation_rate_range,
+            delay_time_range,
+            high_cut_range,
+            eq_ranges,
+            compressor_ranges,
+            sequencer_ranges,
+            glide_range,
+            fm_index_range,
+            balance_range,
+
+            // oscillators state
+            oscillator_panels,
+            oscillators_scroll_state,
+            add_oscillator_state,
+
+            // cross modulation state
+            cross_mod_state,
+            cross_mod_selected,
+            cross_mod_label,
+            fm_index_state,
+            fm_index_label,
+
+            // routing state
+            routing_state,
+            routing_selected,
+            routing_label,
+            balance_state,
+            balance_label,
+
+            // filters state
+            filter_panels,
+            filters_scroll_state,
+
+            // lfos state
+            lfo_panels,
+
+            // modulation matrix state
+            page: Page::Synth,
+            performance_button_state: button::State::default(),
+            matrix_button_state: button::State::default(),
+            sequencer_button_state: button::State::default(),
+            back_button_state: button::State::default(),
+            mod_slot_panels,
+            matrix_scroll_state: scrollable::State::new(),
+
+            // step sequencer state
+            sequencer_panel,
+
+            // voice modes state
+            voicing_panel,
+
+            // effects state
+            effects_scroll_state: scrollable::State::new(),
+            tempo_state,
+            tempo_label,
+            arp_panel,
+            effect_slot_panels,
+            picked_effect: None,
+            distortion_panel,
+            modulation_panel,
+            delay_panel,
+            reverb_panel,
+            convolution_panel,
+            eq_panels,
+            compressor_panel,
+
+            // patch file
+            patch_path: String::new(),
+            patch_path_state: text_input::State::default(),
+            save_patch_state: button::State::default(),
+            load_patch_state: button::State::default(),
+        };
+        app.show_step_lock();
+        app
+    }
+
+    fn update(&mut self, event: Message) {
+        match event {
+            Message::Detune(i, normal) => {
+                let value = self.oscillator_ranges.detune.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].detune = value;
+                self.oscillator_panels[i].detune_label = format!("Detune\n{:+.1} Hz", value);
+                info!("detune osc{}: {value} Hz", i + 1)
+            }
+            Message::WaveformSelected(i, waveform) => {
+                self.oscillator_panels[i].waveform_selected = Some(waveform);
+                self.oscillators.write().unwrap()[i].waveform = waveform;
+            }
+            Message::Attack(i, normal) => {
+                let value = self.oscillator_ranges.adsr.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].adsr.0 = value as f64;
+                self.oscillator_panels[i].attack_label = format!("Attack\n{:.2} s", value);
+                info!("attack osc{}: {value} s", i + 1)
+            }
+            Message::Decay(i, normal) => {
+                let value = self.oscillator_ranges.adsr.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].adsr.1 = value as f64;
+                self.oscillator_panels[i].decay_label = format!("Decay\n{:.2} s", value);
+                info!("decay osc{}: {value} s", i + 1)
+            }
+            Message::Sustain(i, normal) => {
+                let value = self.oscillator_ranges.adsr.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].adsr.2 = value as f64;
+                self.oscillator_panels[i].sustain_label = format!("Sustain\n{:.2} s", value);
+                info!("sustain osc{}: {value} s", i + 1)
+            }
+            Message::Release(i, normal) => {
+                let value = self.oscillator_ranges.adsr.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].adsr.3 = value as f64;
+                self.oscillator_panels[i].release_label = format!("Release\n{:.2} s", value);
+                info!("release osc{}: {value} s", i + 1)
+            }
+            Message::FilterSelected(i, slot) => {
+                self.oscillator_panels[i].filter_selected = Some(slot);
+                self.oscillators.write().unwrap()[i].filter = slot;
+            }
+            Message::FilePathChanged(i, path) => {
+                self.oscillator_panels[i].file_path = path;
+            }
+            Message::LoadFile(i) => {
+                let path = self.oscillator_panels[i].file_path.clone();
+                let waveform = self.oscillators.read().unwrap()[i].waveform;
+                match waveform {
+                    Waveform::Sample => match Sample::load(&path) {
+                        Ok(sample) => {
+                            info!("loaded sample {path} into osc{}", i + 1);
+                            self.oscillator_panels[i].sample_label =
+                                format!("Sample\n{}", sample.name);
+                            self.oscillators.write().unwrap()[i].sample = Some(Arc::new(sample));
+                        }
+                        Err(err) => error!("could not load sample {path}: {err}"),
+                    },
+                    Waveform::Sfz => match SfzInstrument::load(&path) {
+                        Ok(instrument) => {
+                            info!("loaded instrument {path} into osc{}", i + 1);
+                            self.oscillator_panels[i].instrument_label = format!(
+                                "Instrument\n{} ({} regions)",
+                                instrument.name,
+                                instrument.regions.len()
+                            );
+                            self.oscillators.write().unwrap()[i].instrument =
+                                Some(Arc::new(instrument));
+                        }
+                        Err(err) => error!("could not load instrument {path}: {err:#}"),
+                    },
+                    Waveform::SoundFont => match SoundFont::load(&path) {
+                        Ok(soundfont) => {
+                            info!(
+                                "loaded soundfont {path} with {} presets into osc{}",
+                                soundfont.presets.len(),
+                                i + 1
+                            );
+                            let panel = &mut self.oscillator_panels[i];
+                            panel.soundfont_label = format!("SoundFont\n{}", soundfont.name);
+                            panel.presets = soundfont.presets.clone();
+                            panel.preset_selected = soundfont.presets.first().cloned();
+                            let mut oscillators = self.oscillators.write().unwrap();
+                            oscillators[i].soundfont = Some(Arc::new(soundfont));
+                            oscillators[i].preset = 0;
+                        }
+                        Err(err) => error!("could not load soundfont {path}: {err}"),
+                    },
+                    _ => {}
+                }
+            }
+            Message::PresetSelected(i, preset) => {
+                let mut oscillators = self.oscillators.write().unwrap();
+                if let Some(index) = oscillators[i]
+                    .soundfont
+                    .as_ref()
+                    .and_then(|s| s.preset_index(&preset))
+                {
+                    oscillators[i].preset = index;
+                }
+                info!("preset osc{}: {preset}", i + 1);
+                self.oscillator_panels[i].preset_selected = Some(preset);
+            }
+            Message::SampleRoot(i, normal) => {
+                let value = self.oscillator_ranges.root.unmap_to_value(normal).round();
+                self.oscillators.write().unwrap()[i].sample_settings.root = value as u8;
+                self.oscillator_panels[i].root_label = format!("Root Key\n{}", value);
+                info!("sample root osc{}: {value}", i + 1)
+            }
+            Message::PlayModeSelected(i, mode) => {
+                self.oscillator_panels[i].play_mode_selected = Some(mode);
+                self.oscillators.write().unwrap()[i].sample_settings.mode = mode;
+            }
+            Message::SampleStart(i, normal) => {
+                let value = self.oscillator_ranges.position.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].sample_settings.start = value as f64;
+                self.oscillator_panels[i].start_label = format!("Start\n{:.0} %", value * 100.0);
+                info!("sample start osc{}: {value}", i + 1)
+            }
+            Message::SampleEnd(i, normal) => {
+                let value = self.oscillator_ranges.position.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i].sample_settings.end = value as f64;
+                self.oscillator_panels[i].end_label = format!("End\n{:.0} %", value * 100.0);
+                info!("sample end osc{}: {value}", i + 1)
+            }
+            Message::LoopStart(i, normal) => {
+                let value = self.oscillator_ranges.position.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i]
+                    .sample_settings
+                    .loop_start = value as f64;
+                self.oscillator_panels[i].loop_start_label =
+                    format!("Loop Start\n{:.0} %", value * 100.0);
+                info!("loop start osc{}: {value}", i + 1)
+            }
+            Message::LoopEnd(i, normal) => {
+                let value = self.oscillator_ranges.position.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i]
+                    .sample_settings
+                    .loop_end = value as f64;
+                self.oscillator_panels[i].loop_end_label =
+                    format!("Loop End\n{:.0} %", value * 100.0);
+                info!("loop end osc{}: {value}", i + 1)
+            }
+            Message::Crossfade(i, normal) => {
+                let value = self.oscillator_ranges.crossfade.unmap_to_value(normal);
+                self.oscillators.write().unwrap()[i]
+                    .sample_settings
+                    .crossfade = value as f64;
+                self.oscillator_panels[i].crossfade_label =
+                    format!("Crossfade\n{:.0} ms", value * 1000.0);
+                info!("loop crossfade osc{}: {value} s", i + 1)
+            }
+            Message::AddOscillator => {
+                let oscillator = Oscillator::new(
+                    Waveform::Sine,
+                    ADSR(0.11, 0.14, 0.47, 0.63),
+                    0.0,
+                    FilterSlot::Filter1,
+                );
+                self.oscillator_panels
+                    .push(OscillatorPanel::new(&oscillator, &self.oscillator_ranges));
+                self.oscillators.write().unwrap().push(oscillator);
+                info!("added osc{}", self.oscillator_panels.len())
+            }
+            Message::RemoveOscillator(i) => {
+                self.oscillator_panels.remove(i);
+                self.oscillators.write().unwrap().remove(i);
+                info!("removed osc{}", i + 1)
+            }
+            Message::CrossModSelected(mode) => {
+                self.cross_mod_selected = Some(mode);
+                self.cross_mod.write().unwrap().mode = mode;
+            }
+            Message::FmIndex(normal) => {
+                let value = self.fm_index_range.unmap_to_value(normal);
+                self.cross_mod.write().unwrap().fm_index = value as f64;
+                self.fm_index_label = format!("FM Index\n{:.2}", value);
+                info!("fm index: {value}")
+            }
+            Message::RoutingSelected(mode) => {
+                self.routing_selected = Some(mode);
+                self.routing.write().unwrap().mode = mode;
+            }
+            Message::Balance(normal) => {
+                let value = self.balance_range.unmap_to_value(normal);
+                self.routing.write().unwrap().balance = value as f64;
+                self.balance_label = format!("Balance\n{:.2}", value);
+                info!("filter balance: {value}")
+            }
+            Message::FilterTypeSelected(i, filter_type) => {
+                self.filter_panels[i].type_selected = Some(filter_type);
+                self.filters[i].write().unwrap().filter_type = filter_type;
+            }
+            Message::Cutoff(i, normal) => {
+                let value = self.filter_ranges.cutoff.unmap_to_value(normal);
+                self.filters[i].write().unwrap().cutoff = value as f64;
+                self.filter_panels[i].cutoff_label = format!("Cutoff\n{:.2} Hz", value);
+                info!("cutoff f{}: {value} Hz", i + 1)
+            }
+            Message::Resonance(i, normal) => {
+                let value = self.filter_ranges.resonance.unmap_to_value(normal);
+                self.filters[i].write().unwrap().resonance = value as f64;
+                self.filter_panels[i].resonance_label = format!("Resonance\n{:.2} %", value);
+                info!("resonance f{}: {value} %", i + 1)
+            }
+            Message::FilterAttack(i, normal) => {
+                let value = self.filter_ranges.adsr.unmap_to_value(normal);
+                self.filters[i].write().unwrap().envelope.0 = value as f64;
+                self.filter_panels[i].attack_label = format!("Attack\n{:.2} s", value);
+                info!("envelope attack f{}: {value} s", i + 1)
+            }
+            Message::FilterDecay(i, normal) => {
+                let value = self.filter_ranges.adsr.unmap_to_value(normal);
+                self.filters[i].write().unwrap().envelope.1 = value as f64;
+                self.filter_panels[i].decay_label = format!("Decay\n{:.2} s", value);
+                info!("envelope decay f{}: {value} s", i + 1)
+            }
+            Message::FilterSustain(i, normal) => {
+                let value = self.filter_ranges.adsr.unmap_to_value(normal);
+                self.filters[i].write().unwrap().envelope.2 = value as f64;
+                self.filter_panels[i].sustain_label = format!("Sustain\n{:.2}", value);
+                info!("envelope sustain f{}: {value}", i + 1)
+            }
+            Message::FilterRelease(i, normal) => {
+                let value = self.filter_ranges.adsr.unmap_to_value(normal);
+                self.filters[i].write().unwrap().envelope.3 = value as f64;
+                self.filter_panels[i].release_label = format!("Release\n{:.2} s", value);
+                info!("envelope release f{}: {value} s", i + 1)
+            }
+            Message::EnvelopeAmount(i, normal) => {
+                let value = self.filter_ranges.amount.unmap_to_value(normal);
+                self.filters[i].write().unwrap().envelope_amount = value as f64;
+                self.filter_panels[i].amount_label = format!("Env Amount\n{:+.2} oct", value);
+                info!("envelope amount f{}: {value} octaves", i + 1)
+            }
+            Message::VelocityAmount(i, normal) => {
+                let value = self.filter_ranges.velocity.unmap_to_value(normal);
+                self.filters[i].write().unwrap().velocity_amount = value as f64;
+                self.filter_panels[i].velocity_label = format!("Velocity\n{:.0} %", value * 100.0);
+                info!("envelope velocity f{}: {value}", i + 1)
+            }
+            Message::KeyTracking(i, normal) => {
+                let value = self.filter_ranges.key_tracking.unmap_to_value(normal);
+                self.filters[i].write().unwrap().key_tracking = value as f64;
+                self.filter_panels[i].key_tracking_label =
+                    format!("Key Track\n{:.0} %", value * 100.0);
+                info!("key tracking f{}: {value}", i + 1)
+            }
+            Message::Drive(i, normal) => {
+                let value = self.filter_ranges.drive.unmap_to_value(normal);
+                self.filters[i].write().unwrap().drive = value as f64;
+                self.filter_panels[i].drive_label = format!("Drive\n{:.0} %", value * 100.0);
+                info!("drive f{}: {value}", i + 1)
+            }
+            Message::LfoWaveformSelected(i, waveform) => {
+                self.lfo_panels[i].waveform_selected = Some(waveform);
+                self.lfos[i].write().unwrap().waveform = waveform;
+            }
+            Message::LfoRate(i, normal) => {
+                let value = self.lfo_ranges.rate.unmap_to_value(normal);
+                self.lfos[i].write().unwrap().rate = value as f64;
+                self.lfo_panels[i].rate_label = format!("Rate\n{:.2} Hz", value);
+                info!("rate lfo{}: {value} Hz", i + 1)
+            }
+            Message::LfoDepth(i, normal) => {
+                let value = self.lfo_ranges.depth.unmap_to_value(normal);
+                self.lfos[i].write().unwrap().depth = value as f64;
+                self.lfo_panels[i].depth_label = format!("Depth\n{:.0} %", value * 100.0);
+                info!("depth lfo{}: {value}", i + 1)
+            }
+            Message::LfoPhase(i, normal) => {
+                let value = self.lfo_ranges.phase.unmap_to_value(normal);
+                self.lfos[i].write().unwrap().phase = value as f64;
+                self.lfo_panels[i].phase_label = format!("Phase\n{:.0}°", value * 360.0);
+                info!("phase lfo{}: {value}", i + 1)
+            }
+            Message::LfoRetrigger(i, retrigger) => {
+                self.lfo_panels[i].retrigger = retrigger;
+                self.lfos[i].write().unwrap().retrigger = retrigger;
+            }
+            Message::LfoDestinationSelected(i, destination) => {
+                self.lfo_panels[i].destination_selected = Some(destination);
+                self.lfos[i].write().unwrap().destination = destination;
+            }
+            Message::ShowPage(page) => self.page = page,
+            Message::MpeToggled(enabled) => {
+                let mut mpe = self.mpe.write().unwrap();
+                mpe.enabled = enabled;
+                // Switching on without any zone set up by a controller opens the lower one
+                if enabled && mpe.lower_members == 0 && mpe.upper_members == 0 {
+                    mpe.lower_members = Mpe::default().lower_members;
+                }
+                info!("mpe: {enabled}")
+            }
+            Message::PatchPathChanged(path) => self.patch_path = path,
+            Message::SavePatch => match self.patch().save(&self.patch_path) {
+                Ok(()) => info!("saved patch {}", self.patch_path),
+                Err(err) => error!("could not save patch {}: {err}", self.patch_path),
+            },
+            Message::LoadPatch => {
+                let path = self.patch_path.clone();
+                match Patch::load(&path) {
+                    Ok(patch) => {
+                        self.load_patch(patch);
+                        info!("loaded patch {path}")
+                    }
+                    Err(err) => error!("could not load patch {path}: {err}"),
+                }
+            }
+            Message::VoiceModeSelected(mode) => {
+                self.voicing_panel.mode_selected = Some(mode);
+                self.voicing.write().unwrap().mode = mode;
+            }
+            Message::NotePrioritySelected(priority) => {
+                self.voicing_panel.priority_selected = Some(priority);
+                self.voicing.write().unwrap().priority = priority;
+            }
+            Message::Glide(normal) => {
+                let value = self.glide_range.unmap_to_value(normal);
+                self.voicing.write().unwrap().glide = value as f64;
+                self.voicing_panel.glide_label = format!("Glide\n{:.2} s", value);
+                info!("glide: {value} s")
+            }
+            Message::GlideCurveSelected(curve) => {
+                self.voicing_panel.curve_selected = Some(curve);
+                self.voicing.write().unwrap().glide_curve = curve;
+            }
+            Message::Tempo(normal) => {
+                let value = self.tempo_range.unmap_to_value(normal);
+                *self.tempo.write().unwrap() = value as f64;
+                self.tempo_label = format!("Tempo\n{:.0} BPM", value);
+                info!("tempo: {value} bpm")
+            }
+            Message::ArpToggled(enabled) => {
+                self.arp_panel.enabled = enabled;
+                self.arpeggiator.write().unwrap().enabled = enabled;
+                info!("arpeggiator: {enabled}")
+            }
+            Message::ArpLatchToggled(latch) => {
+                self.arp_panel.latch = latch;
+                self.arpeggiator.write().unwrap().latch = latch;
+                info!("arpeggiator latch: {latch}")
+            }
+            Message::ArpModeSelected(mode) => {
+                self.arp_panel.mode_selected = Some(mode);
+                self.arpeggiator.write().unwrap().mode = mode;
+            }
+            Message::ArpRateSelected(rate) => {
+                self.arp_panel.rate_selected = Some(rate);
+                self.arpeggiator.write().unwrap().rate = rate;
+            }
+            Message::ArpOctaves(normal) => {
+                let value = self.octaves_range.unmap_to_value(normal).round() as u8;
+                self.arpeggiator.write().unwrap().octaves = value;
+                self.arp_panel.octaves_label = format!("Octaves\n{}", value);
+                info!("arpeggiator octaves: {value}")
+            }
+            Message::ArpGate(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.arpeggiator.write().unwrap().gate = value as f64;
+                self.arp_panel.gate_label = format!("Gate\n{:.0} %", value * 100.0);
+                info!("arpeggiator gate: {value}")
+            }
+            Message::ArpSwing(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.arpeggiator.write().unwrap().swing = value as f64;
+                self.arp_panel.swing_label = format!("Swing\n{:.0} %", value * 100.0);
+                info!("arpeggiator swing: {value}")
+            }
+            Message::SequencerPlayToggled(playing) => {
+                self.sequencer_panel.playing = playing;
+                self.sequencer.write().unwrap().playing = playing;
+                info!("sequencer: {playing}")
+            }
+            Message::SequencerLengthSelected(length) => {
+                self.sequencer_panel.length_selected = Some(length);
+                self.sequencer.write().unwrap().length = length;
+                if self.sequencer_panel.selected >= length {
+                    self.sequencer_panel.select(0, &self.sequencer_ranges);
+                    self.show_step_lock();
+                }
+            }
+            Message::SequencerRateSelected(rate) => {
+                self.sequencer_panel.rate_selected = Some(rate);
+                self.sequencer.write().unwrap().rate = rate;
+            }
+            Message::SequencerLockParameterSelected(parameter) => {
+                self.sequencer_panel.lock_parameter_selected = Some(parameter);
+                self.show_step_lock();
+            }
+            Message::SequencerStepSelected(i) => {
+                // Clicking the selected step again switches it on or off
+                if i == self.sequencer_panel.selected {
+                    self.update_step(|step| step.active = !step.active);
+                } else {
+                    self.sequencer_panel.select(i, &self.sequencer_ranges);
+                    self.show_step_lock();
+                }
+            }
+            Message::StepActiveToggled(active) => {
+                self.update_step(|step| step.active = active);
+            }
+            Message::StepTieToggled(tie) => {
+                self.update_step(|step| step.tie = tie);
+            }
+            Message::StepNote(normal) => {
+                let value = self.sequencer_ranges.note.unmap_to_value(normal).round() as u8;
+                self.update_step(|step| step.note = value);
+                self.sequencer_panel.note_label = format!("Note\n{}", note_name(value));
+                info!("step {} note: {value}", self.sequencer_panel.selected + 1)
+            }
+            Message::StepVelocity(normal) => {
+                let value = self
+                    .sequencer_ranges
+                    .velocity
+                    .unmap_to_value(normal)
+                    .round() as u8;
+                self.update_step(|step| step.velocity = value);
+                self.sequencer_panel.velocity_label = format!("Velocity\n{}", value);
+                info!(
+                    "step {} velocity: {value}",
+                    self.sequencer_panel.selected + 1
+                )
+            }
+            Message::StepGate(normal) => {
+                let value = self.sequencer_ranges.gate.unmap_to_value(normal);
+                self.update_step(|step| step.gate = value as f64);
+                self.sequencer_panel.gate_label = format!("Gate\n{:.0} %", value * 100.0);
+                info!("step {} gate: {value}", self.sequencer_panel.selected + 1)
+            }
+            Message::StepLockToggled(locked) => {
+                // A new lock starts at the value of the patch
+                let parameter = self
+                    .sequencer_panel
+                    .lock_parameter_selected
+                    .unwrap_or_default();
+                let lock = locked.then(|| self.patch_value(parameter));
+                self.update_step(|step| step.locks[parameter as usize] = lock);
+                self.show_step_lock();
+            }
+            Message::StepLock(normal) => {
+                let parameter = self
+                    .sequencer_panel
+                    .lock_parameter_selected
+                    .unwrap_or_default();
+                let value =
+                    parameter.value(self.sequencer_ranges.lock.unmap_to_value(normal) as f64);
+                self.update_step(|step| step.locks[parameter as usize] = Some(value));
+                self.sequencer_panel.lock_label = lock_label(parameter, Some(value));
+                info!(
+                    "step {} lock {parameter}: {value}",
+                    self.sequencer_panel.selected + 1
+                )
+            }
+            Message::DistortionPlacementSelected(placement) => {
+                self.distortion_panel.placement_selected = Some(placement);
+                self.distortion.write().unwrap().placement = placement;
+            }
+            Message::DistortionCurveSelected(curve) => {
+                self.distortion_panel.curve_selected = Some(curve);
+                self.distortion.write().unwrap().curve = curve;
+            }
+            Message::DistortionDrive(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.distortion.write().unwrap().drive = value as f64;
+                self.distortion_panel.drive_label = format!("Drive\n{:.0} %", value * 100.0);
+                info!("distortion drive: {value}")
+            }
+            Message::DistortionBits(normal) => {
+                let value = self.bits_range.unmap_to_value(normal);
+                self.distortion.write().unwrap().bits = value as f64;
+                self.distortion_panel.bits_label = format!("Bits\n{:.1}", value);
+                info!("distortion bits: {value}")
+            }
+            Message::DistortionDownsample(normal) => {
+                let value = self.downsample_range.unmap_to_value(normal);
+                self.distortion.write().unwrap().downsample = value as f64;
+                self.distortion_panel.downsample_label = format!("Downsample\n{:.1}x", value);
+                info!("distortion downsample: {value}x")
+            }
+            Message::DistortionMix(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.distortion.write().unwrap().mix = value as f64;
+                self.distortion_panel.mix_label = format!("Mix\n{:.0} %", value * 100.0);
+                info!("distortion mix: {value}")
+            }
+            Message::ModulationTypeSelected(modulation_type) => {
+                self.modulation_panel.type_selected = Some(modulation_type);
+                self.modulation.write().unwrap().modulation_type = modulation_type;
+            }
+            Message::ModulationRate(normal) => {
+                let value = self.modulation_rate_range.unmap_to_value(normal);
+                self.modulation.write().unwrap().rate = value as f64;
+                self.modulation_panel.rate_label = format!("Rate\n{:.2} Hz", value);
+                info!("modulation rate: {value} Hz")
+            }
+            Message::ModulationDepth(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.modulation.write().unwrap().depth = value as f64;
+                self.modulation_panel.depth_label = format!("Depth\n{:.0} %", value * 100.0);
+                info!("modulation depth: {value}")
+            }
+            Message::ModulationFeedback(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.modulation.write().unwrap().feedback = value as f64;
+                self.modulation_panel.feedback_label = format!("Feedback\n{:.0} %", value * 100.0);
+                info!("modulation feedback: {value}")
+            }
+            Message::ModulationMix(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.modulation.write().unwrap().mix = value as f64;
+                self.modulation_panel.mix_label = format!("Mix\n{:.0} %", value * 100.0);
+                info!("modulation mix: {value}")
+            }
+            Message::DelaySyncToggled(sync) => {
+                self.delay_panel.sync = sync;
+                self.delay.write().unwrap().sync = sync;
+                info!("delay sync: {sync}")
+            }
+            Message::DelayTime(normal) => {
+                let value = self.delay_time_range.unmap_to_value(normal);
+                self.delay.write().unwrap().time = value as f64;
+                self.delay_panel.time_label = format!("Time\n{:.0} ms", value * 1000.0);
+                info!("delay time: {value} s")
+            }
+            Message::DelayDivisionSelected(division) => {
+                self.delay_panel.division_selected = Some(division);
+                self.delay.write().unwrap().division = division;
+            }
+            Message::DelayFeedback(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.delay.write().unwrap().feedback = value as f64;
+                self.delay_panel.feedback_label = format!("Feedback\n{:.0} %", value * 100.0);
+                info!("delay feedback: {value}")
+            }
+            Message::DelayHighCut(normal) => {
+                let value = self.high_cut_range.unmap_to_value(normal);
+                self.delay.write().unwrap().high_cut = value as f64;
+                self.delay_panel.high_cut_label = format!("High Cut\n{:.0} Hz", value);
+                info!("delay high cut: {value} Hz")
+            }
+            Message::DelayPingPongToggled(ping_pong) => {
+                self.delay_panel.ping_pong = ping_pong;
+                self.delay.write().unwrap().ping_pong = ping_pong;
+                info!("delay ping-pong: {ping_pong}")
+            }
+            Message::DelayMix(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.delay.write().unwrap().mix = value as f64;
+                self.delay_panel.mix_label = format!("Mix\n{:.0} %", value * 100.0);
+                info!("delay mix: {value}")
+            }
+            Message::EffectMoved(from, to) => {
+                self.chain.write().unwrap().slots.swap(from, to);
+                self.effect_slot_panels.swap(from, to);
+                self.picked_effect = None;
+                info!("effect chain: moved slot {} to {}", from + 1, to + 1)
+            }
+            Message::EffectPicked(i) => {
+                self.picked_effect = match self.picked_effect {
+                    Some(picked) if picked == i => None,
+                    _ => Some(i),
+                };
+            }
+            Message::EffectDropped(to) => {
+                if let Some(from) = self.picked_effect.take() {
+                    // The slots in between shift by one towards where the slot came from
+                    let mut chain = self.chain.write().unwrap();
+                    if from < to {
+                        chain.slots[from..=to].rotate_left(1);
+                        self.effect_slot_panels[from..=to].rotate_left(1);
+                    } else {
+                        chain.slots[to..=from].rotate_right(1);
+                        self.effect_slot_panels[to..=from].rotate_right(1);
+                    }
+                    info!("effect chain: moved slot {} to {}", from + 1, to + 1)
+                }
+            }
+            Message::EffectBypassToggled(i, bypass) => {
+                self.effect_slot_panels[i].bypass = bypass;
+                self.chain.write().unwrap().slots[i].bypass = bypass;
+                info!("bypass effect slot {}: {bypass}", i + 1)
+            }
+            Message::ImpulsePathChanged(path) => self.convolution_panel.file_path = path,
+            Message::LoadImpulse => {
+                let path = self.convolution_panel.file_path.clone();
+                match ImpulseResponse::load(&path) {
+                    Ok(impulse) => {
+                        info!(
+                            "loaded impulse response {path} with {} channels",
+                            impulse.channels.len()
+                        );
+                        self.convolution_panel.impulse_label = format!("Impulse\n{}", impulse.name);
+                        // Partitioned here so the audio thread only has to swap it in
+                        let kernel = Kernel::new(&impulse, self.sample_rate);
+                        self.convolution.write().unwrap().load(kernel);
+                    }
+                    Err(err) => error!("could not load impulse response {path}: {err}"),
+                }
+            }
+            Message::ConvolutionPreDelay(normal) => {
+                let value = self.pre_delay_range.unmap_to_value(normal);
+                self.convolution.write().unwrap().pre_delay = value as f64;
+                self.convolution_panel.pre_delay_label =
+                    format!("Pre-Delay\n{:.0} ms", value * 1000.0);
+                info!("convolution pre-delay: {value} s")
+            }
+            Message::ConvolutionHighCut(normal) => {
+                let value = self.high_cut_range.unmap_to_value(normal);
+                self.convolution.write().unwrap().high_cut = value as f64;
+                self.convolution_panel.high_cut_label = format!("High Cut\n{:.0} Hz", value);
+                info!("convolution high cut: {value} Hz")
+            }
+            Message::ConvolutionMix(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.convolution.write().unwrap().mix = value as f64;
+                self.convolution_panel.mix_label = format!("Mix\n{:.0} %", value * 100.0);
+                info!("convolution mix: {value}")
+            }
+            Message::EqFrequency(i, normal) => {
+                let value = self.eq_ranges.frequency.unmap_to_value(normal);
+                self.eq.write().unwrap().bands[i].frequency = value as f64;
+                self.eq_panels[i].frequency_label = format!("Freq\n{:.0} Hz", value);
+                info!("eq band {} frequency: {value} Hz", i + 1)
+            }
+            Message::EqGain(i, normal) => {
+                let value = self.eq_ranges.gain.unmap_to_value(normal);
+                self.eq.write().unwrap().bands[i].gain = value as f64;
+                self.eq_panels[i].gain_label = format!("Gain\n{:+.1} dB", value);
+                info!("eq band {} gain: {value} dB", i + 1)
+            }
+            Message::EqQ(i, normal) => {
+                let value = self.eq_ranges.q.unmap_to_value(normal);
+                self.eq.write().unwrap().bands[i].q = value as f64;
+                self.eq_panels[i].q_label = format!("Q\n{:.2}", value);
+                info!("eq band {} q: {value}", i + 1)
+            }
+            Message::CompressorThreshold(normal) => {
+                let value = self.compressor_ranges.threshold.unmap_to_value(normal);
+                self.compressor.write().unwrap().threshold = value as f64;
+                self.compressor_panel.threshold_label = format!("Threshold\n{:.1} dB", value);
+                info!("compressor threshold: {value} dB")
+            }
+            Message::CompressorRatio(normal) => {
+                let value = self.compressor_ranges.ratio.unmap_to_value(normal);
+                self.compressor.write().unwrap().ratio = value as f64;
+                self.compressor_panel.ratio_label = format!("Ratio\n{:.1}:1", value);
+                info!("compressor ratio: {value}")
+            }
+            Message::CompressorAttack(normal) => {
+                let value = self.compressor_ranges.attack.unmap_to_value(normal);
+                self.compressor.write().unwrap().attack = value as f64;
+                self.compressor_panel.attack_label = format!("Attack\n{:.1} ms", value * 1000.0);
+                info!("compressor attack: {value} s")
+            }
+            Message::CompressorRelease(normal) => {
+                let value = self.compressor_ranges.release.unmap_to_value(normal);
+                self.compressor.write().unwrap().release = value as f64;
+                self.compressor_panel.release_label = format!("Release\n{:.0} ms", value * 1000.0);
+                info!("compressor release: {value} s")
+            }
+            Message::CompressorMakeup(normal) => {
+                let value = self.compressor_ranges.makeup.unmap_to_value(normal);
+                self.compressor.write().unwrap().makeup = value as f64;
+                self.compressor_panel.makeup_label = format!("Makeup\n{:+.1} dB", value);
+                info!("compressor makeup: {value} dB")
+            }
+            Message::CompressorKnee(normal) => {
+                let value = self.compressor_ranges.knee.unmap_to_value(normal);
+                self.compressor.write().unwrap().knee = value as f64;
+                self.compressor_panel.knee_label = format!("Knee\n{:.1} dB", value);
+                info!("compressor knee: {value} dB")
+            }
+            Message::ReverbSize(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.reverb.write().unwrap().size = value as f64;
+                self.reverb_panel.size_label = format!("Size\n{:.0} %", value * 100.0);
+                info!("reverb size: {value}")
+            }
+            Message::ReverbDamping(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.reverb.write().unwrap().damping = value as f64;
+                self.reverb_panel.damping_label = format!("Damping\n{:.0} %", value * 100.0);
+                info!("reverb damping: {value}")
+            }
+            Message::ReverbPreDelay(normal) => {
+                let value = self.pre_delay_range.unmap_to_value(normal);
+                self.reverb.write().unwrap().pre_delay = value as f64;
+                self.reverb_panel.pre_delay_label = format!("Pre-Delay\n{:.0} ms", value * 1000.0);
+                info!("reverb pre-delay: {value} s")
+            }
+            Message::ReverbWidth(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.reverb.write().unwrap().width = value as f64;
+                self.reverb_panel.width_label = format!("Width\n{:.0} %", value * 100.0);
+                info!("reverb width: {value}")
+            }
+            Message::ReverbMix(normal) => {
+                let value = self.unit_range.unmap_to_value(normal);
+                self.reverb.write().unwrap().mix = value as f64;
+                self.reverb_panel.mix_label = format!("Mix\n{:.0} %", value * 100.0);
+                info!("reverb mix: {value}")
+            }
+            Message::ModSourceSelected(i, source) => {
+                self.mod_slot_panels[i].source_selected = Some(source);
+                self.matrix.write().unwrap()[i].source = source;
+            }
+            Message::ModDestinationSelected(i, destination) => {
+                self.mod_slot_panels[i].destination_selected = Some(destination);
+                self.matrix.write().unwrap()[i].destination = destination;
+            }
+            Message::ModAmount(i, normal) => {
+                let value = self.mod_amount_range.unmap_to_value(normal);
+                self.matrix.write().unwrap()[i].amount = value as f64;
+                self.mod_slot_panels[i].amount_label = format!("{:+.0} %", value * 100.0);
+                info!("amount mod slot {}: {value}", i + 1)
+            }
+        }
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        if self.page == Page::Matrix {
+            return self.view_matrix();
+        }
+        if self.page == Page::Sequencer {
+            return self.view_sequencer();
+        }
+        if self.page == Page::Performance {
+            return self.view_performance();
+        }
+
+        let cross_mod_mode = PickList::new(
+            &mut self.cross_mod_state,
+            &CrossModulation::ALL[..],
+            self.cross_mod_selected,
+            Message::CrossModSelected,
+        );
+
+        let fm_index = Knob::new(&mut self.fm_index_state, Message::FmIndex, || None, || None);
+
+        let cross_mod = Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(Text::new("Osc 2 > Osc 1").size(12))
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.cross_mod_label).size(12))
+                                .push(cross_mod_mode),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.fm_index_label).size(12))
+                                .push(fm_index),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill);
+
+        let mut add_oscillator = Button::new(
+            &mut self.add_oscillator_state,
+            Text::new("Add Oscillator").size(12),
+        );
+        if self.oscillator_panels.len() < MAX_OSCILLATORS {
+            add_oscillator = add_oscillator.on_press(Message::AddOscillator);
+        }
+
+        let removable = self.oscillator_panels.len() > 1;
+        let mut oscillators = Scrollable::new(&mut self.oscillators_scroll_state)
+            .spacing(20)
+            .padding(20)
+            .height(Length::Fill)
+            .align_items(Alignment::Start);
+        let mut cross_mod = Some(cross_mod);
+        for (i, panel) in self.oscillator_panels.iter_mut().enumerate() {
+            // Cross modulation sits between the two oscillators it couples
+            if i == 1 {
+                if let Some(cross_mod) = cross_mod.take() {
+                    oscillators = oscillators.push(cross_mod);
+                }
+            }
+            oscillators = oscillators.push(panel.view(i, removable));
+        }
+        let oscillators = oscillators.push(add_oscillator);
+
+        let oscillators_container = Container::new(Container::new(
+            Row::new()
+                .align_items(Alignment::Start)
+                .push(Image::new(image::Handle::from_path(format!(
+                    "{}/assets/oscillators_text.png",
+                    env!("CARGO_MANIFEST_DIR")
+                ))))
+                .push(oscillators),
+        ))
+        .align_x(iced::alignment::Horizontal::Left)
+        .width(Length::Units(214))
+        .height(Length::Units(465))
+        .max_width(214)
+        .max_height(465)
+        .style(styling::OscillatorsContainer);
+
+        let routing_mode = PickList::new(
+            &mut self.routing_state,
+            &FilterRouting::ALL[..],
+            self.routing_selected,
+            Message::RoutingSelected,
+        );
+
+        let balance = Knob::new(&mut self.balance_state, Message::Balance, || None, || None);
+
+        let routing = Container::new(
+            Column::new()
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(5)
+                .push(Text::new("Routing").size(12))
+                .push(
+                    Row::new()
+                        .spacing(8)
+                        .width(Length::Fill)
+                        .align_items(Alignment::Center)
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.routing_label).size(12))
+                                .push(routing_mode),
+                        )
+                        .push(
+                            Column::new()
+                                .push(Text::new(&self.balance_label).size(12))
+                                .push(balance),
+                        ),
+                ),
+        )
+        .style(styling::GroupContainer)
+        .width(Length::Fill);
+
+        let mut filters = Scrollable::new(&mut self.filters_scroll_state)
+            .spacing(20)
+            .padding(20)
+            .height(Length::Fill)
+            .align_items(Alignment::Start)
+            .push(routing);
+        for (i, panel) in self.filter_panels.iter_mut().enumerate() {
+            filters = filters.push(panel.view(i));
+        }
+        for (i, panel) in self.lfo_panels.iter_mut().enumerate() {
+            filters = filters.push(panel.view(i));
+        }
+
+        let filters_container = Container::new(Container::new(
+            Row::new()
+                .align_items(Alignment::Center)
+                .push(Image::new(image::Handle::from_path(format!(
+                    "{}/assets/filters_text.png",
+                    env!("CARGO_MANIFEST_DIR")
+                ))))
+                .push(filters),
         ))
         .align_x(iced::alignment::Horizontal::Left)
         .width(Length::Units(214))
@@ -661,6 +3754,61 @@ impl Sandbox for App {
         .max_height(465)
         .style(styling::FiltersContainer);
 
+        let mut eq = Column::new()
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(5);
+        for (i, panel) in self.eq_panels.iter_mut().enumerate() {
+            eq = eq.push(panel.view(i));
+        }
+
+        // Panels in the order of EffectKind, then taken out in the order of the chain
+        let mut effect_panels = [
+            Some(self.distortion_panel.view()),
+            Some(self.modulation_panel.view()),
+            Some(self.delay_panel.view()),
+            Some(self.reverb_panel.view()),
+            Some(self.convolution_panel.view()),
+            Some(
+                Container::new(eq)
+                    .style(styling::GroupContainer)
+                    .width(Length::Fill)
+                    .into(),
+            ),
+            Some(self.compressor_panel.view()),
+        ];
+        let count = self.effect_slot_panels.len();
+        let picked = self.picked_effect;
+        let mut effects = Column::new().spacing(20);
+        for (i, slot) in self.effect_slot_panels.iter_mut().enumerate() {
+            if let Some(panel) = effect_panels[slot.kind as usize].take() {
+                effects = effects.push(slot.view(i, count, picked, panel));
+            }
+        }
+
+        // The other pages are reached from above the effects, outside of their scrolling
+        let pages = Row::new()
+            .spacing(4)
+            .padding([20, 20, 0, 20])
+            .push(
+                Button::new(
+                    &mut self.performance_button_state,
+                    Text::new("Play").size(12),
+                )
+                .on_press(Message::ShowPage(Page::Performance)),
+            )
+            .push(
+                Button::new(&mut self.matrix_button_state, Text::new("Matrix").size(12))
+                    .on_press(Message::ShowPage(Page::Matrix)),
+            )
+            .push(
+                Button::new(
+                    &mut self.sequencer_button_state,
+                    Text::new("Sequencer").size(12),
+                )
+                .on_press(Message::ShowPage(Page::Sequencer)),
+            );
+
         let effects_container = Container::new(Container::new(
             Row::new()
                 .align_items(Alignment::End)
@@ -669,10 +3817,14 @@ impl Sandbox for App {
                     env!("CARGO_MANIFEST_DIR")
                 ))))
                 .push(
-                    Column::new()
-                        .spacing(20)
-                        .padding(20)
-                        .align_items(Alignment::Start),
+                    Column::new().push(pages).push(
+                        Scrollable::new(&mut self.effects_scroll_state)
+                            .spacing(20)
+                            .padding(20)
+                            .height(Length::Fill)
+                            .align_items(Alignment::Start)
+                            .push(effects),
+                    ),
                 ),
         ))
         .align_x(iced::alignment::Horizontal::Left)
@@ -702,3 +3854,348 @@ impl Sandbox for App {
             .into()
     }
 }
+
+impl App {
+    fn view_matrix(&mut self) -> Element<Message> {
+        let back = Button::new(&mut self.back_button_state, Text::new("Back").size(12))
+            .on_press(Message::ShowPage(Page::Synth));
+
+        let mut slots = Scrollable::new(&mut self.matrix_scroll_state)
+            .spacing(10)
+            .padding(20)
+            .height(Length::Fill)
+            .align_items(Alignment::Start);
+        for (i, panel) in self.mod_slot_panels.iter_mut().enumerate() {
+            slots = slots.push(panel.view(i));
+        }
+
+        Container::new(
+            Column::new()
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .align_items(Alignment::Center)
+                        .push(back)
+                        .push(Text::new("Modulation Matrix").size(16)),
+                )
+                .push(slots),
+        )
+        .width(Length::Units(650))
+        .height(Length::Units(465))
+        .style(styling::EffectsContainer)
+        .into()
+    }
+
+    fn view_performance(&mut self) -> Element<Message> {
+        let back = Button::new(&mut self.back_button_state, Text::new("Back").size(12))
+            .on_press(Message::ShowPage(Page::Synth));
+        // MPE Configuration Messages from a controller switch MPE on and off as well
+        let mpe_enabled = self.mpe.read().unwrap().enabled;
+        let patch = Column::new()
+            .spacing(5)
+            .width(Length::Units(120))
+            .push(Text::new("Patch").size(12))
+            .push(
+                TextInput::new(
+                    &mut self.patch_path_state,
+                    "path/to/patch.txt",
+                    &self.patch_path,
+                    Message::PatchPathChanged,
+                )
+                .size(12)
+                .padding(3),
+            )
+            .push(
+                Row::new()
+                    .spacing(4)
+                    .push(
+                        Button::new(&mut self.save_patch_state, Text::new("Save").size(12))
+                            .on_press(Message::SavePatch),
+                    )
+                    .push(
+                        Button::new(&mut self.load_patch_state, Text::new("Load").size(12))
+                            .on_press(Message::LoadPatch),
+                    ),
+            );
+
+        Container::new(
+            Column::new()
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .align_items(Alignment::Center)
+                        .push(back)
+                        .push(Text::new("Performance").size(16)),
+                )
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .align_items(Alignment::Start)
+                        .push(
+                            Column::new()
+                                .spacing(20)
+                                .push(
+                                    Column::new()
+                                        .push(Text::new(&self.tempo_label).size(12))
+                                        .push(Knob::new(
+                                            &mut self.tempo_state,
+                                            Message::Tempo,
+                                            || None,
+                                            || None,
+                                        )),
+                                )
+                                .push(
+                                    Checkbox::new(mpe_enabled, "MPE", Message::MpeToggled)
+                                        .size(12)
+                                        .text_size(12),
+                                )
+                                .push(patch),
+                        )
+                        .push(self.voicing_panel.view())
+                        .push(self.arp_panel.view()),
+                ),
+        )
+        .width(Length::Units(650))
+        .height(Length::Units(465))
+        .style(styling::EffectsContainer)
+        .into()
+    }
+
+    /// Settings of the sound as they are, to save to a patch file.
+    fn patch(&self) -> Patch {
+        Patch {
+            tempo: *self.tempo.read().unwrap(),
+            oscillators: self.oscillators.read().unwrap().clone(),
+            cross_mod: self.cross_mod.read().unwrap().clone(),
+            routing: self.routing.read().unwrap().clone(),
+            filters: self
+                .filters
+                .iter()
+                .map(|f| f.read().unwrap().clone())
+                .collect(),
+            lfos: self
+                .lfos
+                .iter()
+                .map(|l| l.read().unwrap().clone())
+                .collect(),
+            matrix: self.matrix.read().unwrap().clone(),
+            voicing: self.voicing.read().unwrap().clone(),
+            arpeggiator: self.arpeggiator.read().unwrap().clone(),
+            chain: *self.chain.read().unwrap(),
+            distortion: self.distortion.read().unwrap().clone(),
+            modulation: self.modulation.read().unwrap().clone(),
+            delay: self.delay.read().unwrap().clone(),
+            reverb: self.reverb.read().unwrap().clone(),
+            convolution: self.convolution.read().unwrap().clone(),
+            eq: self.eq.read().unwrap().clone(),
+            compressor: self.compressor.read().unwrap().clone(),
+        }
+    }
+
+    /// Takes over the settings of a patch and shows them on every panel.
+    fn load_patch(&mut self, patch: Patch) {
+        *self.tempo.write().unwrap() = patch.tempo;
+        {
+            // Files are not part of a patch, so each oscillator keeps the ones it had loaded
+            let mut oscillators = self.oscillators.write().unwrap();
+            let loaded = patch
+                .oscillators
+                .into_iter()
+                .take(MAX_OSCILLATORS)
+                .enumerate()
+                .map(|(i, mut oscillator)| {
+                    if let Some(current) = oscillators.get(i) {
+                        oscillator.sample = current.sample.clone();
+                        oscillator.instrument = current.instrument.clone();
+                        oscillator.soundfont = current.soundfont.clone();
+                    }
+                    oscillator
+                })
+                .collect();
+            *oscillators = loaded;
+        }
+        *self.cross_mod.write().unwrap() = patch.cross_mod;
+        *self.routing.write().unwrap() = patch.routing;
+        for (filter, loaded) in self.filters.iter().zip(patch.filters) {
+            *filter.write().unwrap() = loaded;
+        }
+        for (lfo, loaded) in self.lfos.iter().zip(patch.lfos) {
+            *lfo.write().unwrap() = loaded;
+        }
+        {
+            let mut matrix = self.matrix.write().unwrap();
+            *matrix = patch.matrix;
+            matrix.resize(MOD_SLOTS, ModSlot::default());
+        }
+        *self.voicing.write().unwrap() = patch.voicing;
+        *self.arpeggiator.write().unwrap() = patch.arpeggiator;
+        *self.chain.write().unwrap() = patch.chain;
+        *self.distortion.write().unwrap() = patch.distortion;
+        *self.modulation.write().unwrap() = patch.modulation;
+        *self.delay.write().unwrap() = patch.delay;
+        *self.reverb.write().unwrap() = patch.reverb;
+        {
+            // The impulse response that is loaded stays, along with its prepared states
+            let mut convolution = self.convolution.write().unwrap();
+            convolution.pre_delay = patch.convolution.pre_delay;
+            convolution.high_cut = patch.convolution.high_cut;
+            convolution.mix = patch.convolution.mix;
+        }
+        *self.eq.write().unwrap() = patch.eq;
+        *self.compressor.write().unwrap() = patch.compressor;
+
+        let tempo = *self.tempo.read().unwrap();
+        self.tempo_state = knob::State::new(self.tempo_range.normal_param(tempo as f32, 120.0));
+        self.tempo_label = format!("Tempo\n{:.0} BPM", tempo);
+        self.oscillator_panels = self
+            .oscillators
+            .read()
+            .unwrap()
+            .iter()
+            .map(|o| OscillatorPanel::new(o, &self.oscillator_ranges))
+            .collect();
+        let cross_mod = self.cross_mod.read().unwrap().clone();
+        self.cross_mod_selected = Some(cross_mod.mode);
+        self.fm_index_state = knob::State::new(
+            self.fm_index_range
+                .normal_param(cross_mod.fm_index as f32, 0.0),
+        );
+        self.fm_index_label = format!("FM Index\n{:.2}", cross_mod.fm_index);
+        let routing = self.routing.read().unwrap().clone();
+        self.routing_selected = Some(routing.mode);
+        self.balance_state =
+            knob::State::new(self.balance_range.normal_param(routing.balance as f32, 0.5));
+        self.balance_label = format!("Balance\n{:.2}", routing.balance);
+        self.filter_panels = self
+            .filters
+            .iter()
+            .map(|f| FilterPanel::new(&f.read().unwrap(), &self.filter_ranges))
+            .collect();
+        self.lfo_panels = self
+            .lfos
+            .iter()
+            .map(|l| LfoPanel::new(&l.read().unwrap(), &self.lfo_ranges))
+            .collect();
+        self.mod_slot_panels = self
+            .matrix
+            .read()
+            .unwrap()
+            .iter()
+            .map(|slot| ModSlotPanel::new(slot, &self.mod_amount_range))
+            .collect();
+        self.voicing_panel = VoicingPanel::new(&self.voicing.read().unwrap(), &self.glide_range);
+        self.arp_panel = ArpPanel::new(
+            &self.arpeggiator.read().unwrap(),
+            &self.octaves_range,
+            &self.unit_range,
+        );
+        self.effect_slot_panels = self
+            .chain
+            .read()
+            .unwrap()
+            .slots
+            .iter()
+            .map(EffectSlotPanel::new)
+            .collect();
+        self.picked_effect = None;
+        self.distortion_panel = DistortionPanel::new(
+            &self.distortion.read().unwrap(),
+            &self.unit_range,
+            &self.bits_range,
+            &self.downsample_range,
+        );
+        self.modulation_panel = ModulationPanel::new(
+            &self.modulation.read().unwrap(),
+            &self.modulation_rate_range,
+            &self.unit_range,
+        );
+        self.delay_panel = DelayPanel::new(
+            &self.delay.read().unwrap(),
+            &self.delay_time_range,
+            &self.unit_range,
+            &self.high_cut_range,
+        );
+        self.reverb_panel = ReverbPanel::new(
+            &self.reverb.read().unwrap(),
+            &self.unit_range,
+            &self.pre_delay_range,
+        );
+        let file_path = std::mem::take(&mut self.convolution_panel.file_path);
+        self.convolution_panel = ConvolutionPanel::new(
+            &self.convolution.read().unwrap(),
+            &self.unit_range,
+            &self.pre_delay_range,
+            &self.high_cut_range,
+        );
+        self.convolution_panel.file_path = file_path;
+        self.eq_panels = (0..EQ_BANDS)
+            .map(|i| EqBandPanel::new(&self.eq.read().unwrap(), i, &self.eq_ranges))
+            .collect();
+        self.compressor_panel =
+            CompressorPanel::new(&self.compressor.read().unwrap(), &self.compressor_ranges);
+        self.show_step_lock();
+    }
+
+    fn view_sequencer(&mut self) -> Element<Message> {
+        let back = Button::new(&mut self.back_button_state, Text::new("Back").size(12))
+            .on_press(Message::ShowPage(Page::Synth));
+
+        Container::new(
+            Column::new()
+                .push(
+                    Row::new()
+                        .spacing(20)
+                        .padding(20)
+                        .align_items(Alignment::Center)
+                        .push(back)
+                        .push(Text::new("Step Sequencer").size(16)),
+                )
+                .push(
+                    Container::new(self.sequencer_panel.view())
+                        .padding(20)
+                        .width(Length::Fill),
+                ),
+        )
+        .width(Length::Units(650))
+        .height(Length::Units(465))
+        .style(styling::EffectsContainer)
+        .into()
+    }
+
+    /// Changes the selected step of the sequencer and its copy in the grid.
+    fn update_step(&mut self, update: impl Fn(&mut Step)) {
+        let i = self.sequencer_panel.selected;
+        update(&mut self.sequencer.write().unwrap().steps[i]);
+        update(&mut self.sequencer_panel.steps[i]);
+    }
+
+    fn show_step_lock(&mut self) {
+        let parameter = self
+            .sequencer_panel
+            .lock_parameter_selected
+            .unwrap_or_default();
+        let patch = self.patch_value(parameter);
+        self.sequencer_panel
+            .show_lock(patch, &self.sequencer_ranges);
+    }
+
+    /// Value of a parameter in the patch, which a step can lock to a value of its own.
+    fn patch_value(&self, parameter: LockParameter) -> f64 {
+        let filter = |i: usize| self.filters[i].read().unwrap().clone();
+        match parameter {
+            LockParameter::Cutoff1 => filter(0).cutoff,
+            LockParameter::Cutoff2 => filter(1).cutoff,
+            LockParameter::Resonance1 => filter(0).resonance,
+            LockParameter::Resonance2 => filter(1).resonance,
+            LockParameter::Drive1 => filter(0).drive,
+            LockParameter::Drive2 => filter(1).drive,
+            LockParameter::EnvelopeAmount1 => filter(0).envelope_amount,
+            LockParameter::EnvelopeAmount2 => filter(1).envelope_amount,
+            LockParameter::FilterBalance => self.routing.read().unwrap().balance,
+            LockParameter::FmIndex => self.cross_mod.read().unwrap().fm_index,
+        }
+    }
+}
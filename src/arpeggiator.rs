@@ -0,0 +1,394 @@
+use crate::modulation::random;
+use crate::tempo::NoteDivision;
+use std::time::{Duration, Instant};
+
+const MAX_OCTAVES: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArpMode {
+    Up,
+    Down,
+    UpDown,
+    Random,
+    AsPlayed,
+}
+
+impl ArpMode {
+    pub const ALL: [ArpMode; 5] = [
+        ArpMode::Up,
+        ArpMode::Down,
+        ArpMode::UpDown,
+        ArpMode::Random,
+        ArpMode::AsPlayed,
+    ];
+}
+
+impl Default for ArpMode {
+    fn default() -> ArpMode {
+        ArpMode::Up
+    }
+}
+
+impl std::fmt::Display for ArpMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ArpMode::Up => "Up",
+                ArpMode::Down => "Down",
+                ArpMode::UpDown => "Up-Down",
+                ArpMode::Random => "Random",
+                ArpMode::AsPlayed => "As Played",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct ArpSettings {
+    pub enabled: bool,
+    pub mode: ArpMode,
+    /// Octaves the pattern spans, from 1 to `MAX_OCTAVES`.
+    pub octaves: u8,
+    /// Length of a step.
+    pub rate: NoteDivision,
+    /// Share of a step that each note is held for.
+    pub gate: f64,
+    /// Delay of every second step from 0 to 1, where 1 is half a step.
+    pub swing: f64,
+    /// Keep playing the pattern after the keys are released.
+    pub latch: bool,
+}
+
+impl Default for ArpSettings {
+    fn default() -> ArpSettings {
+        ArpSettings {
+            enabled: false,
+            mode: ArpMode::Up,
+            octaves: 1,
+            rate: NoteDivision::Sixteenth,
+            gate: 0.5,
+            swing: 0.0,
+            latch: false,
+        }
+    }
+}
+
+/// What the arpeggiator asks of the voice allocator.
+pub enum ArpEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+}
+
+/// Turns the held keys into a rhythmic pattern of single notes.
+pub struct Arpeggiator {
+    /// Notes of the pattern with their velocities, in the order they were played.
+    notes: Vec<(u8, u8)>,
+    /// Keys that are physically down, which differ from `notes` while latched.
+    pressed: Vec<u8>,
+    step: usize,
+    /// Start of the next step before swing is applied.
+    next_step: Option<Instant>,
+    /// Notes that are playing and when they end.
+    sounding: Vec<(u8, Instant)>,
+    /// State of the random mode, which differs from run to run.
+    seed: u32,
+}
+
+impl Arpeggiator {
+    /// Creates an arpeggiator whose random mode is seeded from the clock of the synth.
+    pub fn new(epoch: Instant) -> Arpeggiator {
+        Arpeggiator {
+            notes: Vec::new(),
+            pressed: Vec::new(),
+            step: 0,
+            next_step: None,
+            sounding: Vec::new(),
+            seed: epoch.elapsed().subsec_nanos(),
+        }
+    }
+
+    pub fn note_on(&mut self, settings: &ArpSettings, note: u8, velocity: u8) {
+        // With the latch on, the first key after letting go of all of them starts a new pattern
+        if settings.latch && self.pressed.is_empty() {
+            self.notes.clear();
+        }
+        self.pressed.push(note);
+        if !self.notes.iter().any(|(n, _)| *n == note) {
+            self.notes.push((note, velocity));
+        }
+    }
+
+    pub fn note_off(&mut self, settings: &ArpSettings, note: u8) {
+        self.pressed.retain(|n| *n != note);
+        if !settings.latch {
+            self.notes.retain(|(n, _)| *n != note);
+        }
+    }
+
+    /// Advances the pattern to `now`, returning the notes to start and stop.
+    pub fn tick(&mut self, settings: &ArpSettings, tempo: f64, now: Instant) -> Vec<ArpEvent> {
+        let mut events = Vec::new();
+        if !settings.enabled {
+            self.notes.clear();
+            self.pressed.clear();
+        } else if !settings.latch {
+            let pressed = &self.pressed;
+            self.notes.retain(|(n, _)| pressed.contains(n));
+        }
+
+        self.sounding.retain(|(note, end)| {
+            let ended = *end <= now || !settings.enabled;
+            if ended {
+                events.push(ArpEvent::NoteOff { note: *note });
+            }
+            !ended
+        });
+
+        let pattern = self.pattern(settings);
+        if pattern.is_empty() {
+            self.next_step = None;
+            self.step = 0;
+            return events;
+        }
+
+        let length = Duration::from_secs_f64(settings.rate.seconds(tempo.max(1.0)));
+        // The first key plays at once. After a stall the pattern goes on from now, dropping
+        // the steps it missed rather than playing them all in one go
+        let next_step = match self.next_step {
+            Some(next_step) if now < next_step + length => next_step,
+            _ => now,
+        };
+        let swing = if self.step % 2 == 1 {
+            length.mul_f64(settings.swing.clamp(0.0, 1.0) / 2.0)
+        } else {
+            Duration::ZERO
+        };
+        let start = next_step + swing;
+        if now >= start {
+            let (note, velocity) = match settings.mode {
+                ArpMode::Random => {
+                    self.seed = self.seed.wrapping_add(1);
+                    let index = (random(self.seed) + 1.0) / 2.0 * pattern.len() as f64;
+                    pattern[(index as usize).min(pattern.len() - 1)]
+                }
+                _ => pattern[self.step % pattern.len()],
+            };
+            let gate = length.mul_f64(settings.gate.clamp(0.05, 1.0));
+            events.push(ArpEvent::NoteOn { note, velocity });
+            self.sounding.push((note, start + gate));
+            self.step += 1;
+            self.next_step = Some(next_step + length);
+        } else {
+            self.next_step = Some(next_step);
+        }
+        events
+    }
+
+    /// Notes of one cycle of the pattern, across all octaves.
+    fn pattern(&self, settings: &ArpSettings) -> Vec<(u8, u8)> {
+        let mut notes = self.notes.clone();
+        if settings.mode != ArpMode::AsPlayed {
+            notes.sort_unstable_by_key(|(n, _)| *n);
+        }
+        let mut pattern: Vec<(u8, u8)> = (0..settings.octaves.clamp(1, MAX_OCTAVES))
+            .flat_map(|octave| {
+                notes
+                    .iter()
+                    .filter_map(move |(n, v)| n.checked_add(octave * 12).map(|n| (n, *v)))
+            })
+            .filter(|(n, _)| *n < 128)
+            .collect();
+
+        match settings.mode {
+            ArpMode::Down => pattern.reverse(),
+            ArpMode::UpDown if pattern.len() > 2 => {
+                // The top and bottom notes are not repeated at the turns
+                let down: Vec<(u8, u8)> = pattern[1..pattern.len() - 1]
+                    .iter()
+                    .rev()
+                    .copied()
+                    .collect();
+                pattern.extend(down);
+            }
+            _ => {}
+        }
+        pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Length of a sixteenth at the tempo of the tests.
+    const STEP: Duration = Duration::from_millis(125);
+    const TEMPO: f64 = 120.0;
+
+    fn settings(mode: ArpMode) -> ArpSettings {
+        ArpSettings {
+            enabled: true,
+            mode,
+            ..ArpSettings::default()
+        }
+    }
+
+    fn holding(settings: &ArpSettings, notes: &[u8]) -> Arpeggiator {
+        let mut arpeggiator = Arpeggiator::new(Instant::now());
+        for note in notes {
+            arpeggiator.note_on(settings, *note, 100);
+        }
+        arpeggiator
+    }
+
+    fn started(events: &[ArpEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ArpEvent::NoteOn { note, .. } => Some(*note),
+                ArpEvent::NoteOff { .. } => None,
+            })
+            .collect()
+    }
+
+    fn stopped(events: &[ArpEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ArpEvent::NoteOff { note } => Some(*note),
+                ArpEvent::NoteOn { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Notes started on the first `steps` steps, ticking once at the start of each.
+    fn play(arpeggiator: &mut Arpeggiator, settings: &ArpSettings, steps: u32) -> Vec<u8> {
+        let start = Instant::now();
+        (0..steps)
+            .flat_map(|step| started(&arpeggiator.tick(settings, TEMPO, start + STEP * step)))
+            .collect()
+    }
+
+    #[test]
+    fn patterns_follow_the_mode() {
+        let played = [64, 60, 67];
+        let pattern = |mode| {
+            let settings = settings(mode);
+            holding(&settings, &played)
+                .pattern(&settings)
+                .iter()
+                .map(|(note, _)| *note)
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(pattern(ArpMode::Up), vec![60, 64, 67]);
+        assert_eq!(pattern(ArpMode::Down), vec![67, 64, 60]);
+        assert_eq!(pattern(ArpMode::UpDown), vec![60, 64, 67, 64]);
+        assert_eq!(pattern(ArpMode::AsPlayed), vec![64, 60, 67]);
+    }
+
+    #[test]
+    fn steps_play_the_pattern_in_order() {
+        let settings = settings(ArpMode::UpDown);
+        let mut arpeggiator = holding(&settings, &[60, 64, 67]);
+        assert_eq!(
+            play(&mut arpeggiator, &settings, 6),
+            vec![60, 64, 67, 64, 60, 64]
+        );
+    }
+
+    #[test]
+    fn random_steps_pick_among_the_held_notes() {
+        let settings = settings(ArpMode::Random);
+        let mut arpeggiator = holding(&settings, &[60, 64, 67]);
+        arpeggiator.seed = 1;
+        let notes = play(&mut arpeggiator, &settings, 32);
+        assert_eq!(notes.len(), 32);
+        assert!(notes.iter().all(|note| [60, 64, 67].contains(note)));
+        for note in [60, 64, 67] {
+            assert!(notes.contains(&note));
+        }
+    }
+
+    #[test]
+    fn octaves_repeat_the_pattern_higher_up() {
+        let settings = ArpSettings {
+            octaves: 2,
+            ..settings(ArpMode::Up)
+        };
+        let mut arpeggiator = holding(&settings, &[60, 64]);
+        assert_eq!(
+            play(&mut arpeggiator, &settings, 5),
+            vec![60, 64, 72, 76, 60]
+        );
+
+        // Notes that would go above the MIDI range are left out
+        let mut arpeggiator = holding(&settings, &[120]);
+        assert_eq!(play(&mut arpeggiator, &settings, 2), vec![120, 120]);
+    }
+
+    #[test]
+    fn notes_are_held_for_the_gate() {
+        let settings = ArpSettings {
+            gate: 0.5,
+            ..settings(ArpMode::Up)
+        };
+        let mut arpeggiator = holding(&settings, &[60]);
+        let start = Instant::now();
+        assert_eq!(
+            started(&arpeggiator.tick(&settings, TEMPO, start)),
+            vec![60]
+        );
+        let before = arpeggiator.tick(&settings, TEMPO, start + Duration::from_millis(62));
+        assert!(stopped(&before).is_empty());
+        let after = arpeggiator.tick(&settings, TEMPO, start + Duration::from_millis(63));
+        assert_eq!(stopped(&after), vec![60]);
+    }
+
+    #[test]
+    fn swing_delays_every_second_step() {
+        let settings = ArpSettings {
+            swing: 1.0,
+            ..settings(ArpMode::Up)
+        };
+        let mut arpeggiator = holding(&settings, &[60, 64]);
+        let start = Instant::now();
+        let at = |arpeggiator: &mut Arpeggiator, millis| {
+            started(&arpeggiator.tick(&settings, TEMPO, start + Duration::from_millis(millis)))
+        };
+        assert_eq!(at(&mut arpeggiator, 0), vec![60]);
+        // Full swing moves the second step half a step later
+        assert!(at(&mut arpeggiator, 125).is_empty());
+        assert!(at(&mut arpeggiator, 187).is_empty());
+        assert_eq!(at(&mut arpeggiator, 188), vec![64]);
+        // The step after it is back on the grid
+        assert!(at(&mut arpeggiator, 249).is_empty());
+        assert_eq!(at(&mut arpeggiator, 250), vec![60]);
+    }
+
+    #[test]
+    fn latch_keeps_playing_after_the_keys_are_released() {
+        let settings = ArpSettings {
+            latch: true,
+            ..settings(ArpMode::Up)
+        };
+        let mut arpeggiator = holding(&settings, &[60, 64]);
+        arpeggiator.note_off(&settings, 60);
+        arpeggiator.note_off(&settings, 64);
+        assert_eq!(play(&mut arpeggiator, &settings, 3), vec![60, 64, 60]);
+
+        // The first key after letting go of all of them starts a new pattern
+        arpeggiator.note_on(&settings, 67, 100);
+        arpeggiator.note_on(&settings, 72, 100);
+        assert_eq!(arpeggiator.pattern(&settings), vec![(67, 100), (72, 100)]);
+    }
+
+    #[test]
+    fn released_keys_stop_without_latch() {
+        let settings = settings(ArpMode::Up);
+        let mut arpeggiator = holding(&settings, &[60, 64]);
+        arpeggiator.note_off(&settings, 60);
+        arpeggiator.note_off(&settings, 64);
+        assert!(play(&mut arpeggiator, &settings, 3).is_empty());
+    }
+}
@@ -1,19 +1,51 @@
-use crate::oscillator::Oscillator;
+use crate::arpeggiator::{ArpEvent, ArpSettings, Arpeggiator};
+use crate::effects::{
+    CompressorSettings, ConvolutionSettings, DelaySettings, DistortionSettings, EffectChain,
+    EqSettings, MasterBus, ModulationSettings, ReverbSettings,
+};
+use crate::filter::{Filter, Routing};
+use crate::modulation::{Lfo, ModSlot};
+use crate::mpe::{ChannelRole, Mpe, RpnReceiver};
+use crate::oscillator::{CrossMod, Oscillator};
+use crate::sequencer::{
+    LockParameter, Sequencer, SequencerEvent, SequencerSettings, LOCK_PARAMETERS,
+};
+use crate::voice::Voice;
+use crate::voicing::{MonoVoice, VoiceMode, VoicingSettings};
 use anyhow::bail;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Sample, SampleFormat, StreamConfig};
+use cpal::{Device, Sample, SampleFormat, Stream, StreamConfig};
 use crossbeam_queue::SegQueue;
 use fundsp::hacker::var;
 use fundsp::prelude::{An, Tag, Var};
 use log::info;
 use midi_msg::{ChannelVoiceMsg, MidiMsg};
 use midir::{Ignore, MidiInput, MidiInputConnection};
-use std::collections::VecDeque;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 const PITCH_TAG: Tag = 1;
-const FINISHED_TAG: Tag = PITCH_TAG + 1;
+pub const FINISHED_TAG: Tag = PITCH_TAG + 1;
 const RELEASE_TAG: Tag = FINISHED_TAG + 1;
+const MOD_WHEEL_TAG: Tag = RELEASE_TAG + 1;
+const AFTERTOUCH_TAG: Tag = MOD_WHEEL_TAG + 1;
+const BREATH_TAG: Tag = AFTERTOUCH_TAG + 1;
+const VOLUME_TAG: Tag = BREATH_TAG + 1;
+const EXPRESSION_TAG: Tag = VOLUME_TAG + 1;
+const PRESSURE_TAG: Tag = EXPRESSION_TAG + 1;
+const TIMBRE_TAG: Tag = PRESSURE_TAG + 1;
+const NOTE_TAG: Tag = TIMBRE_TAG + 1;
+const LOCK_TAG: Tag = NOTE_TAG + 1;
+
+const MOD_WHEEL_CC: u8 = 1;
+const BREATH_CC: u8 = 2;
+const VOLUME_CC: u8 = 7;
+const EXPRESSION_CC: u8 = 11;
+const TIMBRE_CC: u8 = 74;
+
+/// Voices the stream plays at once. Notes beyond this are not played, since making room for
+/// them would allocate on the audio thread.
+const MAX_VOICES: usize = 64;
 
 pub fn run_midi(midi_queue: Arc<SegQueue<MidiMsg>>) -> anyhow::Result<MidiInputConnection<()>> {
     let mut midi_in = MidiInput::new("midir reading input")?;
@@ -47,130 +79,520 @@ pub fn run_midi(midi_queue: Arc<SegQueue<MidiMsg>>) -> anyhow::Result<MidiInputC
     Ok(connection)
 }
 
-pub fn setup_output(
-    midi_out: Arc<SegQueue<MidiMsg>>,
-    oscillators: Vec<Arc<RwLock<Oscillator>>>,
-    filters: Vec<Arc<RwLock<(f64, f64)>>>,
-) {
+/// Performance controllers received over MIDI, each normalized to 0 to 1.
+#[derive(Clone)]
+pub struct Controllers {
+    pub mod_wheel: An<Var<f64>>,
+    pub aftertouch: An<Var<f64>>,
+    pub breath: An<Var<f64>>,
+    pub volume: An<Var<f64>>,
+    pub expression: An<Var<f64>>,
+    /// Pitch bend of global channels as a frequency ratio.
+    pub pitch_bend: An<Var<f64>>,
+    /// Timbre (CC74) of global channels, which new notes start from.
+    pub timbre: An<Var<f64>>,
+}
+
+impl Default for Controllers {
+    fn default() -> Controllers {
+        Controllers {
+            mod_wheel: var(MOD_WHEEL_TAG, 0.0),
+            aftertouch: var(AFTERTOUCH_TAG, 0.0),
+            breath: var(BREATH_TAG, 0.0),
+            // Full level until a controller says otherwise
+            volume: var(VOLUME_TAG, 1.0),
+            expression: var(EXPRESSION_TAG, 1.0),
+            pitch_bend: var(PITCH_TAG, 1.0),
+            timbre: var(TIMBRE_TAG, 0.0),
+        }
+    }
+}
+
+impl Controllers {
+    /// Applies a control change, returning false for controllers the synth does not use.
+    pub fn set(&self, control: u8, value: u8) -> bool {
+        let value = value as f64 / 127.0;
+        match control {
+            MOD_WHEEL_CC => self.mod_wheel.set_value(value),
+            BREATH_CC => self.breath.set_value(value),
+            VOLUME_CC => self.volume.set_value(value),
+            EXPRESSION_CC => self.expression.set_value(value),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Output gain from channel volume and expression, following the squared curve MIDI
+    /// recommends for both.
+    pub fn gain(&self) -> f64 {
+        (self.volume.value() * self.expression.value()).powi(2)
+    }
+}
+
+/// Values through which the MIDI thread steers a single playing voice.
+#[derive(Clone)]
+pub struct VoiceControls {
+    pub releasing: An<Var<f64>>,
+    pub finished: An<Var<f64>>,
+    pub pitch_bend: An<Var<f64>>,
+    /// Polyphonic aftertouch of the note, from 0 to 1.
+    pub pressure: An<Var<f64>>,
+    /// Timbre (CC74) of the note, from 0 to 1.
+    pub timbre: An<Var<f64>>,
+    /// Value of each `LockParameter` that a sequencer step locks, or NaN where the patch
+    /// decides.
+    pub locks: Vec<An<Var<f64>>>,
+    /// Note the voice plays, which legato moves to another note without a new voice.
+    pub note: An<Var<f64>>,
+    /// Note the pitch glides from at the start of the voice.
+    pub glide_from: Option<u8>,
+}
+
+impl Default for VoiceControls {
+    fn default() -> VoiceControls {
+        VoiceControls {
+            releasing: var(RELEASE_TAG, 0.0),
+            finished: var(FINISHED_TAG, 0.0),
+            pitch_bend: var(PITCH_TAG, 1.0),
+            pressure: var(PRESSURE_TAG, 0.0),
+            timbre: var(TIMBRE_TAG, 0.0),
+            locks: LockParameter::ALL
+                .iter()
+                .map(|_| var(LOCK_TAG, f64::NAN))
+                .collect(),
+            note: var(NOTE_TAG, 0.0),
+            glide_from: None,
+        }
+    }
+}
+
+impl VoiceControls {
+    /// Controls for another voice of the same note, which shares its expression but not its
+    /// envelopes.
+    pub fn renewed(&self) -> VoiceControls {
+        let defaults = VoiceControls::default();
+        VoiceControls {
+            releasing: defaults.releasing,
+            finished: defaults.finished,
+            note: defaults.note,
+            ..self.clone()
+        }
+    }
+
+    /// Locks the parameters a sequencer step sets, leaving the others as they are.
+    pub fn lock(&self, locks: &[Option<f64>; LOCK_PARAMETERS]) {
+        for (var, lock) in self.locks.iter().zip(locks) {
+            if let Some(value) = lock {
+                var.set_value(*value);
+            }
+        }
+    }
+}
+
+/// A note that has been started and not yet released.
+struct HeldNote {
+    channel: u8,
+    note: u8,
+    controls: VoiceControls,
+    /// Whether this is the voice of the mono modes, which the mono voice releases itself.
+    mono: bool,
+}
+
+/// Keeps the voice of the mono modes among the held notes while a key plays it, so the
+/// expression of its channel reaches it.
+fn hold_mono(held: &mut Vec<HeldNote>, mono: &MonoVoice) {
+    held.retain(|n| !n.mono);
+    if let Some((Some(channel), note, controls)) = mono.playing() {
+        held.push(HeldNote {
+            channel,
+            note,
+            controls: controls.clone(),
+            mono: true,
+        });
+    }
+}
+
+/// Synth settings shared between the GUI and the audio engine.
+#[derive(Clone)]
+pub struct Parameters {
+    pub oscillators: Arc<RwLock<Vec<Oscillator>>>,
+    pub filters: Vec<Arc<RwLock<Filter>>>,
+    pub routing: Arc<RwLock<Routing>>,
+    pub cross_mod: Arc<RwLock<CrossMod>>,
+    pub lfos: Vec<Arc<RwLock<Lfo>>>,
+    pub matrix: Arc<RwLock<Vec<ModSlot>>>,
+    pub controllers: Controllers,
+    pub mpe: Arc<RwLock<Mpe>>,
+    pub distortion: Arc<RwLock<DistortionSettings>>,
+    pub modulation: Arc<RwLock<ModulationSettings>>,
+    pub delay: Arc<RwLock<DelaySettings>>,
+    pub reverb: Arc<RwLock<ReverbSettings>>,
+    pub convolution: Arc<RwLock<ConvolutionSettings>>,
+    pub eq: Arc<RwLock<EqSettings>>,
+    pub compressor: Arc<RwLock<CompressorSettings>>,
+    pub chain: Arc<RwLock<EffectChain>>,
+    pub arpeggiator: Arc<RwLock<ArpSettings>>,
+    pub sequencer: Arc<RwLock<SequencerSettings>>,
+    pub voicing: Arc<RwLock<VoicingSettings>>,
+    /// Tempo in beats per minute that synced times follow.
+    pub tempo: Arc<RwLock<f64>>,
+    /// Clock that free-running LFOs are in phase with.
+    pub epoch: Instant,
+}
+
+/// Starts the audio and MIDI threads, returning the sample rate of the output.
+pub fn setup_output(midi_out: Arc<SegQueue<MidiMsg>>, parameters: Parameters) -> f64 {
     let host = cpal::default_host();
     let device = host
         .default_output_device()
         .expect("failed to find a default output device");
     info!("Audio device: {:?}", device.name().expect("None"));
     let config = device.default_output_config().unwrap();
+    let sample_rate = config.sample_rate().0 as f64;
     match config.sample_format() {
-        SampleFormat::F32 => {
-            output_sound::<f32>(oscillators, filters, midi_out, device, config.into())
-        }
-        SampleFormat::I16 => {
-            output_sound::<i16>(oscillators, filters, midi_out, device, config.into())
-        }
-        SampleFormat::U16 => {
-            output_sound::<u16>(oscillators, filters, midi_out, device, config.into())
-        }
+        SampleFormat::F32 => output_sound::<f32>(parameters, midi_out, device, config.into()),
+        SampleFormat::I16 => output_sound::<i16>(parameters, midi_out, device, config.into()),
+        SampleFormat::U16 => output_sound::<u16>(parameters, midi_out, device, config.into()),
     }
+    sample_rate
 }
 
 fn output_sound<T: Sample>(
-    oscillators: Vec<Arc<RwLock<Oscillator>>>,
-    filters: Vec<Arc<RwLock<(f64, f64)>>>,
+    parameters: Parameters,
     midi_out: Arc<SegQueue<MidiMsg>>,
     device: Device,
     config: StreamConfig,
 ) {
-    let device = Arc::new(device);
-    let config = Arc::new(config);
     std::thread::spawn(move || {
-        let mut awaiting_release: VecDeque<An<Var<f64>>> = VecDeque::new();
+        let sample_rate = config.sample_rate.0 as f64;
+        let new_voices = Arc::new(SegQueue::new());
+        let finished_voices = Arc::new(SegQueue::new());
+        let stream = build_stream::<T>(
+            &parameters,
+            new_voices.clone(),
+            finished_voices.clone(),
+            &device,
+            &config,
+        );
+        stream.play().unwrap();
+
+        let mut held: Vec<HeldNote> = Vec::new();
+        let mut rpn = RpnReceiver::default();
+        // Member channels keep their last bend and timbre for the next note played on them
+        let mut channel_bends = [1.0; 16];
+        let mut channel_timbres = [0.0; 16];
+        let mut arpeggiator = Arpeggiator::new(parameters.epoch);
+        let mut arp_notes: Vec<HeldNote> = Vec::new();
+        let mut sequencer = Sequencer::default();
+        let mut sequenced: Vec<HeldNote> = Vec::new();
+        // Controls of the last note of the sequencer, which tied steps lock parameters on
+        let mut step_controls: Option<VoiceControls> = None;
+        let mut mono = MonoVoice::new(sample_rate);
         loop {
-            if let Some(MidiMsg::ChannelVoice { channel: _, msg }) = midi_out.pop() {
-                info!("Received {msg:?}");
-                match msg {
-                    ChannelVoiceMsg::NoteOff {
-                        note: _,
-                        velocity: _,
+            let voicing = parameters.voicing.read().unwrap().clone();
+            let poly = voicing.mode == VoiceMode::Poly;
+            let arp = parameters.arpeggiator.read().unwrap().clone();
+            let tempo = *parameters.tempo.read().unwrap();
+            parameters.convolution.read().unwrap().free_retired();
+            // Voices are freed here rather than on the audio thread
+            while finished_voices.pop().is_some() {}
+            for event in arpeggiator.tick(&arp, tempo, Instant::now()) {
+                match event {
+                    ArpEvent::NoteOn { note, velocity } => {
+                        let controls = VoiceControls::default();
+                        controls
+                            .timbre
+                            .set_value(parameters.controllers.timbre.value());
+                        if !poly {
+                            if let Some(voice) =
+                                mono.note_on(&voicing, &parameters, None, note, velocity, controls)
+                            {
+                                new_voices.push(voice);
+                            }
+                            hold_mono(&mut held, &mono);
+                            continue;
+                        }
+                        arp_notes.push(HeldNote {
+                            channel: 0,
+                            note,
+                            controls: controls.clone(),
+                            mono: false,
+                        });
+                        new_voices.push(Voice::new(
+                            &parameters,
+                            note,
+                            velocity,
+                            controls,
+                            sample_rate,
+                        ));
+                    }
+                    ArpEvent::NoteOff { note } => {
+                        if let Some(voice) = mono.note_off(&voicing, &parameters, None, note) {
+                            new_voices.push(voice);
+                        }
+                        hold_mono(&mut held, &mono);
+                        if let Some(i) = arp_notes.iter().position(|n| n.note == note) {
+                            arp_notes.remove(i).controls.releasing.set_value(1.0);
+                        }
+                    }
+                }
+            }
+
+            let events =
+                sequencer.tick(&parameters.sequencer.read().unwrap(), tempo, Instant::now());
+            for event in events {
+                match event {
+                    SequencerEvent::NoteOn {
+                        note,
+                        velocity,
+                        locks,
                     } => {
-                        for oscillator in &oscillators {
-                            oscillator
-                                .read()
-                                .unwrap()
-                                .release_all(&mut awaiting_release);
+                        let controls = VoiceControls::default();
+                        controls.lock(&locks);
+                        controls
+                            .timbre
+                            .set_value(parameters.controllers.timbre.value());
+                        step_controls = Some(controls.clone());
+                        if !poly {
+                            if let Some(voice) =
+                                mono.note_on(&voicing, &parameters, None, note, velocity, controls)
+                            {
+                                new_voices.push(voice);
+                            }
+                            hold_mono(&mut held, &mono);
+                            // Legato keeps the controls of the voice that is already playing
+                            step_controls = mono.playing().map(|(_, _, c)| c.clone());
+                            continue;
+                        }
+                        sequenced.push(HeldNote {
+                            channel: 0,
+                            note,
+                            controls: controls.clone(),
+                            mono: false,
+                        });
+                        new_voices.push(Voice::new(
+                            &parameters,
+                            note,
+                            velocity,
+                            controls,
+                            sample_rate,
+                        ));
+                    }
+                    SequencerEvent::Locks { locks } => {
+                        if let Some(controls) = &step_controls {
+                            controls.lock(&locks);
+                        }
+                    }
+                    SequencerEvent::NoteOff { note } => {
+                        if let Some(voice) = mono.note_off(&voicing, &parameters, None, note) {
+                            new_voices.push(voice);
+                        }
+                        hold_mono(&mut held, &mono);
+                        if let Some(i) = sequenced.iter().position(|n| n.note == note) {
+                            sequenced.remove(i).controls.releasing.set_value(1.0);
+                        }
+                    }
+                }
+            }
+
+            if let Some(message) = midi_out.pop() {
+                let bytes = message.to_midi();
+                let control = control_change(&bytes);
+                let channel = bytes.first().map_or(0, |status| status & 0x0f);
+                let msg = match message {
+                    MidiMsg::ChannelVoice { channel: _, msg } => msg,
+                    _ => continue,
+                };
+                info!("Received {msg:?} on channel {}", channel + 1);
+                let role = parameters.mpe.read().unwrap().role(channel);
+                let on_channel =
+                    |n: &&HeldNote| role == ChannelRole::Global || n.channel == channel;
+                match msg {
+                    // A note on without velocity is how many devices send a note off
+                    ChannelVoiceMsg::NoteOff { note, .. }
+                    | ChannelVoiceMsg::NoteOn { note, velocity: 0 } => {
+                        arpeggiator.note_off(&arp, note);
+                        if let Some(voice) =
+                            mono.note_off(&voicing, &parameters, Some(channel), note)
+                        {
+                            new_voices.push(voice);
+                        }
+                        hold_mono(&mut held, &mono);
+                        if let Some(i) = held
+                            .iter()
+                            .position(|n| !n.mono && n.note == note && n.channel == channel)
+                        {
+                            held.remove(i).controls.releasing.set_value(1.0);
                         }
                     }
                     ChannelVoiceMsg::NoteOn { note, velocity } => {
-                        let releasing = var(RELEASE_TAG, 0.0);
-                        awaiting_release.push_back(releasing.clone());
-                        start_sound::<T>(
-                            oscillators.clone(),
-                            filters.clone(),
+                        // The arpeggiator plays the held keys instead of the keys themselves
+                        if arp.enabled {
+                            arpeggiator.note_on(&arp, note, velocity);
+                            continue;
+                        }
+                        let controls = VoiceControls::default();
+                        if role == ChannelRole::Member {
+                            controls
+                                .pitch_bend
+                                .set_value(channel_bends[channel as usize]);
+                            controls.timbre.set_value(channel_timbres[channel as usize]);
+                        } else {
+                            controls
+                                .timbre
+                                .set_value(parameters.controllers.timbre.value());
+                        }
+                        if !poly {
+                            if let Some(voice) = mono.note_on(
+                                &voicing,
+                                &parameters,
+                                Some(channel),
+                                note,
+                                velocity,
+                                controls,
+                            ) {
+                                new_voices.push(voice);
+                            }
+                            hold_mono(&mut held, &mono);
+                            continue;
+                        }
+                        held.push(HeldNote {
+                            channel,
+                            note,
+                            controls: controls.clone(),
+                            mono: false,
+                        });
+                        new_voices.push(Voice::new(
+                            &parameters,
                             note,
                             velocity,
-                            releasing,
-                            device.clone(),
-                            config.clone(),
-                        );
+                            controls,
+                            sample_rate,
+                        ));
+                    }
+                    ChannelVoiceMsg::PitchBend { bend } => {
+                        let ratio = parameters.mpe.read().unwrap().bend_ratio(channel, bend);
+                        match role {
+                            ChannelRole::Global => {
+                                parameters.controllers.pitch_bend.set_value(ratio)
+                            }
+                            ChannelRole::Member => {
+                                channel_bends[channel as usize] = ratio;
+                                for n in held.iter().filter(on_channel) {
+                                    n.controls.pitch_bend.set_value(ratio);
+                                }
+                            }
+                        }
+                    }
+                    ChannelVoiceMsg::ChannelPressure { pressure } => {
+                        let pressure = pressure as f64 / 127.0;
+                        match role {
+                            ChannelRole::Global => {
+                                parameters.controllers.aftertouch.set_value(pressure)
+                            }
+                            ChannelRole::Member => {
+                                for n in held.iter().filter(on_channel) {
+                                    n.controls.pressure.set_value(pressure);
+                                }
+                            }
+                        }
+                    }
+                    ChannelVoiceMsg::PolyPressure { note, pressure } => {
+                        for n in held.iter().filter(on_channel).filter(|n| n.note == note) {
+                            n.controls.pressure.set_value(pressure as f64 / 127.0);
+                        }
+                    }
+                    ChannelVoiceMsg::ControlChange { .. } => {
+                        if let Some((control, value)) = control {
+                            let mut mpe = parameters.mpe.write().unwrap();
+                            if rpn.receive(&mut mpe, channel, control, value) {
+                                continue;
+                            }
+                            drop(mpe);
+
+                            if control == TIMBRE_CC {
+                                let timbre = value as f64 / 127.0;
+                                if role == ChannelRole::Global {
+                                    parameters.controllers.timbre.set_value(timbre);
+                                } else {
+                                    channel_timbres[channel as usize] = timbre;
+                                }
+                                for n in held.iter().filter(on_channel) {
+                                    n.controls.timbre.set_value(timbre);
+                                }
+                            } else if !parameters.controllers.set(control, value) {
+                                info!("Ignored controller {control}");
+                            }
+                        }
                     }
                     _ => {}
                 }
+            } else {
+                // Nothing to do until the next message or step of the arpeggiator or sequencer
+                std::thread::sleep(Duration::from_millis(1));
             }
         }
     });
 }
 
-fn start_sound<T: Sample>(
-    oscillators: Vec<Arc<RwLock<Oscillator>>>,
-    filters: Vec<Arc<RwLock<(f64, f64)>>>,
-    note: u8,
-    velocity: u8,
-    releasing: An<Var<f64>>,
-    device: Arc<Device>,
-    config: Arc<StreamConfig>,
-) {
-    let finished = var(FINISHED_TAG, 0.0);
-    let pitch_bend = var(PITCH_TAG, 1.0);
-
-    let f1 = filters[0].read().unwrap().clone();
-    let f2 = filters[1].read().unwrap().clone();
-
-    let mut sound_osc1 = oscillators[0].read().unwrap().generate_note(
-        note,
-        velocity,
-        releasing.clone(),
-        finished.clone(),
-        pitch_bend.clone(),
-        f1,
-    );
-    let mut sound_osc2 = oscillators[1].read().unwrap().generate_note(
-        note,
-        velocity,
-        releasing.clone(),
-        finished.clone(),
-        pitch_bend.clone(),
-        f2,
-    );
-
-    let mut next_value = move || {
-        let (l1, r1) = sound_osc1.get_stereo();
-        let (l2, r2) = sound_osc2.get_stereo();
-        (l1 + l2, r1 + r2)
-    };
+/// Controller number and 7 bit value of a control change. midi-msg decodes some controllers
+/// into named variants, so the message is read from its raw bytes instead.
+fn control_change(bytes: &[u8]) -> Option<(u8, u8)> {
+    match bytes {
+        [status, control, value, ..] if status & 0xf0 == 0xb0 => Some((*control, *value)),
+        _ => None,
+    }
+}
 
+/// Plays every voice through the master bus on a single stream, and hands voices that are
+/// done back in `finished_voices` to be dropped elsewhere. The stream stops when it is
+/// dropped, so it has to be kept by the caller.
+fn build_stream<T: Sample>(
+    parameters: &Parameters,
+    new_voices: Arc<SegQueue<Voice>>,
+    finished_voices: Arc<SegQueue<Voice>>,
+    device: &Device,
+    config: &StreamConfig,
+) -> Stream {
     let channels = config.channels as usize;
-    std::thread::spawn(move || {
-        let err_fn = |err| eprintln!("an error occurred on stream: {err}");
-        let stream = device
-            .build_output_stream(
-                &config,
-                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-                    write_data(data, channels, &mut next_value)
-                },
-                err_fn,
-            )
-            .unwrap();
+    let mut voices: Vec<Voice> = Vec::with_capacity(MAX_VOICES);
+    let mut master = MasterBus::new(parameters, config.sample_rate.0 as f64);
 
-        stream.play().unwrap();
-        while finished.value() == 0.0 {}
-    });
+    let err_fn = |err| eprintln!("an error occurred on stream: {err}");
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let mut i = 0;
+                while i < voices.len() {
+                    if voices[i].is_finished() {
+                        finished_voices.push(voices.swap_remove(i));
+                    } else {
+                        i += 1;
+                    }
+                }
+                while let Some(voice) = new_voices.pop() {
+                    if voices.len() < MAX_VOICES {
+                        voices.push(voice);
+                    } else {
+                        finished_voices.push(voice);
+                    }
+                }
+                master.update();
+
+                write_data(data, channels, &mut || {
+                    let mut sum = (0.0, 0.0);
+                    for voice in voices.iter_mut() {
+                        let (left, right) = voice.next_sample();
+                        sum.0 += left;
+                        sum.1 += right;
+                    }
+                    master.process(sum)
+                })
+            },
+            err_fn,
+        )
+        .unwrap()
 }
 
 fn write_data<T: Sample>(
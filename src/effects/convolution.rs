@@ -0,0 +1,476 @@
+use super::{DelayLine, EFFECT_SETS};
+use crossbeam_queue::SegQueue;
+use hound::{SampleFormat, WavReader};
+use std::f64::consts::TAU;
+use std::ops::{Add, Mul, Sub};
+use std::sync::Arc;
+
+/// Samples per partition, which is also the latency of the convolution.
+const BLOCK: usize = 256;
+const FFT_SIZE: usize = BLOCK * 2;
+/// Bins of the spectrum of a real signal that are not mirrors of others.
+const BINS: usize = BLOCK + 1;
+/// Longest impulse response that is loaded, to bound the work per block.
+const MAX_LENGTH: f64 = 10.0;
+const MAX_PRE_DELAY: f64 = 0.25;
+
+/// Impulse response loaded from a WAV file, with at most two channels.
+pub struct ImpulseResponse {
+    pub name: String,
+    pub channels: Vec<Vec<f64>>,
+    pub sample_rate: f64,
+}
+
+impl ImpulseResponse {
+    pub fn load(path: &str) -> anyhow::Result<ImpulseResponse> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let interleaved: Vec<f64> = match spec.sample_format {
+            SampleFormat::Float => reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| s as f64))
+                .collect::<Result<_, _>>()?,
+            SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f64;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f64 / scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let frames = interleaved.len() / spec.channels as usize;
+        let frames = frames.min((MAX_LENGTH * spec.sample_rate as f64) as usize);
+        let mut channels: Vec<Vec<f64>> = (0..(spec.channels as usize).min(2))
+            .map(|channel| {
+                (0..frames)
+                    .map(|frame| interleaved[frame * spec.channels as usize + channel])
+                    .collect()
+            })
+            .collect();
+        if channels.is_empty() || frames == 0 {
+            anyhow::bail!("impulse response is empty");
+        }
+
+        // Unit energy per channel keeps the wet level comparable between responses
+        let energy: f64 = channels.iter().flatten().map(|s| s * s).sum();
+        let scale = (channels.len() as f64 / energy.max(1e-12)).sqrt();
+        for sample in channels.iter_mut().flatten() {
+            *sample *= scale;
+        }
+
+        Ok(ImpulseResponse {
+            name: path.rsplit(['/', '\\']).next().unwrap_or(path).to_string(),
+            channels,
+            sample_rate: spec.sample_rate as f64,
+        })
+    }
+}
+
+/// Impulse response resampled to the output rate and split into the spectra of its
+/// partitions, so the audio thread only has to swap it in.
+pub struct Kernel {
+    pub name: String,
+    /// Spectra of the partitions of the left and the right channel.
+    channels: [Vec<Vec<Complex>>; 2],
+}
+
+impl Kernel {
+    pub fn new(impulse: &ImpulseResponse, sample_rate: f64) -> Kernel {
+        let ratio = impulse.sample_rate / sample_rate;
+        // A mono response is used for both channels
+        let channel = |c: usize| {
+            let samples = &impulse.channels[c.min(impulse.channels.len() - 1)];
+            partition(&resample(samples, ratio))
+        };
+        Kernel {
+            name: impulse.name.clone(),
+            channels: [channel(0), channel(1)],
+        }
+    }
+}
+
+/// A kernel together with the input history of both channels, allocated outside of the audio
+/// thread for one convolution to take over.
+pub struct KernelState {
+    kernel: Arc<Kernel>,
+    channels: [Partitioned; 2],
+}
+
+impl KernelState {
+    fn new(kernel: Arc<Kernel>) -> KernelState {
+        KernelState {
+            channels: [
+                Partitioned::new(kernel.channels[0].len()),
+                Partitioned::new(kernel.channels[1].len()),
+            ],
+            kernel,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConvolutionSettings {
+    pub kernel: Option<Arc<Kernel>>,
+    /// States of the current kernel, one for each convolution on the master bus to swap in.
+    pub prepared: Arc<SegQueue<KernelState>>,
+    /// States the audio thread has replaced, to be freed outside of it.
+    pub retired: Arc<SegQueue<KernelState>>,
+    /// Pre-delay in seconds.
+    pub pre_delay: f64,
+    /// Cutoff of the lowpass filter on the wet signal in Hz.
+    pub high_cut: f64,
+    pub mix: f64,
+}
+
+impl Default for ConvolutionSettings {
+    fn default() -> ConvolutionSettings {
+        ConvolutionSettings {
+            kernel: None,
+            prepared: Arc::new(SegQueue::new()),
+            retired: Arc::new(SegQueue::new()),
+            pre_delay: 0.0,
+            high_cut: 20000.0,
+            mix: 0.0,
+        }
+    }
+}
+
+impl ConvolutionSettings {
+    /// Makes a kernel the current one, with a state ready for every set of effects.
+    pub fn load(&mut self, kernel: Kernel) {
+        let kernel = Arc::new(kernel);
+        for _ in 0..EFFECT_SETS {
+            self.prepared.push(KernelState::new(kernel.clone()));
+        }
+        self.kernel = Some(kernel);
+    }
+
+    /// Drops the states the audio thread let go of.
+    pub fn free_retired(&self) {
+        while self.retired.pop().is_some() {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn conj(self) -> Complex {
+        Complex {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+/// In-place radix 2 FFT. The inverse transform is scaled by 1 / n.
+fn fft(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let sign = if inverse { 1.0 } else { -1.0 };
+        let angle = sign * TAU / length as f64;
+        let step = Complex {
+            re: angle.cos(),
+            im: angle.sin(),
+        };
+        for chunk in buffer.chunks_mut(length) {
+            let (evens, odds) = chunk.split_at_mut(length / 2);
+            let mut twiddle = Complex { re: 1.0, im: 0.0 };
+            for (even, odd) in evens.iter_mut().zip(odds.iter_mut()) {
+                let product = *odd * twiddle;
+                *odd = *even - product;
+                *even = *even + product;
+                twiddle = twiddle * step;
+            }
+        }
+        length <<= 1;
+    }
+
+    if inverse {
+        let scale = 1.0 / n as f64;
+        for x in buffer.iter_mut() {
+            x.re *= scale;
+            x.im *= scale;
+        }
+    }
+}
+
+/// Spectrum of each partition of an impulse response.
+fn partition(impulse: &[f64]) -> Vec<Vec<Complex>> {
+    impulse
+        .chunks(BLOCK)
+        .map(|chunk| {
+            let mut spectrum = vec![Complex::default(); FFT_SIZE];
+            for (bin, sample) in spectrum.iter_mut().zip(chunk) {
+                bin.re = *sample;
+            }
+            fft(&mut spectrum, false);
+            spectrum.truncate(BINS);
+            spectrum
+        })
+        .collect()
+}
+
+/// Uniformly partitioned overlap-save convolution of one channel with the partitions of a
+/// kernel.
+struct Partitioned {
+    /// Spectra of the most recent input blocks, one per partition, newest at `head`.
+    history: Vec<Complex>,
+    head: usize,
+    /// Blocks of the history written since the last reset, which are the only ones used.
+    filled: usize,
+    /// The previous and the current input block.
+    input: Vec<f64>,
+    /// Output of the last complete block, played while the next one fills.
+    output: Vec<f64>,
+    spectrum: Vec<Complex>,
+}
+
+impl Partitioned {
+    /// State for a kernel with `count` partitions.
+    fn new(count: usize) -> Partitioned {
+        Partitioned {
+            history: vec![Complex::default(); count * BINS],
+            head: 0,
+            filled: 0,
+            input: vec![0.0; FFT_SIZE],
+            output: vec![0.0; BLOCK],
+            spectrum: vec![Complex::default(); FFT_SIZE],
+        }
+    }
+
+    /// Forgets the input so far. The history is left as it is and only ignored, since zeroing
+    /// all of it would take too long on the audio thread.
+    fn reset(&mut self) {
+        self.head = 0;
+        self.filled = 0;
+        self.input.fill(0.0);
+        self.output.fill(0.0);
+    }
+
+    fn process_block(&mut self, partitions: &[Vec<Complex>]) {
+        for (bin, sample) in self.spectrum.iter_mut().zip(&self.input) {
+            *bin = Complex {
+                re: *sample,
+                im: 0.0,
+            };
+        }
+        fft(&mut self.spectrum, false);
+
+        let count = partitions.len();
+        self.head = (self.head + count - 1) % count;
+        self.filled = (self.filled + 1).min(count);
+        let start = self.head * BINS;
+        self.history[start..start + BINS].copy_from_slice(&self.spectrum[..BINS]);
+
+        // Each partition of the response meets the input block that is as many blocks old
+        let mut sum = [Complex::default(); BINS];
+        for (age, partition) in partitions.iter().enumerate().take(self.filled) {
+            let start = (self.head + age) % count * BINS;
+            let block = &self.history[start..start + BINS];
+            for ((bin, x), h) in sum.iter_mut().zip(block).zip(partition) {
+                *bin = *bin + *x * *h;
+            }
+        }
+
+        // The spectrum of a real signal mirrors around the middle
+        self.spectrum[..BINS].copy_from_slice(&sum);
+        for (i, bin) in self.spectrum.iter_mut().enumerate().skip(BINS) {
+            *bin = sum[FFT_SIZE - i].conj();
+        }
+        fft(&mut self.spectrum, true);
+
+        // Overlap-save: only the second half is free of wrap-around
+        for (output, bin) in self.output.iter_mut().zip(&self.spectrum[BLOCK..]) {
+            *output = bin.re;
+        }
+        self.input.copy_within(BLOCK.., 0);
+    }
+}
+
+/// Convolution reverb that places the sound in the room or cabinet an impulse response was
+/// recorded in.
+pub struct Convolution {
+    state: Option<KernelState>,
+    /// Position in the block that is being filled.
+    index: usize,
+    pre_delay: [DelayLine; 2],
+    pre_delay_samples: usize,
+    filters: [f64; 2],
+    damping: f64,
+    mix: f64,
+    sample_rate: f64,
+}
+
+impl Convolution {
+    pub fn new(sample_rate: f64) -> Convolution {
+        let length = (MAX_PRE_DELAY * sample_rate) as usize + 1;
+        Convolution {
+            state: None,
+            index: 0,
+            pre_delay: [DelayLine::new(length), DelayLine::new(length)],
+            pre_delay_samples: 0,
+            filters: [0.0; 2],
+            damping: 0.0,
+            mix: 0.0,
+            sample_rate,
+        }
+    }
+
+    /// Silences the reverb tail and the pre-delay.
+    pub fn reset(&mut self) {
+        if let Some(state) = self.state.as_mut() {
+            for channel in state.channels.iter_mut() {
+                channel.reset();
+            }
+        }
+        for line in self.pre_delay.iter_mut() {
+            line.clear();
+        }
+        self.filters = [0.0; 2];
+        self.index = 0;
+    }
+
+    /// Applies new settings. A newly loaded kernel comes with its state prepared, which is only
+    /// swapped in, and the replaced state is handed back to be freed outside of the audio
+    /// thread.
+    pub fn set(&mut self, settings: &ConvolutionSettings) {
+        let current = self.state.as_ref().map(|state| &state.kernel);
+        let changed = match (current, &settings.kernel) {
+            (Some(current), Some(new)) => !Arc::ptr_eq(current, new),
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            let state = match &settings.kernel {
+                Some(kernel) => prepared(settings, kernel),
+                None => None,
+            };
+            // Without a state for the new kernel yet, the old one plays on until there is one
+            if state.is_some() || settings.kernel.is_none() {
+                if let Some(old) = std::mem::replace(&mut self.state, state) {
+                    settings.retired.push(old);
+                }
+                self.index = 0;
+            }
+        }
+
+        self.pre_delay_samples =
+            (settings.pre_delay.clamp(0.0, MAX_PRE_DELAY) * self.sample_rate) as usize;
+        let cutoff = settings.high_cut.clamp(20.0, self.sample_rate * 0.45);
+        self.damping = (-TAU * cutoff / self.sample_rate).exp();
+        self.mix = settings.mix;
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return input,
+        };
+
+        let mut wet = [0.0; 2];
+        for (channel, x) in [input.0, input.1].into_iter().enumerate() {
+            self.pre_delay[channel].write(x);
+            let delayed = self.pre_delay[channel].read(self.pre_delay_samples);
+            let convolution = &mut state.channels[channel];
+            convolution.input[BLOCK + self.index] = delayed;
+            let output = convolution.output[self.index];
+            self.filters[channel] = output + (self.filters[channel] - output) * self.damping;
+            wet[channel] = self.filters[channel];
+        }
+
+        self.index += 1;
+        if self.index == BLOCK {
+            for (channel, partitions) in state.channels.iter_mut().zip(&state.kernel.channels) {
+                channel.process_block(partitions);
+            }
+            self.index = 0;
+        }
+
+        (
+            input.0 * (1.0 - self.mix) + wet[0] * self.mix,
+            input.1 * (1.0 - self.mix) + wet[1] * self.mix,
+        )
+    }
+}
+
+/// Takes the prepared state of a kernel, passing the states of kernels that were replaced
+/// before they got played on to be freed.
+fn prepared(settings: &ConvolutionSettings, kernel: &Arc<Kernel>) -> Option<KernelState> {
+    while let Some(state) = settings.prepared.pop() {
+        if Arc::ptr_eq(&state.kernel, kernel) {
+            return Some(state);
+        }
+        settings.retired.push(state);
+    }
+    None
+}
+
+/// Linearly interpolated copy of a signal, with `ratio` source samples per output sample.
+fn resample(data: &[f64], ratio: f64) -> Vec<f64> {
+    if (ratio - 1.0).abs() < 1e-9 {
+        return data.to_vec();
+    }
+    let length = (data.len() as f64 / ratio) as usize;
+    (0..length.max(1))
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let fract = position - index as f64;
+            let a = data.get(index).copied().unwrap_or(0.0);
+            let b = data.get(index + 1).copied().unwrap_or(0.0);
+            a + (b - a) * fract
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use super::DelayLine;
+use crate::tempo::NoteDivision;
+use std::f64::consts::TAU;
+
+const MAX_TIME: f64 = 2.0;
+/// Time constant in seconds with which the delay time follows changes, like a tape delay
+/// instead of a jump that clicks.
+const TIME_SMOOTHING: f64 = 0.05;
+
+#[derive(Clone)]
+pub struct DelaySettings {
+    /// Follow the tempo instead of `time`.
+    pub sync: bool,
+    /// Delay time in seconds, when not synced.
+    pub time: f64,
+    pub division: NoteDivision,
+    /// Share of the output fed back, from 0 to just below 1.
+    pub feedback: f64,
+    /// Cutoff of the lowpass filter in the feedback path in Hz.
+    pub high_cut: f64,
+    /// Bounce the echoes between the left and the right channel.
+    pub ping_pong: bool,
+    pub mix: f64,
+}
+
+impl Default for DelaySettings {
+    fn default() -> DelaySettings {
+        DelaySettings {
+            sync: true,
+            time: 0.3,
+            division: NoteDivision::Eighth,
+            feedback: 0.4,
+            high_cut: 8000.0,
+            ping_pong: false,
+            mix: 0.0,
+        }
+    }
+}
+
+impl DelaySettings {
+    /// Delay time in seconds at a tempo in beats per minute.
+    pub fn seconds(&self, tempo: f64) -> f64 {
+        let time = if self.sync {
+            self.division.seconds(tempo)
+        } else {
+            self.time
+        };
+        time.clamp(0.001, MAX_TIME)
+    }
+}
+
+/// Stereo feedback delay with a damped feedback path and ping-pong mode.
+pub struct Delay {
+    lines: [DelayLine; 2],
+    /// State of the one-pole lowpass filter in each feedback path.
+    filters: [f64; 2],
+    sample_rate: f64,
+    /// Current delay time in samples, which glides towards `target`.
+    delay: f64,
+    target: f64,
+    smoothing: f64,
+    feedback: f64,
+    damping: f64,
+    ping_pong: bool,
+    mix: f64,
+}
+
+impl Delay {
+    pub fn new(sample_rate: f64) -> Delay {
+        let length = (MAX_TIME * sample_rate) as usize + 2;
+        Delay {
+            lines: [DelayLine::new(length), DelayLine::new(length)],
+            filters: [0.0; 2],
+            sample_rate,
+            delay: 0.0,
+            target: 0.0,
+            smoothing: 1.0 - (-1.0 / (TIME_SMOOTHING * sample_rate)).exp(),
+            feedback: 0.0,
+            damping: 0.0,
+            ping_pong: false,
+            mix: 0.0,
+        }
+    }
+
+    /// Empties both delay lines and the feedback filters, so no echoes are left.
+    pub fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.clear();
+        }
+        self.filters = [0.0; 2];
+    }
+
+    pub fn set(&mut self, settings: &DelaySettings, tempo: f64) {
+        self.target = settings.seconds(tempo) * self.sample_rate;
+        if self.delay == 0.0 {
+            self.delay = self.target;
+        }
+        self.feedback = settings.feedback.clamp(0.0, 0.98);
+        let cutoff = settings.high_cut.clamp(20.0, self.sample_rate * 0.45);
+        self.damping = (-TAU * cutoff / self.sample_rate).exp();
+        self.ping_pong = settings.ping_pong;
+        self.mix = settings.mix;
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        self.delay += (self.target - self.delay) * self.smoothing;
+
+        // The lines are read before this sample is written to them
+        let mut echoes = [0.0; 2];
+        for (channel, echo) in echoes.iter_mut().enumerate() {
+            let delayed = self.lines[channel].read_fractional(self.delay - 1.0);
+            self.filters[channel] = delayed + (self.filters[channel] - delayed) * self.damping;
+            *echo = self.filters[channel];
+        }
+
+        let (left_in, right_in) = if self.ping_pong {
+            // Both channels enter on the left, and every repeat crosses over
+            (
+                input.0 + input.1 + echoes[1] * self.feedback,
+                echoes[0] * self.feedback,
+            )
+        } else {
+            (
+                input.0 + echoes[0] * self.feedback,
+                input.1 + echoes[1] * self.feedback,
+            )
+        };
+        self.lines[0].write(left_in);
+        self.lines[1].write(right_in);
+
+        (
+            input.0 * (1.0 - self.mix) + echoes[0] * self.mix,
+            input.1 * (1.0 - self.mix) + echoes[1] * self.mix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_arrives_after_the_delay_time() {
+        let sample_rate = 1000.0;
+        let mut delay = Delay::new(sample_rate);
+        delay.set(
+            &DelaySettings {
+                sync: false,
+                time: 0.01,
+                feedback: 0.0,
+                high_cut: 20000.0,
+                mix: 1.0,
+                ..DelaySettings::default()
+            },
+            120.0,
+        );
+
+        let output: Vec<f64> = (0..20)
+            .map(|i| delay.process((if i == 0 { 1.0 } else { 0.0 }, 0.0)).0)
+            .collect();
+        let first_echo = output.iter().position(|x| x.abs() > 1e-9);
+        assert_eq!(first_echo, Some(10));
+    }
+}
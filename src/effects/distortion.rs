@@ -0,0 +1,179 @@
+/// Gain in dB applied before the curve at full drive.
+const MAX_DRIVE_DB: f64 = 36.0;
+/// Offset that makes the tube curve clip its positive half earlier than its negative half.
+const TUBE_BIAS: f64 = 0.3;
+/// Pole of the filter that removes the offset an asymmetric curve adds.
+const DC_BLOCKER_POLE: f64 = 0.995;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistortionCurve {
+    Tanh,
+    HardClip,
+    Foldback,
+    Tube,
+}
+
+impl DistortionCurve {
+    pub const ALL: [DistortionCurve; 4] = [
+        DistortionCurve::Tanh,
+        DistortionCurve::HardClip,
+        DistortionCurve::Foldback,
+        DistortionCurve::Tube,
+    ];
+
+    pub fn shape(&self, x: f64) -> f64 {
+        match self {
+            DistortionCurve::Tanh => x.tanh(),
+            DistortionCurve::HardClip => x.clamp(-1.0, 1.0),
+            // Everything beyond the rails is mirrored back between them
+            DistortionCurve::Foldback => ((x - 1.0).rem_euclid(4.0) - 2.0).abs() - 1.0,
+            DistortionCurve::Tube => (x + TUBE_BIAS).tanh() - TUBE_BIAS.tanh(),
+        }
+    }
+}
+
+impl Default for DistortionCurve {
+    fn default() -> DistortionCurve {
+        DistortionCurve::Tanh
+    }
+}
+
+impl std::fmt::Display for DistortionCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DistortionCurve::Tanh => "Tanh",
+                DistortionCurve::HardClip => "Hard Clip",
+                DistortionCurve::Foldback => "Foldback",
+                DistortionCurve::Tube => "Tube",
+            }
+        )
+    }
+}
+
+/// Where in the signal path the distortion sits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistortionPlacement {
+    /// In every voice, on the filter inputs.
+    Voice,
+    /// On the sum of all voices, after their filters.
+    Master,
+}
+
+impl DistortionPlacement {
+    pub const ALL: [DistortionPlacement; 2] =
+        [DistortionPlacement::Voice, DistortionPlacement::Master];
+}
+
+impl Default for DistortionPlacement {
+    fn default() -> DistortionPlacement {
+        DistortionPlacement::Master
+    }
+}
+
+impl std::fmt::Display for DistortionPlacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DistortionPlacement::Voice => "Pre-Filter",
+                DistortionPlacement::Master => "Master",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct DistortionSettings {
+    pub placement: DistortionPlacement,
+    pub curve: DistortionCurve,
+    /// Drive from 0 to 1, up to `MAX_DRIVE_DB` of gain into the curve.
+    pub drive: f64,
+    /// Bit depth the output is quantized to.
+    pub bits: f64,
+    /// Factor the sample rate is divided by, holding each sample for that long.
+    pub downsample: f64,
+    pub mix: f64,
+}
+
+impl Default for DistortionSettings {
+    fn default() -> DistortionSettings {
+        DistortionSettings {
+            placement: DistortionPlacement::Master,
+            curve: DistortionCurve::Tanh,
+            drive: 0.0,
+            bits: 16.0,
+            downsample: 1.0,
+            mix: 0.0,
+        }
+    }
+}
+
+/// Waveshaper followed by a bitcrusher, for two independent channels.
+pub struct Distortion {
+    settings: DistortionSettings,
+    /// Samples held by the sample rate reduction.
+    held: [f64; 2],
+    counter: f64,
+    /// Previous input and output of the DC blocker of each channel.
+    dc: [(f64, f64); 2],
+}
+
+impl Distortion {
+    pub fn new(settings: &DistortionSettings) -> Distortion {
+        Distortion {
+            settings: settings.clone(),
+            held: [0.0; 2],
+            counter: 0.0,
+            dc: [(0.0, 0.0); 2],
+        }
+    }
+
+    /// Clears the held sample of the bitcrusher and the DC blocker.
+    pub fn reset(&mut self) {
+        self.held = [0.0; 2];
+        self.counter = 0.0;
+        self.dc = [(0.0, 0.0); 2];
+    }
+
+    pub fn set(&mut self, settings: &DistortionSettings) {
+        self.settings = settings.clone();
+    }
+
+    pub fn placement(&self) -> DistortionPlacement {
+        self.settings.placement
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let gain = 10f64.powf(self.settings.drive.clamp(0.0, 1.0) * MAX_DRIVE_DB / 20.0);
+        let step = 2.0 / self.settings.bits.clamp(1.0, 24.0).exp2();
+
+        self.counter += 1.0;
+        let sample = self.counter >= self.settings.downsample;
+        if sample {
+            self.counter -= self.settings.downsample.max(1.0);
+        }
+
+        let mut wet = [0.0; 2];
+        for (channel, x) in [input.0, input.1].into_iter().enumerate() {
+            let shaped = self.settings.curve.shape(x * gain);
+            let (last_in, last_out) = self.dc[channel];
+            let blocked = shaped - last_in + DC_BLOCKER_POLE * last_out;
+            self.dc[channel] = (shaped, blocked);
+
+            if sample {
+                self.held[channel] = (blocked / step).round() * step;
+            }
+            wet[channel] = self.held[channel];
+        }
+
+        let mix = self.settings.mix;
+        (
+            input.0 * (1.0 - mix) + wet[0] * mix,
+            input.1 * (1.0 - mix) + wet[1] * mix,
+        )
+    }
+}
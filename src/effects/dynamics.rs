@@ -0,0 +1,197 @@
+use std::f64::consts::TAU;
+
+pub const EQ_BANDS: usize = 4;
+
+#[derive(Clone)]
+pub struct EqBand {
+    /// Center frequency in Hz.
+    pub frequency: f64,
+    /// Boost or cut in dB.
+    pub gain: f64,
+    pub q: f64,
+}
+
+#[derive(Clone)]
+pub struct EqSettings {
+    pub bands: [EqBand; EQ_BANDS],
+}
+
+impl Default for EqSettings {
+    fn default() -> EqSettings {
+        let band = |frequency| EqBand {
+            frequency,
+            gain: 0.0,
+            q: 0.7,
+        };
+        EqSettings {
+            bands: [band(100.0), band(500.0), band(2000.0), band(8000.0)],
+        }
+    }
+}
+
+/// Peaking filter from the Audio EQ Cookbook, in transposed direct form II.
+#[derive(Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [[f64; 2]; 2],
+}
+
+impl Biquad {
+    fn set_peak(&mut self, band: &EqBand, sample_rate: f64) {
+        let amplitude = 10f64.powf(band.gain / 40.0);
+        let omega = TAU * band.frequency.clamp(10.0, sample_rate * 0.45) / sample_rate;
+        let alpha = omega.sin() / (2.0 * band.q.max(0.05));
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha / amplitude;
+        self.b = [
+            (1.0 + alpha * amplitude) / a0,
+            -2.0 * cos / a0,
+            (1.0 - alpha * amplitude) / a0,
+        ];
+        self.a = [-2.0 * cos / a0, (1.0 - alpha / amplitude) / a0];
+    }
+
+    fn process(&mut self, channel: usize, x: f64) -> f64 {
+        let state = &mut self.state[channel];
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Four peaking bands in series.
+pub struct Equalizer {
+    bands: [Biquad; EQ_BANDS],
+    sample_rate: f64,
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: f64) -> Equalizer {
+        let mut equalizer = Equalizer {
+            bands: Default::default(),
+            sample_rate,
+        };
+        equalizer.set(&EqSettings::default());
+        equalizer
+    }
+
+    /// Clears the filter memory of every band.
+    pub fn reset(&mut self) {
+        for band in self.bands.iter_mut() {
+            band.state = [[0.0; 2]; 2];
+        }
+    }
+
+    pub fn set(&mut self, settings: &EqSettings) {
+        for (filter, band) in self.bands.iter_mut().zip(&settings.bands) {
+            filter.set_peak(band, self.sample_rate);
+        }
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        self.bands.iter_mut().fold(input, |(left, right), band| {
+            (band.process(0, left), band.process(1, right))
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct CompressorSettings {
+    /// Level in dBFS above which the gain is reduced.
+    pub threshold: f64,
+    /// Input dB above the threshold for each dB of output.
+    pub ratio: f64,
+    /// Attack and release times in seconds.
+    pub attack: f64,
+    pub release: f64,
+    /// Gain in dB added after compression.
+    pub makeup: f64,
+    /// Width in dB of the soft knee around the threshold.
+    pub knee: f64,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> CompressorSettings {
+        CompressorSettings {
+            threshold: 0.0,
+            ratio: 1.0,
+            attack: 0.01,
+            release: 0.1,
+            makeup: 0.0,
+            knee: 6.0,
+        }
+    }
+}
+
+/// Feed-forward compressor, with one gain for both channels so the stereo image stays put.
+pub struct Compressor {
+    settings: CompressorSettings,
+    sample_rate: f64,
+    attack: f64,
+    release: f64,
+    /// Smoothed gain reduction in dB.
+    reduction: f64,
+}
+
+impl Compressor {
+    pub fn new(sample_rate: f64) -> Compressor {
+        let mut compressor = Compressor {
+            settings: CompressorSettings::default(),
+            sample_rate,
+            attack: 0.0,
+            release: 0.0,
+            reduction: 0.0,
+        };
+        compressor.set(&CompressorSettings::default());
+        compressor
+    }
+
+    /// Lets go of any gain reduction, as after a long silence.
+    pub fn reset(&mut self) {
+        self.reduction = 0.0;
+    }
+
+    pub fn set(&mut self, settings: &CompressorSettings) {
+        let coefficient = |time: f64| (-1.0 / (time.max(1e-5) * self.sample_rate)).exp();
+        self.attack = coefficient(settings.attack);
+        self.release = coefficient(settings.release);
+        self.settings = settings.clone();
+    }
+
+    /// Output level in dB for an input level, with a quadratic knee.
+    fn curve(&self, level: f64) -> f64 {
+        let CompressorSettings {
+            threshold,
+            ratio,
+            knee,
+            ..
+        } = self.settings;
+        let over = level - threshold;
+        let slope = 1.0 / ratio.max(1.0) - 1.0;
+        if knee > 0.0 && 2.0 * over.abs() <= knee {
+            level + slope * (over + knee / 2.0).powi(2) / (2.0 * knee)
+        } else if over > 0.0 {
+            level + slope * over
+        } else {
+            level
+        }
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        let peak = input.0.abs().max(input.1.abs()).max(1e-9);
+        let level = 20.0 * peak.log10();
+        let target = self.curve(level) - level;
+
+        let coefficient = if target < self.reduction {
+            self.attack
+        } else {
+            self.release
+        };
+        self.reduction = target + (self.reduction - target) * coefficient;
+
+        let gain = 10f64.powf((self.reduction + self.settings.makeup) / 20.0);
+        (input.0 * gain, input.1 * gain)
+    }
+}
@@ -0,0 +1,411 @@
+mod convolution;
+mod delay;
+mod distortion;
+mod dynamics;
+mod modulation;
+mod reverb;
+
+pub use convolution::{Convolution, ConvolutionSettings, ImpulseResponse, Kernel};
+pub use delay::{Delay, DelaySettings};
+pub use distortion::{Distortion, DistortionCurve, DistortionPlacement, DistortionSettings};
+pub use dynamics::{Compressor, CompressorSettings, EqSettings, Equalizer, EQ_BANDS};
+pub use modulation::{ModulationEffect, ModulationSettings, ModulationType};
+pub use reverb::{Reverb, ReverbSettings};
+
+use crate::audio::Parameters;
+use std::sync::{Arc, RwLock};
+
+pub const EFFECT_SLOTS: usize = 7;
+/// Sets of effects the master bus plays, each of which needs its own convolution state.
+const EFFECT_SETS: usize = 2;
+/// Time in seconds a slot takes to fade in or out when it is bypassed, so it does not click.
+const BYPASS_SMOOTHING: f64 = 0.01;
+/// Time in seconds the input crossfades from the old to the new order of the chain.
+const REORDER_FADE: f64 = 0.01;
+/// Level below which the tail of the old order counts as silent.
+const SILENCE: f64 = 1e-5;
+/// Time in seconds the tail of the old order has to stay silent before it is done, longer
+/// than the longest delay time and pre-delay, so a gap between echoes does not end it.
+const TAIL_HOLD: f64 = 2.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    Distortion,
+    Modulation,
+    Delay,
+    Reverb,
+    Convolution,
+    Eq,
+    Compressor,
+}
+
+impl EffectKind {
+    pub const ALL: [EffectKind; EFFECT_SLOTS] = [
+        EffectKind::Distortion,
+        EffectKind::Modulation,
+        EffectKind::Delay,
+        EffectKind::Reverb,
+        EffectKind::Convolution,
+        EffectKind::Eq,
+        EffectKind::Compressor,
+    ];
+}
+
+impl std::fmt::Display for EffectKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EffectKind::Distortion => "Distortion",
+                EffectKind::Modulation => "Modulation",
+                EffectKind::Delay => "Delay",
+                EffectKind::Reverb => "Reverb",
+                EffectKind::Convolution => "Convolution",
+                EffectKind::Eq => "EQ",
+                EffectKind::Compressor => "Compressor",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EffectSlot {
+    pub kind: EffectKind,
+    pub bypass: bool,
+}
+
+/// Order of the effects on the master bus, with every effect in exactly one slot.
+#[derive(Debug, Clone, Copy)]
+pub struct EffectChain {
+    pub slots: [EffectSlot; EFFECT_SLOTS],
+}
+
+impl Default for EffectChain {
+    fn default() -> EffectChain {
+        EffectChain {
+            slots: EffectKind::ALL.map(|kind| EffectSlot {
+                kind,
+                bypass: false,
+            }),
+        }
+    }
+}
+
+impl EffectChain {
+    fn same_order(&self, other: &EffectChain) -> bool {
+        self.slots
+            .iter()
+            .zip(&other.slots)
+            .all(|(a, b)| a.kind == b.kind)
+    }
+}
+
+/// Circular buffer that reads back a number of samples into the past.
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    position: usize,
+}
+
+impl DelayLine {
+    pub fn new(length: usize) -> DelayLine {
+        DelayLine {
+            buffer: vec![0.0; length.max(1)],
+            position: 0,
+        }
+    }
+
+    /// Value written `delay` writes ago, up to the length of the line. Read right after a
+    /// write, that is the input `delay` samples back. A line in a feedback loop reads before
+    /// it writes, and so has to read one sample less for the same delay.
+    pub fn read(&self, delay: usize) -> f64 {
+        let length = self.buffer.len();
+        let delay = delay.min(length - 1);
+        self.buffer[(self.position + length - delay) % length]
+    }
+
+    /// Value between two samples, interpolated linearly, for delays that change smoothly.
+    pub fn read_fractional(&self, delay: f64) -> f64 {
+        let whole = delay.floor();
+        let fraction = delay - whole;
+        let earlier = self.read(whole as usize + 1);
+        let later = self.read(whole as usize);
+        later + (earlier - later) * fraction
+    }
+
+    /// Silences everything the line holds.
+    pub fn clear(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    /// Advances the line by one sample.
+    pub fn write(&mut self, value: f64) {
+        self.position = (self.position + 1) % self.buffer.len();
+        self.buffer[self.position] = value;
+    }
+}
+
+/// One instance of every effect on the master bus.
+struct Effects {
+    distortion: Distortion,
+    modulation: ModulationEffect,
+    delay: Delay,
+    reverb: Reverb,
+    convolution: Convolution,
+    equalizer: Equalizer,
+    compressor: Compressor,
+}
+
+impl Effects {
+    fn new(parameters: &Parameters, sample_rate: f64) -> Effects {
+        Effects {
+            distortion: Distortion::new(&parameters.distortion.read().unwrap()),
+            modulation: ModulationEffect::new(sample_rate),
+            delay: Delay::new(sample_rate),
+            reverb: Reverb::new(sample_rate),
+            convolution: Convolution::new(sample_rate),
+            equalizer: Equalizer::new(sample_rate),
+            compressor: Compressor::new(sample_rate),
+        }
+    }
+
+    /// Clears what the effects still hold of earlier input, for a fresh start.
+    fn reset(&mut self) {
+        self.distortion.reset();
+        self.modulation.reset();
+        self.delay.reset();
+        self.reverb.reset();
+        self.convolution.reset();
+        self.equalizer.reset();
+        self.compressor.reset();
+    }
+
+    /// Runs the input through the slots of a chain, each faded in by the level of its kind.
+    fn process_chain(
+        &mut self,
+        chain: &EffectChain,
+        levels: &[f64; EFFECT_SLOTS],
+        input: (f64, f64),
+    ) -> (f64, f64) {
+        let mut signal = input;
+        for slot in chain.slots {
+            let level = levels[slot.kind as usize];
+            // Bypassed effects keep running, so their state is current when they come back
+            let wet = self.process(slot.kind, signal);
+            signal = (
+                signal.0 + (wet.0 - signal.0) * level,
+                signal.1 + (wet.1 - signal.1) * level,
+            );
+        }
+        signal
+    }
+
+    fn process(&mut self, kind: EffectKind, input: (f64, f64)) -> (f64, f64) {
+        match kind {
+            // Distortion placed in the voices has already been applied before their filters
+            EffectKind::Distortion => match self.distortion.placement() {
+                DistortionPlacement::Master => self.distortion.process(input),
+                DistortionPlacement::Voice => input,
+            },
+            EffectKind::Modulation => self.modulation.process(input),
+            EffectKind::Delay => self.delay.process(input),
+            EffectKind::Reverb => self.reverb.process(input),
+            EffectKind::Convolution => self.convolution.process(input),
+            EffectKind::Eq => self.equalizer.process(input),
+            EffectKind::Compressor => self.compressor.process(input),
+        }
+    }
+}
+
+/// Effects applied to the sum of all voices, in the order of the effect chain.
+pub struct MasterBus {
+    chain: EffectChain,
+    /// Order the input crossfades away from after the order changed, kept until its tail has
+    /// rung out.
+    previous: Option<EffectChain>,
+    /// Order that was set while the previous one was still ringing, which starts after it.
+    pending: Option<EffectChain>,
+    /// Level of each effect, indexed by its kind, which fades to 0 while it is bypassed.
+    levels: [f64; EFFECT_SLOTS],
+    level_smoothing: f64,
+    /// Share of the input that goes to the current order while it crossfades from the
+    /// previous one.
+    fade: f64,
+    fade_step: f64,
+    /// Level of the tail of the previous order, which fades out early when a pending order
+    /// needs its set of effects.
+    tail_level: f64,
+    /// Samples the tail of the previous order has been silent for.
+    silent: usize,
+    tail_hold: usize,
+    /// Two sets of effects, so the previous order keeps its own state while it rings out.
+    effects: [Effects; EFFECT_SETS],
+    /// Index of the set that plays the current order.
+    active: usize,
+    distortion_settings: Arc<RwLock<DistortionSettings>>,
+    modulation_settings: Arc<RwLock<ModulationSettings>>,
+    delay_settings: Arc<RwLock<DelaySettings>>,
+    reverb_settings: Arc<RwLock<ReverbSettings>>,
+    convolution_settings: Arc<RwLock<ConvolutionSettings>>,
+    eq_settings: Arc<RwLock<EqSettings>>,
+    compressor_settings: Arc<RwLock<CompressorSettings>>,
+    chain_settings: Arc<RwLock<EffectChain>>,
+    tempo: Arc<RwLock<f64>>,
+}
+
+impl MasterBus {
+    pub fn new(parameters: &Parameters, sample_rate: f64) -> MasterBus {
+        let chain = *parameters.chain.read().unwrap();
+        let mut levels = [0.0; EFFECT_SLOTS];
+        for slot in chain.slots.iter() {
+            levels[slot.kind as usize] = if slot.bypass { 0.0 } else { 1.0 };
+        }
+
+        MasterBus {
+            chain,
+            previous: None,
+            pending: None,
+            levels,
+            level_smoothing: 1.0 - (-1.0 / (BYPASS_SMOOTHING * sample_rate)).exp(),
+            fade: 1.0,
+            fade_step: 1.0 / (REORDER_FADE * sample_rate),
+            tail_level: 1.0,
+            silent: 0,
+            tail_hold: (TAIL_HOLD * sample_rate) as usize,
+            effects: [
+                Effects::new(parameters, sample_rate),
+                Effects::new(parameters, sample_rate),
+            ],
+            active: 0,
+            distortion_settings: parameters.distortion.clone(),
+            modulation_settings: parameters.modulation.clone(),
+            delay_settings: parameters.delay.clone(),
+            reverb_settings: parameters.reverb.clone(),
+            convolution_settings: parameters.convolution.clone(),
+            eq_settings: parameters.eq.clone(),
+            compressor_settings: parameters.compressor.clone(),
+            chain_settings: parameters.chain.clone(),
+            tempo: parameters.tempo.clone(),
+        }
+    }
+
+    /// Picks up settings changed in the GUI. This runs on the audio thread, so it never waits
+    /// for a lock and keeps the old settings instead.
+    pub fn update(&mut self) {
+        for effects in self.effects.iter_mut() {
+            if let Ok(settings) = self.distortion_settings.try_read() {
+                effects.distortion.set(&settings);
+            }
+            if let Ok(settings) = self.modulation_settings.try_read() {
+                effects.modulation.set(&settings);
+            }
+            if let (Ok(settings), Ok(tempo)) =
+                (self.delay_settings.try_read(), self.tempo.try_read())
+            {
+                effects.delay.set(&settings, *tempo);
+            }
+            if let Ok(settings) = self.reverb_settings.try_read() {
+                effects.reverb.set(&settings);
+            }
+            if let Ok(settings) = self.convolution_settings.try_read() {
+                effects.convolution.set(&settings);
+            }
+            if let Ok(settings) = self.eq_settings.try_read() {
+                effects.equalizer.set(&settings);
+            }
+            if let Ok(settings) = self.compressor_settings.try_read() {
+                effects.compressor.set(&settings);
+            }
+        }
+        if let Ok(chain) = self.chain_settings.try_read() {
+            // Bypass changes are smoothed. A new order waits until the set of effects that
+            // is not playing the current one has rung out.
+            if chain.same_order(&self.chain) {
+                self.chain = *chain;
+                self.pending = None;
+            } else {
+                self.pending = Some(*chain);
+            }
+        }
+        if self.previous.is_none() {
+            if let Some(chain) = self.pending.take() {
+                self.start(chain);
+            }
+        }
+    }
+
+    /// Starts a new order on the other set of effects, which fades in while the previous
+    /// order gets less and less of the input and rings out.
+    fn start(&mut self, chain: EffectChain) {
+        self.active = 1 - self.active;
+        self.effects[self.active].reset();
+        self.previous = Some(self.chain);
+        self.chain = chain;
+        self.fade = 0.0;
+        self.tail_level = 1.0;
+        self.silent = 0;
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        for slot in self.chain.slots {
+            let target = if slot.bypass { 0.0 } else { 1.0 };
+            let level = &mut self.levels[slot.kind as usize];
+            *level += (target - *level) * self.level_smoothing;
+        }
+
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => {
+                return self.effects[self.active].process_chain(&self.chain, &self.levels, input)
+            }
+        };
+        let fade = self.fade;
+        let output = self.effects[self.active].process_chain(
+            &self.chain,
+            &self.levels,
+            (input.0 * fade, input.1 * fade),
+        );
+        let tail = self.effects[1 - self.active].process_chain(
+            previous,
+            &self.levels,
+            (input.0 * (1.0 - fade), input.1 * (1.0 - fade)),
+        );
+        let tail = (tail.0 * self.tail_level, tail.1 * self.tail_level);
+
+        self.fade = (self.fade + self.fade_step).min(1.0);
+        if fade == 1.0 {
+            if tail.0.abs().max(tail.1.abs()) < SILENCE {
+                self.silent += 1;
+            } else {
+                self.silent = 0;
+            }
+            // A pending order cuts the tail short, fading it out first so it does not click
+            if self.pending.is_some() {
+                self.tail_level = (self.tail_level - self.fade_step).max(0.0);
+            }
+            if self.silent >= self.tail_hold || self.tail_level == 0.0 {
+                self.previous = None;
+            }
+        }
+        (output.0 + tail.0, output.1 + tail.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_line_reads_back_whole_samples() {
+        let mut line = DelayLine::new(8);
+        line.write(1.0);
+        assert_eq!(line.read(0), 1.0);
+        for _ in 0..3 {
+            line.write(0.0);
+        }
+        assert_eq!(line.read(3), 1.0);
+        assert_eq!(line.read(2), 0.0);
+        assert_eq!(line.read(4), 0.0);
+        assert_eq!(line.read_fractional(2.5), 0.5);
+    }
+}
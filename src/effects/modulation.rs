@@ -0,0 +1,155 @@
+use super::DelayLine;
+use std::f64::consts::{PI, TAU};
+
+const MAX_DELAY: f64 = 0.05;
+const PHASER_STAGES: usize = 6;
+/// Lowest notch frequency of the phaser sweep in Hz.
+const PHASER_BASE: f64 = 200.0;
+/// Octaves the phaser sweeps over at full depth.
+const PHASER_OCTAVES: f64 = 5.0;
+const MAX_FEEDBACK: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModulationType {
+    Chorus,
+    Flanger,
+    Phaser,
+}
+
+impl ModulationType {
+    pub const ALL: [ModulationType; 3] = [
+        ModulationType::Chorus,
+        ModulationType::Flanger,
+        ModulationType::Phaser,
+    ];
+
+    /// Shortest delay and the sweep added to it at full depth, in seconds.
+    fn delay_range(&self) -> (f64, f64) {
+        match self {
+            ModulationType::Chorus => (0.007, 0.012),
+            ModulationType::Flanger => (0.0005, 0.006),
+            ModulationType::Phaser => (0.0, 0.0),
+        }
+    }
+}
+
+impl Default for ModulationType {
+    fn default() -> ModulationType {
+        ModulationType::Chorus
+    }
+}
+
+impl std::fmt::Display for ModulationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ModulationType::Chorus => "Chorus",
+                ModulationType::Flanger => "Flanger",
+                ModulationType::Phaser => "Phaser",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct ModulationSettings {
+    pub modulation_type: ModulationType,
+    /// Rate of the sweep in Hz.
+    pub rate: f64,
+    /// Width of the sweep from 0 to 1.
+    pub depth: f64,
+    /// Share of the wet signal fed back from 0 to 1.
+    pub feedback: f64,
+    pub mix: f64,
+}
+
+impl Default for ModulationSettings {
+    fn default() -> ModulationSettings {
+        ModulationSettings {
+            modulation_type: ModulationType::Chorus,
+            rate: 0.8,
+            depth: 0.5,
+            feedback: 0.0,
+            mix: 0.0,
+        }
+    }
+}
+
+/// Chorus, flanger or phaser, swept by an internal LFO whose right channel runs a quarter
+/// cycle ahead of the left to widen the image.
+pub struct ModulationEffect {
+    lines: [DelayLine; 2],
+    /// State of each first order allpass stage of the phaser.
+    stages: [[f64; PHASER_STAGES]; 2],
+    /// Previous wet sample of each channel, for feedback.
+    wet: [f64; 2],
+    phase: f64,
+    sample_rate: f64,
+    settings: ModulationSettings,
+}
+
+impl ModulationEffect {
+    pub fn new(sample_rate: f64) -> ModulationEffect {
+        let length = (MAX_DELAY * sample_rate) as usize + 2;
+        ModulationEffect {
+            lines: [DelayLine::new(length), DelayLine::new(length)],
+            stages: [[0.0; PHASER_STAGES]; 2],
+            wet: [0.0; 2],
+            phase: 0.0,
+            sample_rate,
+            settings: ModulationSettings::default(),
+        }
+    }
+
+    /// Empties the delay lines, the phaser stages and the feedback.
+    pub fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.clear();
+        }
+        self.stages = [[0.0; PHASER_STAGES]; 2];
+        self.wet = [0.0; 2];
+    }
+
+    pub fn set(&mut self, settings: &ModulationSettings) {
+        self.settings = settings.clone();
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        self.phase = (self.phase + self.settings.rate / self.sample_rate).fract();
+        let feedback = self.settings.feedback.clamp(0.0, 1.0) * MAX_FEEDBACK;
+        let depth = self.settings.depth.clamp(0.0, 1.0);
+
+        for (channel, dry) in [input.0, input.1].into_iter().enumerate() {
+            let sweep = 0.5 - 0.5 * (TAU * (self.phase + channel as f64 * 0.25)).cos();
+            let x = dry + self.wet[channel] * feedback;
+            self.wet[channel] = match self.settings.modulation_type {
+                ModulationType::Phaser => {
+                    let cutoff = PHASER_BASE * (sweep * depth * PHASER_OCTAVES).exp2();
+                    let tan = (PI * cutoff.min(self.sample_rate * 0.45) / self.sample_rate).tan();
+                    let coefficient = (tan - 1.0) / (tan + 1.0);
+                    self.stages[channel].iter_mut().fold(x, |x, state| {
+                        let y = coefficient * x + *state;
+                        *state = x - coefficient * y;
+                        y
+                    })
+                }
+                modulation_type => {
+                    let (base, range) = modulation_type.delay_range();
+                    let delay = (base + range * depth * sweep) * self.sample_rate;
+                    // Read before writing, which is one sample later than the delay
+                    let wet = self.lines[channel].read_fractional((delay - 1.0).max(0.0));
+                    self.lines[channel].write(x);
+                    wet
+                }
+            };
+        }
+
+        let mix = self.settings.mix;
+        (
+            input.0 * (1.0 - mix) + self.wet[0] * mix,
+            input.1 * (1.0 - mix) + self.wet[1] * mix,
+        )
+    }
+}
@@ -0,0 +1,178 @@
+use super::DelayLine;
+
+/// Delays of the comb filters in samples at 44.1 kHz, as tuned for Freeverb.
+const COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+/// Extra delay of the right channel, which decorrelates it from the left.
+const STEREO_SPREAD: usize = 23;
+const TUNING_SAMPLE_RATE: f64 = 44100.0;
+
+const INPUT_GAIN: f64 = 0.015;
+const WET_GAIN: f64 = 3.0;
+const MAX_PRE_DELAY: f64 = 0.25;
+
+#[derive(Clone)]
+pub struct ReverbSettings {
+    /// Room size from 0 to 1, which sets how long the tail rings.
+    pub size: f64,
+    /// High frequency damping of the tail from 0 to 1.
+    pub damping: f64,
+    /// Pre-delay in seconds.
+    pub pre_delay: f64,
+    /// Stereo width from mono at 0 to fully spread at 1.
+    pub width: f64,
+    /// Wet share of the output from 0 to 1.
+    pub mix: f64,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> ReverbSettings {
+        ReverbSettings {
+            size: 0.5,
+            damping: 0.5,
+            pre_delay: 0.0,
+            width: 1.0,
+            mix: 0.0,
+        }
+    }
+}
+
+struct Comb {
+    buffer: Vec<f64>,
+    index: usize,
+    filter_store: f64,
+}
+
+impl Comb {
+    fn new(length: usize) -> Comb {
+        Comb {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f64, feedback: f64, damping: f64) -> f64 {
+        let output = self.buffer[self.index];
+        self.filter_store = output * (1.0 - damping) + self.filter_store * damping;
+        self.buffer[self.index] = input + self.filter_store * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f64>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Allpass {
+        Allpass {
+            buffer: vec![0.0; length.max(1)],
+            index: 0,
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// Algorithmic stereo reverb after the Freeverb design: parallel damped comb filters followed
+/// by a chain of allpass filters for each channel.
+pub struct Reverb {
+    combs: [Vec<Comb>; 2],
+    allpasses: [Vec<Allpass>; 2],
+    pre_delay: DelayLine,
+    sample_rate: f64,
+    feedback: f64,
+    damping: f64,
+    pre_delay_samples: usize,
+    wet_direct: f64,
+    wet_cross: f64,
+    mix: f64,
+}
+
+impl Reverb {
+    pub fn new(sample_rate: f64) -> Reverb {
+        let scale = sample_rate / TUNING_SAMPLE_RATE;
+        let length = |tuning: usize, spread: usize| ((tuning + spread) as f64 * scale) as usize;
+        let combs = |spread: usize| -> Vec<Comb> {
+            COMB_TUNINGS
+                .iter()
+                .map(|t| Comb::new(length(*t, spread)))
+                .collect()
+        };
+        let allpasses = |spread: usize| -> Vec<Allpass> {
+            ALLPASS_TUNINGS
+                .iter()
+                .map(|t| Allpass::new(length(*t, spread)))
+                .collect()
+        };
+
+        let mut reverb = Reverb {
+            combs: [combs(0), combs(STEREO_SPREAD)],
+            allpasses: [allpasses(0), allpasses(STEREO_SPREAD)],
+            pre_delay: DelayLine::new((MAX_PRE_DELAY * sample_rate) as usize + 1),
+            sample_rate,
+            feedback: 0.0,
+            damping: 0.0,
+            pre_delay_samples: 0,
+            wet_direct: 0.0,
+            wet_cross: 0.0,
+            mix: 0.0,
+        };
+        reverb.set(&ReverbSettings::default());
+        reverb
+    }
+
+    /// Empties the combs, allpasses and pre-delay, which ends the tail.
+    pub fn reset(&mut self) {
+        for comb in self.combs.iter_mut().flatten() {
+            comb.buffer.fill(0.0);
+            comb.filter_store = 0.0;
+        }
+        for allpass in self.allpasses.iter_mut().flatten() {
+            allpass.buffer.fill(0.0);
+        }
+        self.pre_delay.clear();
+    }
+
+    pub fn set(&mut self, settings: &ReverbSettings) {
+        self.feedback = settings.size * 0.28 + 0.7;
+        self.damping = settings.damping * 0.4;
+        self.pre_delay_samples =
+            (settings.pre_delay.clamp(0.0, MAX_PRE_DELAY) * self.sample_rate) as usize;
+        self.wet_direct = WET_GAIN * (settings.width / 2.0 + 0.5);
+        self.wet_cross = WET_GAIN * (1.0 - settings.width) / 2.0;
+        self.mix = settings.mix;
+    }
+
+    pub fn process(&mut self, input: (f64, f64)) -> (f64, f64) {
+        self.pre_delay.write((input.0 + input.1) * INPUT_GAIN);
+        let delayed = self.pre_delay.read(self.pre_delay_samples);
+
+        let mut wet = [0.0; 2];
+        for (channel, wet) in wet.iter_mut().enumerate() {
+            let mut sum: f64 = self.combs[channel]
+                .iter_mut()
+                .map(|comb| comb.process(delayed, self.feedback, self.damping))
+                .sum();
+            for allpass in self.allpasses[channel].iter_mut() {
+                sum = allpass.process(sum);
+            }
+            *wet = sum;
+        }
+
+        let left = wet[0] * self.wet_direct + wet[1] * self.wet_cross;
+        let right = wet[1] * self.wet_direct + wet[0] * self.wet_cross;
+        (
+            input.0 * (1.0 - self.mix) + left * self.mix,
+            input.1 * (1.0 - self.mix) + right * self.mix,
+        )
+    }
+}
@@ -0,0 +1,213 @@
+use crate::audio::FINISHED_TAG;
+use crate::oscillator::ADSR;
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use fundsp::{
+    hacker::{adsr_live, bandpass, highpass, lowpass, moog, notch, peak, var},
+    prelude::{An, AudioUnit64, Var},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    Peak,
+    Ladder,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 6] = [
+        FilterType::LowPass,
+        FilterType::HighPass,
+        FilterType::BandPass,
+        FilterType::Notch,
+        FilterType::Peak,
+        FilterType::Ladder,
+    ];
+}
+
+impl Default for FilterType {
+    fn default() -> FilterType {
+        FilterType::LowPass
+    }
+}
+
+impl std::fmt::Display for FilterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FilterType::LowPass => "Low-Pass",
+                FilterType::HighPass => "High-Pass",
+                FilterType::BandPass => "Band-Pass",
+                FilterType::Notch => "Notch",
+                FilterType::Peak => "Peak",
+                FilterType::Ladder => "Ladder 24dB",
+            }
+        )
+    }
+}
+
+/// Note at which key tracking leaves the cutoff untouched.
+const KEY_TRACKING_CENTER: f64 = 60.0;
+/// Resonance in percent above which the ladder filter rings on its own.
+const SELF_OSCILLATION: f64 = 90.0;
+/// Q of the state variable filters at full resonance.
+const MAX_Q: f64 = 100.0;
+
+#[derive(Clone)]
+pub struct Filter {
+    pub filter_type: FilterType,
+    pub cutoff: f64,
+    /// Resonance in percent, from a flat response at 0 to the sharpest peak at the top, where the
+    /// ladder filter self-oscillates.
+    pub resonance: f64,
+    /// Saturation in front of the filter, from clean at 0 to heavily driven at 1.
+    pub drive: f64,
+    pub envelope: ADSR,
+    /// How far the envelope moves the cutoff at full level, in octaves.
+    pub envelope_amount: f64,
+    /// Share of the envelope amount that depends on velocity, from none at 0 to all at 1.
+    pub velocity_amount: f64,
+    /// How closely the cutoff follows the played note, from 0 to 1 for a full octave per octave.
+    pub key_tracking: f64,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            filter_type: FilterType::LowPass,
+            cutoff: 20000.0,
+            resonance: 0.0,
+            drive: 0.0,
+            envelope: ADSR(0.01, 0.3, 0.0, 0.3),
+            envelope_amount: 0.0,
+            velocity_amount: 0.0,
+            key_tracking: 0.0,
+        }
+    }
+}
+
+impl Filter {
+    /// Filter unit with audio, cutoff and Q inputs, to be driven by a `Voice`.
+    pub fn generate(&self) -> Box<dyn AudioUnit64> {
+        match self.filter_type {
+            FilterType::LowPass => Box::new(lowpass()),
+            FilterType::HighPass => Box::new(highpass()),
+            FilterType::BandPass => Box::new(bandpass()),
+            FilterType::Notch => Box::new(notch()),
+            FilterType::Peak => Box::new(peak()),
+            FilterType::Ladder => Box::new(moog()),
+        }
+    }
+
+    /// Cutoff envelope of a note. It never ends the voice, so its finished flag goes nowhere.
+    pub fn generate_envelope(&self, releasing: An<Var<f64>>) -> Box<dyn AudioUnit64> {
+        Box::new(adsr_live(
+            self.envelope.0,
+            self.envelope.1,
+            self.envelope.2,
+            self.envelope.3,
+            releasing,
+            var(FINISHED_TAG, 0.0),
+        ))
+    }
+
+    /// Envelope amount in octaves for a note played at `velocity`.
+    pub fn envelope_octaves(&self, velocity: u8) -> f64 {
+        let scale = 1.0 - self.velocity_amount * (1.0 - velocity as f64 / 127.0);
+        self.envelope_amount * scale
+    }
+
+    /// Cutoff offset in octaves for a note, relative to `KEY_TRACKING_CENTER`.
+    pub fn key_tracking_octaves(&self, note: f64) -> f64 {
+        // A self-oscillating filter is heard as a tone, so it has to play in tune
+        let tracking = self.key_tracking + (1.0 - self.key_tracking) * self.self_oscillation();
+        (note - KEY_TRACKING_CENTER) / 12.0 * tracking
+    }
+
+    pub fn q(&self) -> f64 {
+        let amount = (self.resonance / 100.0).clamp(0.0, 1.0);
+        match self.filter_type {
+            // The ladder resonance is normalized, reaching self-oscillation at 1
+            FilterType::Ladder => amount,
+            // Exponential so the knob sweeps evenly from a flat Butterworth response to a sharp peak
+            _ => FRAC_1_SQRT_2 * (MAX_Q / FRAC_1_SQRT_2).powf(amount),
+        }
+    }
+
+    /// How far the resonance is into self-oscillation, from 0 to 1. Only the ladder feeds its
+    /// output back far enough to oscillate; the state variable filters merely ring.
+    pub fn self_oscillation(&self) -> f64 {
+        match self.filter_type {
+            FilterType::Ladder => {
+                ((self.resonance - SELF_OSCILLATION) / (100.0 - SELF_OSCILLATION)).clamp(0.0, 1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Soft clips the filter input according to the drive.
+    pub fn saturate(&self, input: f64) -> f64 {
+        let gain = 1.0 + 15.0 * self.drive;
+        input + self.drive * ((input * gain).tanh() - input)
+    }
+}
+
+/// How the oscillators of a voice are sent through the two filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterRouting {
+    /// Each oscillator goes through the filter assigned to it.
+    PerOscillator,
+    /// All oscillators go through filter 1 and then filter 2.
+    Serial,
+    /// All oscillators go through both filters, mixed by the balance.
+    Parallel,
+}
+
+impl FilterRouting {
+    pub const ALL: [FilterRouting; 3] = [
+        FilterRouting::PerOscillator,
+        FilterRouting::Serial,
+        FilterRouting::Parallel,
+    ];
+}
+
+impl Default for FilterRouting {
+    fn default() -> FilterRouting {
+        FilterRouting::PerOscillator
+    }
+}
+
+impl std::fmt::Display for FilterRouting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FilterRouting::PerOscillator => "Per Osc",
+                FilterRouting::Serial => "Serial",
+                FilterRouting::Parallel => "Parallel",
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Routing {
+    pub mode: FilterRouting,
+    /// Mix of the parallel filters, from only filter 1 at 0 to only filter 2 at 1.
+    pub balance: f64,
+}
+
+impl Default for Routing {
+    fn default() -> Routing {
+        Routing {
+            mode: FilterRouting::PerOscillator,
+            balance: 0.5,
+        }
+    }
+}
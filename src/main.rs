@@ -2,10 +2,23 @@ use app::App;
 use iced::{window, Sandbox, Settings};
 
 mod app;
+mod arpeggiator;
 mod audio;
+mod effects;
+mod filter;
+mod modulation;
+mod mpe;
 mod oscillator;
+mod patch;
+mod sample;
+mod sequencer;
+mod sf2;
+mod sfz;
 mod styling;
+mod tempo;
 mod util;
+mod voice;
+mod voicing;
 
 fn main() {
     env_logger::init();
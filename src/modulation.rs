@@ -0,0 +1,396 @@
+use std::f64::consts::{SQRT_2, TAU};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LfoWaveform {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    SampleAndHold,
+}
+
+impl LfoWaveform {
+    pub const ALL: [LfoWaveform; 5] = [
+        LfoWaveform::Sine,
+        LfoWaveform::Triangle,
+        LfoWaveform::Saw,
+        LfoWaveform::Square,
+        LfoWaveform::SampleAndHold,
+    ];
+}
+
+impl Default for LfoWaveform {
+    fn default() -> LfoWaveform {
+        LfoWaveform::Sine
+    }
+}
+
+impl std::fmt::Display for LfoWaveform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LfoWaveform::Sine => "Sine",
+                LfoWaveform::Triangle => "Triangle",
+                LfoWaveform::Saw => "Saw",
+                LfoWaveform::Square => "Square",
+                LfoWaveform::SampleAndHold => "S&H",
+            }
+        )
+    }
+}
+
+/// Parameter of a voice that can be modulated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Pitch,
+    PulseWidth,
+    Pan,
+    Level,
+    FmIndex,
+    /// Cutoff of both filters.
+    Cutoff,
+    Cutoff1,
+    Cutoff2,
+    Resonance1,
+    Resonance2,
+    Drive1,
+    Drive2,
+    FilterBalance,
+    Lfo1Rate,
+    Lfo2Rate,
+    Lfo1Depth,
+    Lfo2Depth,
+}
+
+impl Destination {
+    pub const ALL: [Destination; 17] = [
+        Destination::Pitch,
+        Destination::PulseWidth,
+        Destination::Pan,
+        Destination::Level,
+        Destination::FmIndex,
+        Destination::Cutoff,
+        Destination::Cutoff1,
+        Destination::Cutoff2,
+        Destination::Resonance1,
+        Destination::Resonance2,
+        Destination::Drive1,
+        Destination::Drive2,
+        Destination::FilterBalance,
+        Destination::Lfo1Rate,
+        Destination::Lfo2Rate,
+        Destination::Lfo1Depth,
+        Destination::Lfo2Depth,
+    ];
+}
+
+impl Default for Destination {
+    fn default() -> Destination {
+        Destination::Pitch
+    }
+}
+
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Destination::Pitch => "Pitch",
+                Destination::PulseWidth => "Pulse Width",
+                Destination::Pan => "Pan",
+                Destination::Level => "Level",
+                Destination::FmIndex => "FM Index",
+                Destination::Cutoff => "Cutoff",
+                Destination::Cutoff1 => "F1 Cutoff",
+                Destination::Cutoff2 => "F2 Cutoff",
+                Destination::Resonance1 => "F1 Resonance",
+                Destination::Resonance2 => "F2 Resonance",
+                Destination::Drive1 => "F1 Drive",
+                Destination::Drive2 => "F2 Drive",
+                Destination::FilterBalance => "Filter Balance",
+                Destination::Lfo1Rate => "LFO 1 Rate",
+                Destination::Lfo2Rate => "LFO 2 Rate",
+                Destination::Lfo1Depth => "LFO 1 Depth",
+                Destination::Lfo2Depth => "LFO 2 Depth",
+            }
+        )
+    }
+}
+
+/// Signal that can drive a slot of the modulation matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModSource {
+    Off,
+    /// Amplitude envelope of oscillator 1.
+    Envelope,
+    FilterEnvelope1,
+    FilterEnvelope2,
+    Lfo1,
+    Lfo2,
+    Velocity,
+    Note,
+    ModWheel,
+    Breath,
+    /// Channel pressure.
+    Aftertouch,
+    /// Polyphonic pressure of the note the voice is playing.
+    PolyPressure,
+    /// CC74, sent per note by MPE controllers.
+    Timbre,
+    /// A random value picked for every note.
+    Random,
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 14] = [
+        ModSource::Off,
+        ModSource::Envelope,
+        ModSource::FilterEnvelope1,
+        ModSource::FilterEnvelope2,
+        ModSource::Lfo1,
+        ModSource::Lfo2,
+        ModSource::Velocity,
+        ModSource::Note,
+        ModSource::ModWheel,
+        ModSource::Breath,
+        ModSource::Aftertouch,
+        ModSource::PolyPressure,
+        ModSource::Timbre,
+        ModSource::Random,
+    ];
+}
+
+impl Default for ModSource {
+    fn default() -> ModSource {
+        ModSource::Off
+    }
+}
+
+impl std::fmt::Display for ModSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ModSource::Off => "Off",
+                ModSource::Envelope => "Amp Env",
+                ModSource::FilterEnvelope1 => "F1 Env",
+                ModSource::FilterEnvelope2 => "F2 Env",
+                ModSource::Lfo1 => "LFO 1",
+                ModSource::Lfo2 => "LFO 2",
+                ModSource::Velocity => "Velocity",
+                ModSource::Note => "Note",
+                ModSource::ModWheel => "Mod Wheel",
+                ModSource::Breath => "Breath",
+                ModSource::Aftertouch => "Aftertouch",
+                ModSource::PolyPressure => "Poly AT",
+                ModSource::Timbre => "Timbre",
+                ModSource::Random => "Random",
+            }
+        )
+    }
+}
+
+/// One routing of the modulation matrix.
+#[derive(Clone, Default)]
+pub struct ModSlot {
+    pub source: ModSource,
+    pub destination: Destination,
+    /// Bipolar amount from -1 to 1.
+    pub amount: f64,
+}
+
+/// Values of all modulation sources of a voice for one sample.
+#[derive(Default)]
+pub struct ModSources {
+    pub envelope: f64,
+    pub filter_envelopes: [f64; 2],
+    pub lfos: [f64; 2],
+    pub velocity: f64,
+    pub note: f64,
+    pub mod_wheel: f64,
+    pub breath: f64,
+    pub aftertouch: f64,
+    pub poly_pressure: f64,
+    pub timbre: f64,
+    pub random: f64,
+}
+
+impl ModSources {
+    pub fn value(&self, source: ModSource) -> f64 {
+        match source {
+            ModSource::Off => 0.0,
+            ModSource::Envelope => self.envelope,
+            ModSource::FilterEnvelope1 => self.filter_envelopes[0],
+            ModSource::FilterEnvelope2 => self.filter_envelopes[1],
+            ModSource::Lfo1 => self.lfos[0],
+            ModSource::Lfo2 => self.lfos[1],
+            ModSource::Velocity => self.velocity,
+            ModSource::Note => self.note,
+            ModSource::ModWheel => self.mod_wheel,
+            ModSource::Breath => self.breath,
+            ModSource::Aftertouch => self.aftertouch,
+            ModSource::PolyPressure => self.poly_pressure,
+            ModSource::Timbre => self.timbre,
+            ModSource::Random => self.random,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Lfo {
+    pub waveform: LfoWaveform,
+    /// Rate in Hz.
+    pub rate: f64,
+    /// Depth from 0 to 1, where 1 sweeps the destination over its full modulation range.
+    pub depth: f64,
+    /// Start phase in cycles, from 0 to 1.
+    pub phase: f64,
+    /// Restart the LFO with every note instead of letting it run freely.
+    pub retrigger: bool,
+    pub destination: Destination,
+}
+
+impl Default for Lfo {
+    fn default() -> Lfo {
+        Lfo {
+            waveform: LfoWaveform::Sine,
+            rate: 5.0,
+            depth: 0.0,
+            phase: 0.0,
+            retrigger: false,
+            destination: Destination::Pitch,
+        }
+    }
+}
+
+/// Running LFO inside a voice.
+pub struct LfoState {
+    /// Cycles elapsed, counted from the engine start for free-running LFOs.
+    cycles: f64,
+    increment: f64,
+    seed: u32,
+}
+
+impl LfoState {
+    /// Starts an LFO for a new note. A free-running LFO picks up where the engine clock is, so
+    /// every voice hears the same LFO.
+    pub fn new(lfo: &Lfo, epoch: Instant, seed: u32, sample_rate: f64) -> LfoState {
+        let (cycles, seed) = if lfo.retrigger {
+            (lfo.phase, seed)
+        } else {
+            (epoch.elapsed().as_secs_f64() * lfo.rate + lfo.phase, 0)
+        };
+
+        LfoState {
+            cycles,
+            increment: lfo.rate / sample_rate,
+            seed,
+        }
+    }
+
+    /// Next value between -1 and 1, with the rate offset by `octaves`.
+    pub fn tick(&mut self, waveform: LfoWaveform, octaves: f64) -> f64 {
+        let phase = self.cycles.fract();
+        let value = match waveform {
+            LfoWaveform::Sine => (phase * TAU).sin(),
+            LfoWaveform::Triangle => 1.0 - 4.0 * ((phase + 0.25).fract() - 0.5).abs(),
+            LfoWaveform::Saw => phase * 2.0 - 1.0,
+            LfoWaveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            // Hashing the cycle number holds one random value per cycle, shared between voices
+            LfoWaveform::SampleAndHold => random(self.cycles as u32 ^ self.seed),
+        };
+        self.cycles += self.increment * octaves.exp2();
+        value
+    }
+}
+
+/// Pseudo random value between -1 and 1 for a seed.
+pub fn random(seed: u32) -> f64 {
+    let mut x = seed.wrapping_mul(0x9e37_79b9) ^ 0x85eb_ca6b;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x as f64 / u32::MAX as f64 * 2.0 - 1.0
+}
+
+/// Sum of all modulation applied to a voice for one sample.
+#[derive(Default)]
+pub struct Modulation {
+    /// Offset in semitones.
+    pub pitch: f64,
+    /// Offset of the pulse width from a square wave.
+    pub pulse_width: f64,
+    /// Offset from the center, -1 is left and 1 is right.
+    pub pan: f64,
+    /// Offset of the gain, -1 is silent.
+    pub level: f64,
+    pub fm_index: f64,
+    /// Cutoff offset of each filter in octaves.
+    pub cutoff: [f64; 2],
+    /// Resonance offset of each filter in percent.
+    pub resonance: [f64; 2],
+    pub drive: [f64; 2],
+    pub balance: f64,
+    /// Rate offset of each LFO in octaves.
+    pub lfo_rate: [f64; 2],
+    pub lfo_depth: [f64; 2],
+}
+
+impl Modulation {
+    /// Adds a bipolar modulation amount, scaled to the range of the destination.
+    pub fn add(&mut self, destination: Destination, amount: f64) {
+        match destination {
+            Destination::Pitch => self.pitch += amount * 12.0,
+            Destination::PulseWidth => self.pulse_width += amount * 0.45,
+            Destination::Pan => self.pan += amount,
+            Destination::Level => self.level += amount,
+            Destination::FmIndex => self.fm_index += amount * 10.0,
+            Destination::Cutoff => {
+                self.cutoff[0] += amount * 4.0;
+                self.cutoff[1] += amount * 4.0;
+            }
+            Destination::Cutoff1 => self.cutoff[0] += amount * 4.0,
+            Destination::Cutoff2 => self.cutoff[1] += amount * 4.0,
+            Destination::Resonance1 => self.resonance[0] += amount * 100.0,
+            Destination::Resonance2 => self.resonance[1] += amount * 100.0,
+            Destination::Drive1 => self.drive[0] += amount,
+            Destination::Drive2 => self.drive[1] += amount,
+            Destination::FilterBalance => self.balance += amount,
+            Destination::Lfo1Rate => self.lfo_rate[0] += amount * 4.0,
+            Destination::Lfo2Rate => self.lfo_rate[1] += amount * 4.0,
+            Destination::Lfo1Depth => self.lfo_depth[0] += amount,
+            Destination::Lfo2Depth => self.lfo_depth[1] += amount,
+        }
+    }
+
+    pub fn frequency_ratio(&self) -> f64 {
+        (self.pitch / 12.0).exp2()
+    }
+
+    pub fn pulse_width(&self) -> f64 {
+        (0.5 + self.pulse_width).clamp(0.05, 0.95)
+    }
+
+    pub fn gain(&self) -> f64 {
+        (1.0 + self.level).max(0.0)
+    }
+
+    /// Equal power gains of the left and right channel, at unity in the center.
+    pub fn pan_gains(&self) -> (f64, f64) {
+        let angle = (self.pan.clamp(-1.0, 1.0) + 1.0) * TAU / 8.0;
+        (angle.cos() * SQRT_2, angle.sin() * SQRT_2)
+    }
+}
@@ -0,0 +1,255 @@
+use log::info;
+
+/// Registered parameter number of the MPE Configuration Message.
+const MCM_RPN: (u8, u8) = (0, 6);
+/// Registered parameter number of the pitch bend sensitivity.
+const BEND_RANGE_RPN: (u8, u8) = (0, 0);
+/// Parameter number that selects no parameter, so data entry is ignored.
+const NULL_RPN: (u8, u8) = (127, 127);
+
+const RPN_MSB_CC: u8 = 101;
+const RPN_LSB_CC: u8 = 100;
+const NRPN_MSB_CC: u8 = 99;
+const NRPN_LSB_CC: u8 = 98;
+const DATA_ENTRY_CC: u8 = 6;
+
+const LOWER_MASTER: u8 = 0;
+const UPPER_MASTER: u8 = 15;
+
+/// How the messages of a channel are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelRole {
+    /// Affects every voice, as on a conventional synth or the master channel of a zone.
+    Global,
+    /// Affects only the notes played on this channel.
+    Member,
+}
+
+/// MIDI Polyphonic Expression zones. Channels are numbered from 0.
+#[derive(Clone)]
+pub struct Mpe {
+    pub enabled: bool,
+    /// Member channels of the lower zone, which follow master channel 1.
+    pub lower_members: u8,
+    /// Member channels of the upper zone, which precede master channel 16.
+    pub upper_members: u8,
+    /// Pitch bend range of member channels in semitones.
+    pub member_bend_range: f64,
+    /// Pitch bend range of master channels, and of every channel outside MPE mode.
+    pub master_bend_range: f64,
+}
+
+impl Default for Mpe {
+    fn default() -> Mpe {
+        Mpe {
+            enabled: false,
+            lower_members: 15,
+            upper_members: 0,
+            member_bend_range: 48.0,
+            master_bend_range: 2.0,
+        }
+    }
+}
+
+impl Mpe {
+    pub fn role(&self, channel: u8) -> ChannelRole {
+        let lower = LOWER_MASTER + 1..=LOWER_MASTER + self.lower_members;
+        let upper = UPPER_MASTER - self.upper_members..UPPER_MASTER;
+        if self.enabled && (lower.contains(&channel) || upper.contains(&channel)) {
+            ChannelRole::Member
+        } else {
+            ChannelRole::Global
+        }
+    }
+
+    /// Pitch bend as a frequency ratio, from the 14 bit value of a channel.
+    pub fn bend_ratio(&self, channel: u8, bend: u16) -> f64 {
+        let range = match self.role(channel) {
+            ChannelRole::Member => self.member_bend_range,
+            ChannelRole::Global => self.master_bend_range,
+        };
+        let amount = (bend as f64 - 8192.0) / 8192.0;
+        (amount * range / 12.0).exp2()
+    }
+
+    /// Sets up a zone from an MPE Configuration Message received on its master channel.
+    fn configure(&mut self, channel: u8, members: u8) {
+        let members = members.min(15);
+        match channel {
+            LOWER_MASTER => {
+                self.lower_members = members;
+                // A zone taking all member channels shrinks the other one
+                self.upper_members = self.upper_members.min(14 - members.min(14));
+            }
+            UPPER_MASTER => {
+                self.upper_members = members;
+                self.lower_members = self.lower_members.min(14 - members.min(14));
+            }
+            _ => return,
+        }
+        // Configuring a zone switches MPE on, and closing both switches it off
+        self.enabled = self.lower_members > 0 || self.upper_members > 0;
+        info!(
+            "MPE zones: lower {} and upper {} member channels",
+            self.lower_members, self.upper_members
+        );
+    }
+}
+
+/// Follows the registered parameter selected on each channel, to pick up the MPE
+/// configuration and pitch bend ranges.
+pub struct RpnReceiver {
+    selected: [(u8, u8); 16],
+}
+
+impl Default for RpnReceiver {
+    fn default() -> RpnReceiver {
+        RpnReceiver {
+            selected: [NULL_RPN; 16],
+        }
+    }
+}
+
+impl RpnReceiver {
+    /// Feeds a control change, returning true if it was part of a parameter message.
+    pub fn receive(&mut self, mpe: &mut Mpe, channel: u8, control: u8, value: u8) -> bool {
+        let selected = &mut self.selected[channel as usize & 15];
+        match control {
+            RPN_MSB_CC => selected.0 = value,
+            RPN_LSB_CC => selected.1 = value,
+            // Data entry for a non-registered parameter must not change the last registered one
+            NRPN_MSB_CC | NRPN_LSB_CC => *selected = NULL_RPN,
+            DATA_ENTRY_CC => match *selected {
+                MCM_RPN => mpe.configure(channel, value),
+                BEND_RANGE_RPN => match mpe.role(channel) {
+                    ChannelRole::Member => mpe.member_bend_range = value as f64,
+                    ChannelRole::Global => mpe.master_bend_range = value as f64,
+                },
+                _ => {}
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zones(lower_members: u8, upper_members: u8) -> Mpe {
+        Mpe {
+            enabled: true,
+            lower_members,
+            upper_members,
+            ..Mpe::default()
+        }
+    }
+
+    fn members(mpe: &Mpe) -> Vec<u8> {
+        (0..16)
+            .filter(|channel| mpe.role(*channel) == ChannelRole::Member)
+            .collect()
+    }
+
+    /// Sends a complete registered parameter message on a channel.
+    fn send_rpn(receiver: &mut RpnReceiver, mpe: &mut Mpe, channel: u8, rpn: (u8, u8), value: u8) {
+        assert!(receiver.receive(mpe, channel, RPN_MSB_CC, rpn.0));
+        assert!(receiver.receive(mpe, channel, RPN_LSB_CC, rpn.1));
+        assert!(receiver.receive(mpe, channel, DATA_ENTRY_CC, value));
+    }
+
+    #[test]
+    fn lower_zone_members_follow_channel_1() {
+        assert_eq!(members(&zones(7, 0)), (1..=7).collect::<Vec<u8>>());
+        assert_eq!(members(&zones(15, 0)), (1..=15).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn upper_zone_members_precede_channel_16() {
+        assert_eq!(members(&zones(0, 5)), (10..=14).collect::<Vec<u8>>());
+        assert_eq!(members(&zones(0, 15)), (0..=14).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn both_zones_leave_their_master_channels_global() {
+        let mpe = zones(3, 3);
+        assert_eq!(members(&mpe), vec![1, 2, 3, 12, 13, 14]);
+        assert_eq!(mpe.role(LOWER_MASTER), ChannelRole::Global);
+        assert_eq!(mpe.role(UPPER_MASTER), ChannelRole::Global);
+    }
+
+    #[test]
+    fn every_channel_is_global_without_mpe() {
+        let mpe = Mpe {
+            enabled: false,
+            ..zones(7, 7)
+        };
+        assert!(members(&mpe).is_empty());
+    }
+
+    #[test]
+    fn configuration_messages_switch_mpe_on_and_off() {
+        let mut mpe = Mpe::default();
+        let mut receiver = RpnReceiver::default();
+
+        send_rpn(&mut receiver, &mut mpe, LOWER_MASTER, MCM_RPN, 4);
+        assert!(mpe.enabled);
+        assert_eq!((mpe.lower_members, mpe.upper_members), (4, 0));
+
+        // An upper zone taking every member channel closes the lower zone
+        send_rpn(&mut receiver, &mut mpe, UPPER_MASTER, MCM_RPN, 14);
+        assert!(mpe.enabled);
+        assert_eq!((mpe.lower_members, mpe.upper_members), (0, 14));
+
+        send_rpn(&mut receiver, &mut mpe, UPPER_MASTER, MCM_RPN, 0);
+        assert!(!mpe.enabled);
+        assert_eq!((mpe.lower_members, mpe.upper_members), (0, 0));
+    }
+
+    #[test]
+    fn configuration_messages_on_member_channels_are_ignored() {
+        let mut mpe = zones(7, 0);
+        let mut receiver = RpnReceiver::default();
+        send_rpn(&mut receiver, &mut mpe, 3, MCM_RPN, 2);
+        assert_eq!((mpe.lower_members, mpe.upper_members), (7, 0));
+    }
+
+    #[test]
+    fn bend_ranges_are_set_per_role() {
+        let mut mpe = zones(7, 0);
+        let mut receiver = RpnReceiver::default();
+        send_rpn(&mut receiver, &mut mpe, 2, BEND_RANGE_RPN, 24);
+        send_rpn(&mut receiver, &mut mpe, LOWER_MASTER, BEND_RANGE_RPN, 12);
+        assert_eq!(mpe.member_bend_range, 24.0);
+        assert_eq!(mpe.master_bend_range, 12.0);
+
+        assert_eq!(mpe.bend_ratio(2, 8192), 1.0);
+        assert!((mpe.bend_ratio(2, 0) - 0.25).abs() < 1e-9);
+        assert!((mpe.bend_ratio(LOWER_MASTER, 0) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn data_entry_after_an_nrpn_is_ignored() {
+        let mut mpe = zones(7, 0);
+        let mut receiver = RpnReceiver::default();
+        send_rpn(&mut receiver, &mut mpe, LOWER_MASTER, BEND_RANGE_RPN, 12);
+
+        assert!(receiver.receive(&mut mpe, LOWER_MASTER, NRPN_MSB_CC, 0));
+        assert!(receiver.receive(&mut mpe, LOWER_MASTER, NRPN_LSB_CC, 6));
+        assert!(receiver.receive(&mut mpe, LOWER_MASTER, DATA_ENTRY_CC, 1));
+        assert_eq!(mpe.master_bend_range, 12.0);
+        assert_eq!((mpe.lower_members, mpe.upper_members), (7, 0));
+
+        // Selecting a registered parameter again makes data entry apply to it
+        send_rpn(&mut receiver, &mut mpe, LOWER_MASTER, BEND_RANGE_RPN, 2);
+        assert_eq!(mpe.master_bend_range, 2.0);
+    }
+
+    #[test]
+    fn other_controllers_pass_through() {
+        let mut mpe = Mpe::default();
+        let mut receiver = RpnReceiver::default();
+        assert!(!receiver.receive(&mut mpe, 0, 74, 64));
+    }
+}
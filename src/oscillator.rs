@@ -1,7 +1,10 @@
-use std::collections::VecDeque;
+use std::sync::Arc;
 
+use crate::sample::{Sample, SamplePlayer, SampleSettings};
+use crate::sf2::SoundFont;
+use crate::sfz::SfzInstrument;
 use fundsp::{
-    hacker::{adsr_live, lowpass_hz, saw, sine, square, triangle},
+    hacker::{adsr_live, pulse, saw, sine, triangle},
     prelude::{midi_hz, An, AudioUnit64, Var},
 };
 
@@ -14,14 +17,20 @@ pub enum Waveform {
     Triangle,
     Sawtooth,
     Square,
+    Sample,
+    Sfz,
+    SoundFont,
 }
 
 impl Waveform {
-    pub const ALL: [Waveform; 4] = [
+    pub const ALL: [Waveform; 7] = [
         Waveform::Sine,
         Waveform::Triangle,
         Waveform::Sawtooth,
         Waveform::Square,
+        Waveform::Sample,
+        Waveform::Sfz,
+        Waveform::SoundFont,
     ];
 }
 
@@ -41,16 +50,180 @@ impl std::fmt::Display for Waveform {
                 Waveform::Triangle => "Triangle",
                 Waveform::Sawtooth => "Sawtooth",
                 Waveform::Square => "Square",
+                Waveform::Sample => "Sample",
+                Waveform::Sfz => "SFZ",
+                Waveform::SoundFont => "SoundFont",
             }
         )
     }
 }
 
+/// How oscillator 2 interacts with oscillator 1 inside a voice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossModulation {
+    /// Both oscillators play independently.
+    Off,
+    /// Oscillator 2 modulates the frequency of oscillator 1 and is not heard itself.
+    /// Its envelope shapes the modulation depth.
+    Fm,
+    /// Oscillator 1 is multiplied by oscillator 2, which is not heard itself.
+    Ring,
+    /// Oscillator 2 restarts its cycle whenever oscillator 1 completes one.
+    Sync,
+}
+
+impl CrossModulation {
+    pub const ALL: [CrossModulation; 4] = [
+        CrossModulation::Off,
+        CrossModulation::Fm,
+        CrossModulation::Ring,
+        CrossModulation::Sync,
+    ];
+}
+
+impl Default for CrossModulation {
+    fn default() -> CrossModulation {
+        CrossModulation::Off
+    }
+}
+
+impl std::fmt::Display for CrossModulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                CrossModulation::Off => "Off",
+                CrossModulation::Fm => "FM",
+                CrossModulation::Ring => "Ring",
+                CrossModulation::Sync => "Sync",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CrossMod {
+    pub mode: CrossModulation,
+    /// FM index, the peak frequency deviation in multiples of the modulator frequency.
+    pub fm_index: f64,
+}
+
+/// The filter an oscillator is routed through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterSlot {
+    Filter1,
+    Filter2,
+}
+
+impl FilterSlot {
+    pub const ALL: [FilterSlot; 2] = [FilterSlot::Filter1, FilterSlot::Filter2];
+
+    pub fn index(&self) -> usize {
+        match self {
+            FilterSlot::Filter1 => 0,
+            FilterSlot::Filter2 => 1,
+        }
+    }
+}
+
+impl Default for FilterSlot {
+    fn default() -> FilterSlot {
+        FilterSlot::Filter1
+    }
+}
+
+impl std::fmt::Display for FilterSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FilterSlot::Filter1 => "Filter 1",
+                FilterSlot::Filter2 => "Filter 2",
+            }
+        )
+    }
+}
+
+/// Sound generator of one oscillator inside a voice, driven by a frequency and a pulse width.
+pub enum Source {
+    Unit(Box<dyn AudioUnit64>),
+    Sample(SamplePlayer),
+    /// Several samples layered, as triggered by an SFZ instrument or a SoundFont preset.
+    Layers(Vec<SamplePlayer>),
+    Silence,
+}
+
+impl Source {
+    pub fn tick(&mut self, frequency: f64, width: f64) -> f64 {
+        match self {
+            Source::Unit(unit) => {
+                // Only the pulse wave has a width input
+                let input = [frequency, width];
+                let mut output = [0.0];
+                unit.tick(&input[..unit.inputs()], &mut output);
+                output[0]
+            }
+            Source::Sample(player) => player.tick(frequency),
+            Source::Layers(players) => players.iter_mut().map(|p| p.tick(frequency)).sum(),
+            Source::Silence => 0.0,
+        }
+    }
+
+    pub fn reset(&mut self, sample_rate: f64) {
+        match self {
+            Source::Unit(unit) => unit.reset(Some(sample_rate)),
+            Source::Sample(player) => player.reset(sample_rate),
+            Source::Layers(players) => {
+                for player in players {
+                    player.reset(sample_rate);
+                }
+            }
+            Source::Silence => {}
+        }
+    }
+
+    /// Restarts the cycle of the source when the oscillator it is synced to starts a new one.
+    pub fn sync(&mut self, sample_rate: f64) {
+        match self {
+            Source::Unit(unit) => unit.reset(Some(sample_rate)),
+            Source::Sample(player) => player.restart(),
+            Source::Layers(players) => {
+                for player in players {
+                    player.restart();
+                }
+            }
+            Source::Silence => {}
+        }
+    }
+
+    /// Passes the release of the key on to sample players.
+    pub fn release(&mut self) {
+        match self {
+            Source::Sample(player) => player.release(),
+            Source::Layers(players) => {
+                for player in players {
+                    player.release();
+                }
+            }
+            Source::Unit(_) | Source::Silence => {}
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Oscillator {
     pub waveform: Waveform,
     pub adsr: ADSR,
     pub detune: f32,
+    pub filter: FilterSlot,
+    pub sample: Option<Arc<Sample>>,
+    pub sample_settings: SampleSettings,
+    pub instrument: Option<Arc<SfzInstrument>>,
+    pub soundfont: Option<Arc<SoundFont>>,
+    /// Index into the presets of `soundfont`.
+    pub preset: usize,
 }
 
 impl Default for Oscillator {
@@ -59,98 +232,70 @@ impl Default for Oscillator {
             waveform: Waveform::Sine,
             adsr: ADSR(0.1, 0.2, 0.4, 0.0),
             detune: 0.0,
+            filter: FilterSlot::Filter1,
+            sample: None,
+            sample_settings: SampleSettings::default(),
+            instrument: None,
+            soundfont: None,
+            preset: 0,
         }
     }
 }
 
 impl Oscillator {
-    pub fn new(waveform: Waveform, adsr: ADSR, detune: f32) -> Oscillator {
+    pub fn new(waveform: Waveform, adsr: ADSR, detune: f32, filter: FilterSlot) -> Oscillator {
         Oscillator {
             waveform,
             adsr,
             detune,
+            filter,
+            sample: None,
+            sample_settings: SampleSettings::default(),
+            instrument: None,
+            soundfont: None,
+            preset: 0,
         }
     }
 
-    pub fn generate_note(
-        &self,
-        note: u8,
-        velocity: u8,
-        releasing: An<Var<f64>>,
-        finished: An<Var<f64>>,
-        pitch_bend: An<Var<f64>>,
-        filter: (f64, f64),
-    ) -> Box<dyn AudioUnit64> {
-        let pitch = midi_hz(note as f64);
-        let volume = velocity as f64 / 127.0;
+    pub fn frequency(&self, note: u8) -> f64 {
+        midi_hz(note as f64) + self.detune as f64
+    }
 
+    pub fn generate_source(&self, note: u8, velocity: u8) -> Source {
         match &self.waveform {
-            Waveform::Sine => Box::new(
-                (pitch + self.detune as f64) * pitch_bend
-                    >> sine()
-                        * adsr_live(
-                            self.adsr.0,
-                            self.adsr.1,
-                            self.adsr.2,
-                            self.adsr.3,
-                            releasing,
-                            finished,
-                        )
-                        * volume
-                        * 2.0
-                    >> lowpass_hz(filter.0, filter.1),
-            ),
-            Waveform::Triangle => Box::new(
-                (pitch + self.detune as f64) * pitch_bend
-                    >> triangle()
-                        * adsr_live(
-                            self.adsr.0,
-                            self.adsr.1,
-                            self.adsr.2,
-                            self.adsr.3,
-                            releasing,
-                            finished,
-                        )
-                        * volume
-                        * 2.0
-                    >> lowpass_hz(filter.0, filter.1),
-            ),
-            Waveform::Sawtooth => Box::new(
-                (pitch + self.detune as f64) * pitch_bend
-                    >> saw()
-                        * adsr_live(
-                            self.adsr.0,
-                            self.adsr.1,
-                            self.adsr.2,
-                            self.adsr.3,
-                            releasing,
-                            finished,
-                        )
-                        * volume
-                        * 2.0
-                    >> lowpass_hz(filter.0, filter.1),
-            ),
-            Waveform::Square => Box::new(
-                (pitch + self.detune as f64) * pitch_bend
-                    >> square()
-                        * adsr_live(
-                            self.adsr.0,
-                            self.adsr.1,
-                            self.adsr.2,
-                            self.adsr.3,
-                            releasing,
-                            finished,
-                        )
-                        * volume
-                        * 2.0
-                    >> lowpass_hz(filter.0, filter.1),
-            ),
+            Waveform::Sine => Source::Unit(Box::new(sine())),
+            Waveform::Triangle => Source::Unit(Box::new(triangle())),
+            Waveform::Sawtooth => Source::Unit(Box::new(saw())),
+            Waveform::Square => Source::Unit(Box::new(pulse())),
+            Waveform::Sample => match &self.sample {
+                Some(sample) => {
+                    Source::Sample(SamplePlayer::new(sample.clone(), &self.sample_settings))
+                }
+                None => Source::Silence,
+            },
+            Waveform::Sfz => match &self.instrument {
+                Some(instrument) => Source::Layers(instrument.players(note, velocity)),
+                None => Source::Silence,
+            },
+            Waveform::SoundFont => match &self.soundfont {
+                Some(soundfont) => Source::Layers(soundfont.players(self.preset, note, velocity)),
+                None => Source::Silence,
+            },
         }
     }
 
-    pub fn release_all(&self, awaiting_release: &mut VecDeque<An<Var<f64>>>) {
-        while let Some(m) = awaiting_release.pop_front() {
-            m.set_value(1.0);
-        }
+    pub fn generate_envelope(
+        &self,
+        releasing: An<Var<f64>>,
+        finished: An<Var<f64>>,
+    ) -> Box<dyn AudioUnit64> {
+        Box::new(adsr_live(
+            self.adsr.0,
+            self.adsr.1,
+            self.adsr.2,
+            self.adsr.3,
+            releasing,
+            finished,
+        ))
     }
 }
@@ -0,0 +1,556 @@
+use crate::{
+    arpeggiator::{ArpMode, ArpSettings},
+    effects::{
+        CompressorSettings, ConvolutionSettings, DelaySettings, DistortionCurve,
+        DistortionPlacement, DistortionSettings, EffectChain, EffectKind, EqSettings,
+        ModulationSettings, ModulationType, ReverbSettings,
+    },
+    filter::{Filter, FilterRouting, FilterType, Routing},
+    modulation::{Destination, Lfo, LfoWaveform, ModSlot, ModSource},
+    oscillator::{CrossMod, CrossModulation, FilterSlot, Oscillator, Waveform, ADSR},
+    sample::PlayMode,
+    tempo::NoteDivision,
+    voicing::{GlideCurve, NotePriority, VoiceMode, VoicingSettings},
+};
+use anyhow::{anyhow, bail};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Write};
+use std::str::FromStr;
+
+/// Settings of a sound, saved as one `key = value` line each. Samples, SFZ instruments,
+/// SoundFonts and impulse responses are files of their own, so only their settings are kept.
+#[derive(Clone)]
+pub struct Patch {
+    pub tempo: f64,
+    pub oscillators: Vec<Oscillator>,
+    pub cross_mod: CrossMod,
+    pub routing: Routing,
+    pub filters: Vec<Filter>,
+    pub lfos: Vec<Lfo>,
+    pub matrix: Vec<ModSlot>,
+    pub voicing: VoicingSettings,
+    pub arpeggiator: ArpSettings,
+    pub chain: EffectChain,
+    pub distortion: DistortionSettings,
+    pub modulation: ModulationSettings,
+    pub delay: DelaySettings,
+    pub reverb: ReverbSettings,
+    pub convolution: ConvolutionSettings,
+    pub eq: EqSettings,
+    pub compressor: CompressorSettings,
+}
+
+impl Patch {
+    pub fn load(path: &str) -> anyhow::Result<Patch> {
+        Patch::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    /// Lists of settings are numbered from 1, like `osc1.attack` or `effect3.bypass`. Enums
+    /// are written by the name of their variant.
+    fn to_text(&self) -> String {
+        let mut out = Writer(String::from("# Hacksynth patch\n"));
+        out.value("tempo", self.tempo);
+
+        for (i, oscillator) in self.oscillators.iter().enumerate() {
+            let key = |name| format!("osc{}.{name}", i + 1);
+            let sample = &oscillator.sample_settings;
+            out.choice(&key("waveform"), oscillator.waveform);
+            out.value(&key("attack"), oscillator.adsr.0);
+            out.value(&key("decay"), oscillator.adsr.1);
+            out.value(&key("sustain"), oscillator.adsr.2);
+            out.value(&key("release"), oscillator.adsr.3);
+            out.value(&key("detune"), oscillator.detune);
+            out.choice(&key("filter"), oscillator.filter);
+            out.value(&key("preset"), oscillator.preset);
+            out.value(&key("root"), sample.root);
+            out.value(&key("start"), sample.start);
+            out.value(&key("end"), sample.end);
+            out.value(&key("loop_start"), sample.loop_start);
+            out.value(&key("loop_end"), sample.loop_end);
+            out.value(&key("crossfade"), sample.crossfade);
+            out.choice(&key("mode"), sample.mode);
+        }
+        out.choice("cross_mod.mode", self.cross_mod.mode);
+        out.value("cross_mod.fm_index", self.cross_mod.fm_index);
+        out.choice("routing.mode", self.routing.mode);
+        out.value("routing.balance", self.routing.balance);
+
+        for (i, filter) in self.filters.iter().enumerate() {
+            let key = |name| format!("filter{}.{name}", i + 1);
+            out.choice(&key("type"), filter.filter_type);
+            out.value(&key("cutoff"), filter.cutoff);
+            out.value(&key("resonance"), filter.resonance);
+            out.value(&key("drive"), filter.drive);
+            out.value(&key("attack"), filter.envelope.0);
+            out.value(&key("decay"), filter.envelope.1);
+            out.value(&key("sustain"), filter.envelope.2);
+            out.value(&key("release"), filter.envelope.3);
+            out.value(&key("envelope_amount"), filter.envelope_amount);
+            out.value(&key("velocity_amount"), filter.velocity_amount);
+            out.value(&key("key_tracking"), filter.key_tracking);
+        }
+        for (i, lfo) in self.lfos.iter().enumerate() {
+            let key = |name| format!("lfo{}.{name}", i + 1);
+            out.choice(&key("waveform"), lfo.waveform);
+            out.value(&key("rate"), lfo.rate);
+            out.value(&key("depth"), lfo.depth);
+            out.value(&key("phase"), lfo.phase);
+            out.value(&key("retrigger"), lfo.retrigger);
+            out.choice(&key("destination"), lfo.destination);
+        }
+        for (i, slot) in self.matrix.iter().enumerate() {
+            let key = |name| format!("mod{}.{name}", i + 1);
+            out.choice(&key("source"), slot.source);
+            out.choice(&key("destination"), slot.destination);
+            out.value(&key("amount"), slot.amount);
+        }
+
+        out.choice("voicing.mode", self.voicing.mode);
+        out.choice("voicing.priority", self.voicing.priority);
+        out.value("voicing.glide", self.voicing.glide);
+        out.choice("voicing.glide_curve", self.voicing.glide_curve);
+        out.value("arp.enabled", self.arpeggiator.enabled);
+        out.choice("arp.mode", self.arpeggiator.mode);
+        out.value("arp.octaves", self.arpeggiator.octaves);
+        out.choice("arp.rate", self.arpeggiator.rate);
+        out.value("arp.gate", self.arpeggiator.gate);
+        out.value("arp.swing", self.arpeggiator.swing);
+        out.value("arp.latch", self.arpeggiator.latch);
+
+        for (i, slot) in self.chain.slots.iter().enumerate() {
+            out.choice(&format!("effect{}", i + 1), slot.kind);
+            out.value(&format!("effect{}.bypass", i + 1), slot.bypass);
+        }
+        out.choice("distortion.placement", self.distortion.placement);
+        out.choice("distortion.curve", self.distortion.curve);
+        out.value("distortion.drive", self.distortion.drive);
+        out.value("distortion.bits", self.distortion.bits);
+        out.value("distortion.downsample", self.distortion.downsample);
+        out.value("distortion.mix", self.distortion.mix);
+        out.choice("modulation.type", self.modulation.modulation_type);
+        out.value("modulation.rate", self.modulation.rate);
+        out.value("modulation.depth", self.modulation.depth);
+        out.value("modulation.feedback", self.modulation.feedback);
+        out.value("modulation.mix", self.modulation.mix);
+        out.value("delay.sync", self.delay.sync);
+        out.value("delay.time", self.delay.time);
+        out.choice("delay.division", self.delay.division);
+        out.value("delay.feedback", self.delay.feedback);
+        out.value("delay.high_cut", self.delay.high_cut);
+        out.value("delay.ping_pong", self.delay.ping_pong);
+        out.value("delay.mix", self.delay.mix);
+        out.value("reverb.size", self.reverb.size);
+        out.value("reverb.damping", self.reverb.damping);
+        out.value("reverb.pre_delay", self.reverb.pre_delay);
+        out.value("reverb.width", self.reverb.width);
+        out.value("reverb.mix", self.reverb.mix);
+        out.value("convolution.pre_delay", self.convolution.pre_delay);
+        out.value("convolution.high_cut", self.convolution.high_cut);
+        out.value("convolution.mix", self.convolution.mix);
+        for (i, band) in self.eq.bands.iter().enumerate() {
+            let key = |name| format!("eq{}.{name}", i + 1);
+            out.value(&key("frequency"), band.frequency);
+            out.value(&key("gain"), band.gain);
+            out.value(&key("q"), band.q);
+        }
+        out.value("compressor.threshold", self.compressor.threshold);
+        out.value("compressor.ratio", self.compressor.ratio);
+        out.value("compressor.attack", self.compressor.attack);
+        out.value("compressor.release", self.compressor.release);
+        out.value("compressor.makeup", self.compressor.makeup);
+        out.value("compressor.knee", self.compressor.knee);
+        out.0
+    }
+
+    /// Settings missing from the text keep their default, so patches saved before a setting
+    /// existed still load.
+    fn parse(text: &str) -> anyhow::Result<Patch> {
+        let mut values = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {} is not a setting", number + 1))?;
+            values.insert(key.trim(), value.trim());
+        }
+        let patch = Reader(values);
+
+        let mut oscillators = Vec::new();
+        for i in 1..=patch.count("osc") {
+            let key = |name| format!("osc{i}.{name}");
+            let default = Oscillator::default();
+            let mut oscillator = Oscillator::new(
+                patch.choice(&key("waveform"), &Waveform::ALL, default.waveform)?,
+                ADSR(
+                    patch.value(&key("attack"), default.adsr.0)?,
+                    patch.value(&key("decay"), default.adsr.1)?,
+                    patch.value(&key("sustain"), default.adsr.2)?,
+                    patch.value(&key("release"), default.adsr.3)?,
+                ),
+                patch.value(&key("detune"), default.detune)?,
+                patch.choice(&key("filter"), &FilterSlot::ALL, default.filter)?,
+            );
+            oscillator.preset = patch.value(&key("preset"), default.preset)?;
+            let sample = &mut oscillator.sample_settings;
+            sample.root = patch.value(&key("root"), sample.root)?;
+            sample.start = patch.value(&key("start"), sample.start)?;
+            sample.end = patch.value(&key("end"), sample.end)?;
+            sample.loop_start = patch.value(&key("loop_start"), sample.loop_start)?;
+            sample.loop_end = patch.value(&key("loop_end"), sample.loop_end)?;
+            sample.crossfade = patch.value(&key("crossfade"), sample.crossfade)?;
+            sample.mode = patch.choice(&key("mode"), &PlayMode::ALL, sample.mode)?;
+            oscillators.push(oscillator);
+        }
+
+        let cross_mod = CrossMod::default();
+        let cross_mod = CrossMod {
+            mode: patch.choice("cross_mod.mode", &CrossModulation::ALL, cross_mod.mode)?,
+            fm_index: patch.value("cross_mod.fm_index", cross_mod.fm_index)?,
+        };
+        let routing = Routing::default();
+        let routing = Routing {
+            mode: patch.choice("routing.mode", &FilterRouting::ALL, routing.mode)?,
+            balance: patch.value("routing.balance", routing.balance)?,
+        };
+
+        let mut filters = Vec::new();
+        for i in 1..=patch.count("filter") {
+            let key = |name| format!("filter{i}.{name}");
+            let filter = Filter::default();
+            filters.push(Filter {
+                filter_type: patch.choice(&key("type"), &FilterType::ALL, filter.filter_type)?,
+                cutoff: patch.value(&key("cutoff"), filter.cutoff)?,
+                resonance: patch.value(&key("resonance"), filter.resonance)?,
+                drive: patch.value(&key("drive"), filter.drive)?,
+                envelope: ADSR(
+                    patch.value(&key("attack"), filter.envelope.0)?,
+                    patch.value(&key("decay"), filter.envelope.1)?,
+                    patch.value(&key("sustain"), filter.envelope.2)?,
+                    patch.value(&key("release"), filter.envelope.3)?,
+                ),
+                envelope_amount: patch.value(&key("envelope_amount"), filter.envelope_amount)?,
+                velocity_amount: patch.value(&key("velocity_amount"), filter.velocity_amount)?,
+                key_tracking: patch.value(&key("key_tracking"), filter.key_tracking)?,
+            });
+        }
+        let mut lfos = Vec::new();
+        for i in 1..=patch.count("lfo") {
+            let key = |name| format!("lfo{i}.{name}");
+            let lfo = Lfo::default();
+            lfos.push(Lfo {
+                waveform: patch.choice(&key("waveform"), &LfoWaveform::ALL, lfo.waveform)?,
+                rate: patch.value(&key("rate"), lfo.rate)?,
+                depth: patch.value(&key("depth"), lfo.depth)?,
+                phase: patch.value(&key("phase"), lfo.phase)?,
+                retrigger: patch.value(&key("retrigger"), lfo.retrigger)?,
+                destination: patch.choice(
+                    &key("destination"),
+                    &Destination::ALL,
+                    lfo.destination,
+                )?,
+            });
+        }
+        let mut matrix = Vec::new();
+        for i in 1..=patch.count("mod") {
+            let key = |name| format!("mod{i}.{name}");
+            let slot = ModSlot::default();
+            matrix.push(ModSlot {
+                source: patch.choice(&key("source"), &ModSource::ALL, slot.source)?,
+                destination: patch.choice(
+                    &key("destination"),
+                    &Destination::ALL,
+                    slot.destination,
+                )?,
+                amount: patch.value(&key("amount"), slot.amount)?,
+            });
+        }
+
+        let voicing = VoicingSettings::default();
+        let voicing = VoicingSettings {
+            mode: patch.choice("voicing.mode", &VoiceMode::ALL, voicing.mode)?,
+            priority: patch.choice("voicing.priority", &NotePriority::ALL, voicing.priority)?,
+            glide: patch.value("voicing.glide", voicing.glide)?,
+            glide_curve: patch.choice(
+                "voicing.glide_curve",
+                &GlideCurve::ALL,
+                voicing.glide_curve,
+            )?,
+        };
+        let arp = ArpSettings::default();
+        let arpeggiator = ArpSettings {
+            enabled: patch.value("arp.enabled", arp.enabled)?,
+            mode: patch.choice("arp.mode", &ArpMode::ALL, arp.mode)?,
+            octaves: patch.value("arp.octaves", arp.octaves)?,
+            rate: patch.choice("arp.rate", &NoteDivision::ALL, arp.rate)?,
+            gate: patch.value("arp.gate", arp.gate)?,
+            swing: patch.value("arp.swing", arp.swing)?,
+            latch: patch.value("arp.latch", arp.latch)?,
+        };
+
+        let mut chain = EffectChain::default();
+        for (i, slot) in chain.slots.iter_mut().enumerate() {
+            slot.kind = patch.choice(&format!("effect{}", i + 1), &EffectKind::ALL, slot.kind)?;
+            slot.bypass = patch.value(&format!("effect{}.bypass", i + 1), slot.bypass)?;
+        }
+        // With as many slots as effects, a missing effect means another one is in two slots
+        for kind in EffectKind::ALL {
+            if !chain.slots.iter().any(|slot| slot.kind == kind) {
+                bail!("the effect chain has no slot for {kind}");
+            }
+        }
+        let distortion = DistortionSettings::default();
+        let distortion = DistortionSettings {
+            placement: patch.choice(
+                "distortion.placement",
+                &DistortionPlacement::ALL,
+                distortion.placement,
+            )?,
+            curve: patch.choice("distortion.curve", &DistortionCurve::ALL, distortion.curve)?,
+            drive: patch.value("distortion.drive", distortion.drive)?,
+            bits: patch.value("distortion.bits", distortion.bits)?,
+            downsample: patch.value("distortion.downsample", distortion.downsample)?,
+            mix: patch.value("distortion.mix", distortion.mix)?,
+        };
+        let modulation = ModulationSettings::default();
+        let modulation = ModulationSettings {
+            modulation_type: patch.choice(
+                "modulation.type",
+                &ModulationType::ALL,
+                modulation.modulation_type,
+            )?,
+            rate: patch.value("modulation.rate", modulation.rate)?,
+            depth: patch.value("modulation.depth", modulation.depth)?,
+            feedback: patch.value("modulation.feedback", modulation.feedback)?,
+            mix: patch.value("modulation.mix", modulation.mix)?,
+        };
+        let delay = DelaySettings::default();
+        let delay = DelaySettings {
+            sync: patch.value("delay.sync", delay.sync)?,
+            time: patch.value("delay.time", delay.time)?,
+            division: patch.choice("delay.division", &NoteDivision::ALL, delay.division)?,
+            feedback: patch.value("delay.feedback", delay.feedback)?,
+            high_cut: patch.value("delay.high_cut", delay.high_cut)?,
+            ping_pong: patch.value("delay.ping_pong", delay.ping_pong)?,
+            mix: patch.value("delay.mix", delay.mix)?,
+        };
+        let reverb = ReverbSettings::default();
+        let reverb = ReverbSettings {
+            size: patch.value("reverb.size", reverb.size)?,
+            damping: patch.value("reverb.damping", reverb.damping)?,
+            pre_delay: patch.value("reverb.pre_delay", reverb.pre_delay)?,
+            width: patch.value("reverb.width", reverb.width)?,
+            mix: patch.value("reverb.mix", reverb.mix)?,
+        };
+        let convolution = ConvolutionSettings::default();
+        let convolution = ConvolutionSettings {
+            pre_delay: patch.value("convolution.pre_delay", convolution.pre_delay)?,
+            high_cut: patch.value("convolution.high_cut", convolution.high_cut)?,
+            mix: patch.value("convolution.mix", convolution.mix)?,
+            ..convolution
+        };
+        let mut eq = EqSettings::default();
+        for (i, band) in eq.bands.iter_mut().enumerate() {
+            let key = |name| format!("eq{}.{name}", i + 1);
+            band.frequency = patch.value(&key("frequency"), band.frequency)?;
+            band.gain = patch.value(&key("gain"), band.gain)?;
+            band.q = patch.value(&key("q"), band.q)?;
+        }
+        let compressor = CompressorSettings::default();
+        let compressor = CompressorSettings {
+            threshold: patch.value("compressor.threshold", compressor.threshold)?,
+            ratio: patch.value("compressor.ratio", compressor.ratio)?,
+            attack: patch.value("compressor.attack", compressor.attack)?,
+            release: patch.value("compressor.release", compressor.release)?,
+            makeup: patch.value("compressor.makeup", compressor.makeup)?,
+            knee: patch.value("compressor.knee", compressor.knee)?,
+        };
+
+        Ok(Patch {
+            tempo: patch.value("tempo", 120.0)?,
+            oscillators,
+            cross_mod,
+            routing,
+            filters,
+            lfos,
+            matrix,
+            voicing,
+            arpeggiator,
+            chain,
+            distortion,
+            modulation,
+            delay,
+            reverb,
+            convolution,
+            eq,
+            compressor,
+        })
+    }
+}
+
+struct Writer(String);
+
+impl Writer {
+    fn value(&mut self, key: &str, value: impl Display) {
+        writeln!(self.0, "{key} = {value}").unwrap();
+    }
+
+    fn choice(&mut self, key: &str, value: impl Debug) {
+        writeln!(self.0, "{key} = {value:?}").unwrap();
+    }
+}
+
+struct Reader<'a>(HashMap<&'a str, &'a str>);
+
+impl Reader<'_> {
+    fn value<T: FromStr>(&self, key: &str, default: T) -> anyhow::Result<T> {
+        match self.0.get(key) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| anyhow!("{value} is not a valid {key}")),
+            None => Ok(default),
+        }
+    }
+
+    fn choice<T: Copy + Debug>(&self, key: &str, all: &[T], default: T) -> anyhow::Result<T> {
+        match self.0.get(key) {
+            Some(value) => all
+                .iter()
+                .find(|choice| format!("{choice:?}") == *value)
+                .copied()
+                .ok_or_else(|| anyhow!("{value} is not a valid {key}")),
+            None => Ok(default),
+        }
+    }
+
+    /// Number of entries of a list, which are numbered from 1 without gaps.
+    fn count(&self, prefix: &str) -> usize {
+        (1..)
+            .take_while(|i| {
+                let start = format!("{prefix}{i}.");
+                self.0.keys().any(|key| key.starts_with(&start))
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch() -> Patch {
+        let mut oscillator = Oscillator::new(
+            Waveform::Sample,
+            ADSR(0.01, 0.5, 0.25, 1.5),
+            -3.5,
+            FilterSlot::Filter2,
+        );
+        oscillator.sample_settings.mode = PlayMode::LoopSustain;
+        oscillator.sample_settings.loop_start = 0.125;
+        let mut chain = EffectChain::default();
+        chain.slots.reverse();
+        chain.slots[2].bypass = true;
+
+        Patch {
+            tempo: 96.5,
+            oscillators: vec![Oscillator::default(), oscillator],
+            cross_mod: CrossMod {
+                mode: CrossModulation::Fm,
+                fm_index: 2.5,
+            },
+            routing: Routing::default(),
+            filters: vec![
+                Filter {
+                    filter_type: FilterType::Ladder,
+                    cutoff: 440.0,
+                    ..Filter::default()
+                },
+                Filter::default(),
+            ],
+            lfos: vec![Lfo::default(); 2],
+            matrix: vec![
+                ModSlot {
+                    source: ModSource::ModWheel,
+                    destination: Destination::Cutoff1,
+                    amount: -0.75,
+                },
+                ModSlot::default(),
+            ],
+            voicing: VoicingSettings::default(),
+            arpeggiator: ArpSettings {
+                mode: ArpMode::UpDown,
+                rate: NoteDivision::EighthTriplet,
+                ..ArpSettings::default()
+            },
+            chain,
+            distortion: DistortionSettings::default(),
+            modulation: ModulationSettings::default(),
+            delay: DelaySettings {
+                mix: 0.3,
+                ..DelaySettings::default()
+            },
+            reverb: ReverbSettings::default(),
+            convolution: ConvolutionSettings::default(),
+            eq: EqSettings::default(),
+            compressor: CompressorSettings::default(),
+        }
+    }
+
+    #[test]
+    fn patches_round_trip_through_text() {
+        let text = patch().to_text();
+        let loaded = Patch::parse(&text).unwrap();
+
+        assert_eq!(loaded.to_text(), text);
+        assert_eq!(loaded.oscillators.len(), 2);
+        assert_eq!(loaded.oscillators[1].waveform, Waveform::Sample);
+        assert_eq!(
+            loaded.oscillators[1].sample_settings.mode,
+            PlayMode::LoopSustain
+        );
+        assert_eq!(loaded.filters[0].cutoff, 440.0);
+        assert_eq!(loaded.matrix[0].amount, -0.75);
+        assert_eq!(loaded.delay.mix, 0.3);
+    }
+
+    #[test]
+    fn the_effect_chain_keeps_its_order_and_bypass() {
+        let loaded = Patch::parse(&patch().to_text()).unwrap();
+
+        let kinds: Vec<EffectKind> = loaded.chain.slots.iter().map(|slot| slot.kind).collect();
+        let mut reversed = EffectKind::ALL.to_vec();
+        reversed.reverse();
+        assert_eq!(kinds, reversed);
+        let bypassed: Vec<bool> = loaded.chain.slots.iter().map(|slot| slot.bypass).collect();
+        assert_eq!(bypassed, [false, false, true, false, false, false, false]);
+    }
+
+    #[test]
+    fn missing_settings_keep_their_default() {
+        let loaded = Patch::parse("# Hacksynth patch\ntempo = 90\nfilter1.cutoff = 880\n").unwrap();
+
+        assert_eq!(loaded.tempo, 90.0);
+        assert_eq!(loaded.filters.len(), 1);
+        assert_eq!(loaded.filters[0].cutoff, 880.0);
+        assert_eq!(loaded.filters[0].filter_type, FilterType::default());
+        assert!(loaded.oscillators.is_empty());
+        let kinds: Vec<EffectKind> = loaded.chain.slots.iter().map(|slot| slot.kind).collect();
+        assert_eq!(kinds, EffectKind::ALL);
+    }
+
+    #[test]
+    fn invalid_patches_are_rejected() {
+        assert!(Patch::parse("tempo = fast").is_err());
+        assert!(Patch::parse("osc1.waveform = Noise").is_err());
+        assert!(Patch::parse("not a setting").is_err());
+        // The delay would be in two slots, and the reverb in none
+        assert!(Patch::parse("effect4 = Delay").is_err());
+    }
+}
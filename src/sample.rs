@@ -0,0 +1,311 @@
+use crate::sf2::{chunks, u32_at};
+use fundsp::prelude::midi_hz;
+use hound::{SampleFormat, WavReader};
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Audio loaded from disk, mixed down to mono.
+pub struct Sample {
+    pub name: String,
+    pub data: Vec<f64>,
+    pub sample_rate: f64,
+    /// Start and end frame of the first loop in the sampler chunk of the file, with the end
+    /// frame no longer part of the loop.
+    pub loop_points: Option<(f64, f64)>,
+}
+
+impl Sample {
+    pub fn load(path: &str) -> anyhow::Result<Sample> {
+        let bytes = std::fs::read(path)?;
+        let mut reader = WavReader::new(Cursor::new(&bytes))?;
+        let spec = reader.spec();
+        let interleaved: Vec<f64> = match spec.sample_format {
+            SampleFormat::Float => reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| s as f64))
+                .collect::<Result<_, _>>()?,
+            SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f64;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f64 / scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let channels = spec.channels as usize;
+        let data = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f64>() / channels as f64)
+            .collect();
+
+        Ok(Sample {
+            name: path.rsplit(['/', '\\']).next().unwrap_or(path).to_string(),
+            data,
+            sample_rate: spec.sample_rate as f64,
+            loop_points: sampler_loop(&bytes),
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Linearly interpolated value at a fractional frame position.
+    fn read(&self, position: f64) -> f64 {
+        let index = position as usize;
+        let fract = position - index as f64;
+        let a = self.data.get(index).copied().unwrap_or(0.0);
+        let b = self.data.get(index + 1).copied().unwrap_or(0.0);
+        a + (b - a) * fract
+    }
+}
+
+/// First loop of the `smpl` chunk of a WAV file, which hound does not read.
+fn sampler_loop(bytes: &[u8]) -> Option<(f64, f64)> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let chunks = chunks(&bytes[12..]).ok()?;
+    let (_, smpl) = chunks.iter().find(|(id, _)| id == b"smpl")?;
+    // The loops follow 36 bytes of header, with their count at offset 28
+    if smpl.len() < 36 + 24 || u32_at(smpl, 28) == 0 {
+        return None;
+    }
+    let start = u32_at(smpl, 44) as f64;
+    // The end frame is played as part of the loop
+    let end = u32_at(smpl, 48) as f64 + 1.0;
+    if end > start {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    OneShot,
+    Loop,
+    /// Loops while the key is held, then plays on to the end during the release.
+    LoopSustain,
+}
+
+impl PlayMode {
+    pub const ALL: [PlayMode; 3] = [PlayMode::OneShot, PlayMode::Loop, PlayMode::LoopSustain];
+}
+
+impl Default for PlayMode {
+    fn default() -> PlayMode {
+        PlayMode::OneShot
+    }
+}
+
+impl std::fmt::Display for PlayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PlayMode::OneShot => "One Shot",
+                PlayMode::Loop => "Loop",
+                PlayMode::LoopSustain => "Loop Sustain",
+            }
+        )
+    }
+}
+
+/// Playback region of a sample, with positions given as fractions of its length.
+#[derive(Clone)]
+pub struct SampleSettings {
+    pub root: u8,
+    pub start: f64,
+    pub end: f64,
+    pub loop_start: f64,
+    pub loop_end: f64,
+    /// Crossfade at the loop seam in seconds.
+    pub crossfade: f64,
+    pub mode: PlayMode,
+}
+
+impl Default for SampleSettings {
+    fn default() -> SampleSettings {
+        SampleSettings {
+            root: 60,
+            start: 0.0,
+            end: 1.0,
+            loop_start: 0.0,
+            loop_end: 1.0,
+            crossfade: 0.0,
+            mode: PlayMode::OneShot,
+        }
+    }
+}
+
+/// Plays back a region of a `Sample`, pitched relative to its root key.
+pub struct SamplePlayer {
+    sample: Arc<Sample>,
+    root_hz: f64,
+    start: f64,
+    end: f64,
+    loop_start: f64,
+    loop_end: f64,
+    crossfade: f64,
+    mode: PlayMode,
+    gain: f64,
+    position: f64,
+    sample_rate: f64,
+    released: bool,
+}
+
+impl SamplePlayer {
+    pub fn new(sample: Arc<Sample>, settings: &SampleSettings) -> SamplePlayer {
+        let length = sample.frames() as f64;
+        let start = (settings.start * length).floor();
+        let end = (settings.end * length).floor().max(start);
+        let loop_start = (settings.loop_start * length).floor().clamp(start, end);
+        let loop_end = (settings.loop_end * length).floor().clamp(loop_start, end);
+        let crossfade = settings.crossfade * sample.sample_rate;
+
+        SamplePlayer::with_frames(
+            sample,
+            settings.root as f64,
+            start,
+            end,
+            loop_start,
+            loop_end,
+            crossfade,
+            settings.mode,
+        )
+    }
+
+    /// Creates a player from positions in frames rather than fractions.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_frames(
+        sample: Arc<Sample>,
+        root: f64,
+        start: f64,
+        end: f64,
+        loop_start: f64,
+        loop_end: f64,
+        crossfade: f64,
+        mode: PlayMode,
+    ) -> SamplePlayer {
+        // The crossfade reads audio from before the loop start, so it can not be longer than that
+        let crossfade = crossfade
+            .min(loop_start)
+            .min(loop_end - loop_start)
+            .max(0.0);
+        let sample_rate = sample.sample_rate;
+
+        SamplePlayer {
+            sample,
+            root_hz: midi_hz(root),
+            start,
+            end,
+            loop_start,
+            loop_end,
+            crossfade,
+            mode,
+            gain: 1.0,
+            position: start,
+            sample_rate,
+            released: false,
+        }
+    }
+
+    pub fn with_gain(mut self, gain: f64) -> SamplePlayer {
+        self.gain = gain;
+        self
+    }
+
+    /// Restarts playback from the start point.
+    pub fn reset(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.position = self.start;
+        self.released = false;
+    }
+
+    /// Jumps back to the start point for hard sync, staying out of a sustain loop that the
+    /// key has already ended.
+    pub fn restart(&mut self) {
+        self.position = self.start;
+    }
+
+    /// Lets go of the key, which ends a sustain loop.
+    pub fn release(&mut self) {
+        self.released = true;
+    }
+
+    pub fn tick(&mut self, frequency: f64) -> f64 {
+        let looping = match self.mode {
+            PlayMode::OneShot => false,
+            PlayMode::Loop => true,
+            PlayMode::LoopSustain => !self.released,
+        } && self.loop_end > self.loop_start;
+        if !looping && self.position >= self.end {
+            return 0.0;
+        }
+
+        let mut value = self.sample.read(self.position);
+        let fade_start = self.loop_end - self.crossfade;
+        if looping && self.crossfade > 0.0 && self.position >= fade_start {
+            let fade = (self.position - fade_start) / self.crossfade;
+            let wrapped = self.position - (self.loop_end - self.loop_start);
+            value = value * (1.0 - fade) + self.sample.read(wrapped) * fade;
+        }
+
+        self.position +=
+            frequency.abs() / self.root_hz * self.sample.sample_rate / self.sample_rate;
+        if looping && self.position >= self.loop_end {
+            self.position -= self.loop_end - self.loop_start;
+        }
+
+        value * self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut body = b"WAVE".to_vec();
+        for (id, data) in chunks {
+            body.extend_from_slice(*id);
+            body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            body.extend_from_slice(data);
+        }
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    fn smpl(loops: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = vec![0; 28];
+        data.extend_from_slice(&(loops.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        for (start, end) in loops {
+            for value in [0, 0, *start, *end, 0, 0] {
+                data.extend_from_slice(&u32::to_le_bytes(value));
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn reads_the_first_loop_of_the_sampler_chunk() {
+        let bytes = wave(&[
+            (b"fmt ", vec![0; 16]),
+            (b"smpl", smpl(&[(100, 899), (0, 10)])),
+        ]);
+        assert_eq!(sampler_loop(&bytes), Some((100.0, 900.0)));
+    }
+
+    #[test]
+    fn files_without_loops_have_none() {
+        assert_eq!(sampler_loop(&wave(&[(b"fmt ", vec![0; 16])])), None);
+        assert_eq!(sampler_loop(&wave(&[(b"smpl", smpl(&[]))])), None);
+        assert_eq!(sampler_loop(b"RIFF"), None);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::tempo::NoteDivision;
+use std::time::{Duration, Instant};
+
+pub const MAX_STEPS: usize = 64;
+pub const STEP_COUNTS: [usize; 4] = [16, 32, 48, 64];
+pub const LOCK_PARAMETERS: usize = 10;
+
+/// Parameter of a voice that a step can set to a value of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockParameter {
+    Cutoff1,
+    Cutoff2,
+    Resonance1,
+    Resonance2,
+    Drive1,
+    Drive2,
+    EnvelopeAmount1,
+    EnvelopeAmount2,
+    FilterBalance,
+    FmIndex,
+}
+
+impl LockParameter {
+    pub const ALL: [LockParameter; LOCK_PARAMETERS] = [
+        LockParameter::Cutoff1,
+        LockParameter::Cutoff2,
+        LockParameter::Resonance1,
+        LockParameter::Resonance2,
+        LockParameter::Drive1,
+        LockParameter::Drive2,
+        LockParameter::EnvelopeAmount1,
+        LockParameter::EnvelopeAmount2,
+        LockParameter::FilterBalance,
+        LockParameter::FmIndex,
+    ];
+
+    /// Value for a knob position from 0 to 1, in the units of the parameter.
+    pub fn value(&self, normal: f64) -> f64 {
+        match self {
+            LockParameter::Cutoff1 | LockParameter::Cutoff2 => 20.0 * 1000.0f64.powf(normal),
+            LockParameter::Resonance1 | LockParameter::Resonance2 => normal * 100.0,
+            LockParameter::Drive1 | LockParameter::Drive2 | LockParameter::FilterBalance => normal,
+            LockParameter::EnvelopeAmount1 | LockParameter::EnvelopeAmount2 => normal * 10.0 - 5.0,
+            LockParameter::FmIndex => normal * 10.0,
+        }
+    }
+
+    /// Knob position from 0 to 1 for a value of the parameter.
+    pub fn normal(&self, value: f64) -> f64 {
+        let normal = match self {
+            LockParameter::Cutoff1 | LockParameter::Cutoff2 => (value / 20.0).log(1000.0),
+            LockParameter::Resonance1 | LockParameter::Resonance2 => value / 100.0,
+            LockParameter::Drive1 | LockParameter::Drive2 | LockParameter::FilterBalance => value,
+            LockParameter::EnvelopeAmount1 | LockParameter::EnvelopeAmount2 => (value + 5.0) / 10.0,
+            LockParameter::FmIndex => value / 10.0,
+        };
+        normal.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for LockParameter {
+    fn default() -> LockParameter {
+        LockParameter::Cutoff1
+    }
+}
+
+impl std::fmt::Display for LockParameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LockParameter::Cutoff1 => "F1 Cutoff",
+                LockParameter::Cutoff2 => "F2 Cutoff",
+                LockParameter::Resonance1 => "F1 Resonance",
+                LockParameter::Resonance2 => "F2 Resonance",
+                LockParameter::Drive1 => "F1 Drive",
+                LockParameter::Drive2 => "F2 Drive",
+                LockParameter::EnvelopeAmount1 => "F1 Env Amount",
+                LockParameter::EnvelopeAmount2 => "F2 Env Amount",
+                LockParameter::FilterBalance => "Filter Balance",
+                LockParameter::FmIndex => "FM Index",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    pub active: bool,
+    pub note: u8,
+    pub velocity: u8,
+    /// Share of the step that the note is held for.
+    pub gate: f64,
+    /// Hold the note of the previous step through this one instead of starting a new one.
+    pub tie: bool,
+    /// Value each parameter is locked to on this step, indexed by `LockParameter`, in place
+    /// of the value of the patch.
+    pub locks: [Option<f64>; LOCK_PARAMETERS],
+}
+
+impl Default for Step {
+    fn default() -> Step {
+        Step {
+            active: false,
+            note: 48,
+            velocity: 100,
+            gate: 0.5,
+            tie: false,
+            locks: [None; LOCK_PARAMETERS],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SequencerSettings {
+    pub playing: bool,
+    /// Number of steps that are played, from 16 to `MAX_STEPS`.
+    pub length: usize,
+    pub rate: NoteDivision,
+    pub steps: [Step; MAX_STEPS],
+}
+
+impl Default for SequencerSettings {
+    fn default() -> SequencerSettings {
+        SequencerSettings {
+            playing: false,
+            length: 16,
+            rate: NoteDivision::Sixteenth,
+            steps: [Step::default(); MAX_STEPS],
+        }
+    }
+}
+
+pub enum SequencerEvent {
+    NoteOn {
+        note: u8,
+        velocity: u8,
+        locks: [Option<f64>; LOCK_PARAMETERS],
+    },
+    /// A tied step sets the parameters it locks on the note that keeps playing through it.
+    Locks {
+        locks: [Option<f64>; LOCK_PARAMETERS],
+    },
+    NoteOff {
+        note: u8,
+    },
+}
+
+/// Plays the steps from an internal clock that follows the tempo.
+#[derive(Default)]
+pub struct Sequencer {
+    position: usize,
+    /// Start of the next step.
+    next_step: Option<Instant>,
+    /// Note that is playing and when it ends.
+    sounding: Option<(u8, Instant)>,
+}
+
+impl Sequencer {
+    /// Advances the sequence to `now`, returning the notes to start and stop.
+    pub fn tick(
+        &mut self,
+        settings: &SequencerSettings,
+        tempo: f64,
+        now: Instant,
+    ) -> Vec<SequencerEvent> {
+        let mut events = Vec::new();
+        if !settings.playing {
+            if let Some((note, _)) = self.sounding.take() {
+                events.push(SequencerEvent::NoteOff { note });
+            }
+            self.position = 0;
+            self.next_step = None;
+            return events;
+        }
+
+        let duration = Duration::from_secs_f64(settings.rate.seconds(tempo.max(1.0)));
+        // The first step plays as soon as the sequence starts. A clock that has fallen more
+        // than a step behind restarts from now, so missed steps are skipped
+        let start = match self.next_step {
+            Some(next_step) if now < next_step + duration => next_step,
+            _ => now,
+        };
+        if now >= start {
+            let length = settings.length.clamp(1, MAX_STEPS);
+            let position = self.position % length;
+            let step = &settings.steps[position];
+            let next = &settings.steps[(position + 1) % length];
+            // A note runs into the next step when that one is tied to it
+            let end = if next.active && next.tie {
+                start + duration
+            } else {
+                start + duration.mul_f64(step.gate.clamp(0.05, 1.0))
+            };
+
+            if step.active {
+                match self.sounding.as_mut() {
+                    Some((_, sounding_end)) if step.tie => {
+                        *sounding_end = end;
+                        if step.locks.iter().any(Option::is_some) {
+                            events.push(SequencerEvent::Locks { locks: step.locks });
+                        }
+                    }
+                    _ => {
+                        if let Some((note, _)) = self.sounding.take() {
+                            events.push(SequencerEvent::NoteOff { note });
+                        }
+                        events.push(SequencerEvent::NoteOn {
+                            note: step.note,
+                            velocity: step.velocity,
+                            locks: step.locks,
+                        });
+                        self.sounding = Some((step.note, end));
+                    }
+                }
+            }
+            self.position = (position + 1) % length;
+            self.next_step = Some(start + duration);
+        } else {
+            self.next_step = Some(start);
+        }
+
+        if let Some((note, end)) = self.sounding {
+            if end <= now {
+                events.push(SequencerEvent::NoteOff { note });
+                self.sounding = None;
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Length of a sixteenth at the tempo of the tests.
+    const STEP: Duration = Duration::from_millis(125);
+    const TEMPO: f64 = 120.0;
+
+    fn step(note: u8) -> Step {
+        Step {
+            active: true,
+            note,
+            ..Step::default()
+        }
+    }
+
+    fn playing(steps: &[(usize, Step)]) -> SequencerSettings {
+        let mut settings = SequencerSettings {
+            playing: true,
+            ..SequencerSettings::default()
+        };
+        for (position, step) in steps {
+            settings.steps[*position] = *step;
+        }
+        settings
+    }
+
+    /// Events of each of the first `steps` steps, ticking once at the start of each.
+    fn play(settings: &SequencerSettings, steps: u32) -> Vec<Vec<SequencerEvent>> {
+        let mut sequencer = Sequencer::default();
+        let start = Instant::now();
+        (0..steps)
+            .map(|step| sequencer.tick(settings, TEMPO, start + STEP * step))
+            .collect()
+    }
+
+    fn started(events: &[SequencerEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                SequencerEvent::NoteOn { note, .. } => Some(*note),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn stopped(events: &[SequencerEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                SequencerEvent::NoteOff { note } => Some(*note),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tied_steps_hold_the_previous_note() {
+        let tied = Step {
+            tie: true,
+            ..step(60)
+        };
+        let settings = playing(&[(0, step(48)), (1, tied), (2, step(50))]);
+        let steps = play(&settings, 3);
+
+        assert_eq!(started(&steps[0]), vec![48]);
+        // The note of the tied step is not played, and the note before it is not stopped
+        assert!(started(&steps[1]).is_empty());
+        assert!(stopped(&steps[1]).is_empty());
+        assert_eq!(stopped(&steps[2]), vec![48]);
+        assert_eq!(started(&steps[2]), vec![50]);
+    }
+
+    #[test]
+    fn tied_steps_lock_parameters_on_the_held_note() {
+        let mut tied = Step {
+            tie: true,
+            ..step(48)
+        };
+        tied.locks[LockParameter::Cutoff1 as usize] = Some(500.0);
+        let settings = playing(&[(0, step(48)), (1, tied)]);
+        let steps = play(&settings, 2);
+
+        let locks: Vec<[Option<f64>; LOCK_PARAMETERS]> = steps[1]
+            .iter()
+            .filter_map(|event| match event {
+                SequencerEvent::Locks { locks } => Some(*locks),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(locks.len(), 1);
+        assert_eq!(locks[0][LockParameter::Cutoff1 as usize], Some(500.0));
+        assert_eq!(locks[0][LockParameter::Cutoff2 as usize], None);
+    }
+
+    #[test]
+    fn steps_wrap_at_the_step_count() {
+        let settings = SequencerSettings {
+            length: 16,
+            ..playing(&[(0, step(48)), (15, step(55)), (16, step(60))])
+        };
+        let steps = play(&settings, 17);
+
+        assert_eq!(started(&steps[15]), vec![55]);
+        // Step 17 is past the step count, so the sequence starts over instead
+        assert_eq!(started(&steps[16]), vec![48]);
+    }
+
+    #[test]
+    fn lock_values_round_trip_through_knob_positions() {
+        for parameter in LockParameter::ALL {
+            for normal in [0.0, 0.25, 0.5, 1.0] {
+                let value = parameter.value(normal);
+                assert!(
+                    (parameter.normal(value) - normal).abs() < 1e-9,
+                    "{parameter} at {normal}"
+                );
+            }
+        }
+        assert!((LockParameter::Cutoff1.value(1.0) - 20000.0).abs() < 1e-6);
+        assert_eq!(LockParameter::EnvelopeAmount1.value(0.5), 0.0);
+    }
+}
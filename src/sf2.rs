@@ -0,0 +1,581 @@
+use crate::sample::{PlayMode, Sample, SamplePlayer};
+use anyhow::{anyhow, bail};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Generator operators, see the SoundFont 2.04 specification section 8.1.2
+const START_OFFSET: u16 = 0;
+const END_OFFSET: u16 = 1;
+const LOOP_START_OFFSET: u16 = 2;
+const LOOP_END_OFFSET: u16 = 3;
+const START_COARSE_OFFSET: u16 = 4;
+const END_COARSE_OFFSET: u16 = 12;
+const INSTRUMENT: u16 = 41;
+const KEY_RANGE: u16 = 43;
+const VELOCITY_RANGE: u16 = 44;
+const LOOP_START_COARSE_OFFSET: u16 = 45;
+const ATTENUATION: u16 = 48;
+const LOOP_END_COARSE_OFFSET: u16 = 50;
+const COARSE_TUNE: u16 = 51;
+const FINE_TUNE: u16 = 52;
+const SAMPLE_ID: u16 = 53;
+const SAMPLE_MODES: u16 = 54;
+const ROOT_KEY: u16 = 58;
+
+/// Bank, program and name of a preset, as listed to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetInfo {
+    pub bank: u16,
+    pub program: u16,
+    pub name: String,
+}
+
+impl std::fmt::Display for PresetInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:03}:{:03} {}", self.bank, self.program, self.name)
+    }
+}
+
+#[derive(Default)]
+struct Zone {
+    generators: Vec<(u16, u16)>,
+}
+
+impl Zone {
+    fn get(&self, operator: u16) -> Option<u16> {
+        self.generators
+            .iter()
+            .find(|(op, _)| *op == operator)
+            .map(|(_, amount)| *amount)
+    }
+
+    fn signed(&self, operator: u16) -> Option<i64> {
+        self.get(operator).map(|amount| amount as i16 as i64)
+    }
+
+    fn contains(&self, operator: u16, value: u8) -> bool {
+        match self.get(operator) {
+            Some(range) => ((range & 0xff) as u8..=(range >> 8) as u8).contains(&value),
+            None => true,
+        }
+    }
+}
+
+/// Zones of a preset or an instrument, with the optional global zone split off.
+struct Zones {
+    global: Zone,
+    zones: Vec<Zone>,
+}
+
+impl Zones {
+    fn new(mut zones: Vec<Zone>, terminal: u16) -> Zones {
+        let global = match zones.first() {
+            Some(first) if first.get(terminal).is_none() => zones.remove(0),
+            _ => Zone::default(),
+        };
+        Zones { global, zones }
+    }
+
+    fn matching(&self, note: u8, velocity: u8) -> impl Iterator<Item = (&Zone, &Zone)> {
+        self.zones
+            .iter()
+            .filter(move |z| {
+                let z: &Zone = z;
+                let key = if z.get(KEY_RANGE).is_some() {
+                    z
+                } else {
+                    &self.global
+                };
+                let vel = if z.get(VELOCITY_RANGE).is_some() {
+                    z
+                } else {
+                    &self.global
+                };
+                key.contains(KEY_RANGE, note) && vel.contains(VELOCITY_RANGE, velocity)
+            })
+            .map(move |z| (z, &self.global))
+    }
+}
+
+struct SampleHeader {
+    sample: Arc<Sample>,
+    loop_start: i64,
+    loop_end: i64,
+    original_pitch: u8,
+    pitch_correction: i8,
+}
+
+/// How an instrument zone plays its sample, with the generators of the preset zone it was
+/// reached through applied.
+#[derive(Debug, PartialEq)]
+struct ZoneSettings {
+    /// Key the sample plays back unaltered at, including the tuning.
+    root: f64,
+    /// Sample addresses in frames.
+    start: i64,
+    end: i64,
+    loop_start: i64,
+    loop_end: i64,
+    mode: PlayMode,
+    /// Attenuation in centibels.
+    attenuation: i64,
+}
+
+impl ZoneSettings {
+    /// Settings from an instrument zone and a preset zone, each with their global zone.
+    fn new(
+        header: &SampleHeader,
+        instrument: (&Zone, &Zone),
+        preset: (&Zone, &Zone),
+    ) -> ZoneSettings {
+        let (zone, global) = instrument;
+        let value = |op: u16| zone.signed(op).or_else(|| global.signed(op)).unwrap_or(0);
+        // Preset generators offset the values of the instrument, except for the sample
+        // addresses, which are only valid in instruments
+        let preset_offset = |op: u16| {
+            preset
+                .0
+                .signed(op)
+                .or_else(|| preset.1.signed(op))
+                .unwrap_or(0)
+        };
+        let address = |fine: u16, coarse: u16| value(fine) + value(coarse) * 32768;
+
+        let frames = header.sample.frames() as i64;
+        let start = address(START_OFFSET, START_COARSE_OFFSET).clamp(0, frames);
+        let end = (frames + address(END_OFFSET, END_COARSE_OFFSET)).clamp(start, frames);
+        let loop_start = (header.loop_start + address(LOOP_START_OFFSET, LOOP_START_COARSE_OFFSET))
+            .clamp(start, end);
+        let loop_end = (header.loop_end + address(LOOP_END_OFFSET, LOOP_END_COARSE_OFFSET))
+            .clamp(loop_start, end);
+
+        let root_key = zone
+            .signed(ROOT_KEY)
+            .or_else(|| global.signed(ROOT_KEY))
+            .filter(|key| (0..=127).contains(key))
+            .unwrap_or(header.original_pitch as i64);
+        let tune = (value(COARSE_TUNE) + preset_offset(COARSE_TUNE)) as f64
+            + (value(FINE_TUNE) + preset_offset(FINE_TUNE)) as f64 / 100.0
+            + header.pitch_correction as f64 / 100.0;
+        let mode = match value(SAMPLE_MODES) & 3 {
+            1 => PlayMode::Loop,
+            3 => PlayMode::LoopSustain,
+            _ => PlayMode::OneShot,
+        };
+
+        ZoneSettings {
+            root: root_key as f64 - tune,
+            start,
+            end,
+            loop_start,
+            loop_end,
+            mode,
+            attenuation: (value(ATTENUATION) + preset_offset(ATTENUATION)).max(0),
+        }
+    }
+}
+
+/// A SoundFont 2 bank whose presets can be played as an oscillator source.
+pub struct SoundFont {
+    pub name: String,
+    pub presets: Vec<PresetInfo>,
+    preset_zones: Vec<Zones>,
+    instruments: Vec<Zones>,
+    samples: Vec<SampleHeader>,
+}
+
+impl SoundFont {
+    pub fn load(path: &str) -> anyhow::Result<SoundFont> {
+        let bytes = std::fs::read(path)?;
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"sfbk" {
+            bail!("not a SoundFont 2 file");
+        }
+
+        let mut smpl: &[u8] = &[];
+        let mut pdta: HashMap<[u8; 4], &[u8]> = HashMap::new();
+        for (id, body) in chunks(&bytes[12..])? {
+            if &id != b"LIST" || body.len() < 4 {
+                continue;
+            }
+            for (id, sub) in chunks(&body[4..])? {
+                match &body[0..4] {
+                    b"sdta" if &id == b"smpl" => smpl = sub,
+                    b"pdta" => {
+                        pdta.insert(id, sub);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let chunk = |id: &[u8; 4]| {
+            pdta.get(id)
+                .copied()
+                .ok_or_else(|| anyhow!("missing {} chunk", String::from_utf8_lossy(id)))
+        };
+
+        let phdr = records(chunk(b"phdr")?, 38);
+        let pbag = records(chunk(b"pbag")?, 4);
+        let pgen = records(chunk(b"pgen")?, 4);
+        let inst = records(chunk(b"inst")?, 22);
+        let ibag = records(chunk(b"ibag")?, 4);
+        let igen = records(chunk(b"igen")?, 4);
+        let shdr = records(chunk(b"shdr")?, 46);
+
+        let zones = |bags: &[&[u8]], gens: &[&[u8]], first: usize, last: usize| -> Vec<Zone> {
+            (first..last.min(bags.len().saturating_sub(1)))
+                .map(|bag| {
+                    let from = u16_at(bags[bag], 0) as usize;
+                    let to = (u16_at(bags[bag + 1], 0) as usize).min(gens.len());
+                    Zone {
+                        generators: (from..to.max(from))
+                            .map(|g| (u16_at(gens[g], 0), u16_at(gens[g], 2)))
+                            .collect(),
+                    }
+                })
+                .collect()
+        };
+
+        // The last header of each list only terminates it
+        let mut presets = Vec::new();
+        let mut preset_zones = Vec::new();
+        for pair in phdr.windows(2) {
+            presets.push(PresetInfo {
+                name: name(pair[0]),
+                program: u16_at(pair[0], 20),
+                bank: u16_at(pair[0], 22),
+            });
+            preset_zones.push(Zones::new(
+                zones(
+                    &pbag,
+                    &pgen,
+                    u16_at(pair[0], 24) as usize,
+                    u16_at(pair[1], 24) as usize,
+                ),
+                INSTRUMENT,
+            ));
+        }
+
+        let instruments = inst
+            .windows(2)
+            .map(|pair| {
+                Zones::new(
+                    zones(
+                        &ibag,
+                        &igen,
+                        u16_at(pair[0], 20) as usize,
+                        u16_at(pair[1], 20) as usize,
+                    ),
+                    SAMPLE_ID,
+                )
+            })
+            .collect();
+
+        let samples = shdr
+            .iter()
+            .take(shdr.len().saturating_sub(1))
+            .map(|header| {
+                let start = u32_at(header, 20) as usize;
+                let end = (u32_at(header, 24) as usize).min(smpl.len() / 2).max(start);
+                let data = (start..end)
+                    .map(|i| i16::from_le_bytes([smpl[i * 2], smpl[i * 2 + 1]]) as f64 / 32768.0)
+                    .collect();
+                SampleHeader {
+                    sample: Arc::new(Sample {
+                        name: name(header),
+                        data,
+                        sample_rate: u32_at(header, 36) as f64,
+                        loop_points: None,
+                    }),
+                    loop_start: u32_at(header, 28) as i64 - start as i64,
+                    loop_end: u32_at(header, 32) as i64 - start as i64,
+                    original_pitch: header[40],
+                    pitch_correction: header[41] as i8,
+                }
+            })
+            .collect();
+
+        let mut presets_sorted: Vec<(PresetInfo, Zones)> =
+            presets.into_iter().zip(preset_zones).collect();
+        presets_sorted.sort_by_key(|(info, _)| (info.bank, info.program));
+        let (presets, preset_zones): (Vec<PresetInfo>, Vec<Zones>) =
+            presets_sorted.into_iter().unzip();
+
+        Ok(SoundFont {
+            name: path.rsplit(['/', '\\']).next().unwrap_or(path).to_string(),
+            presets,
+            preset_zones,
+            instruments,
+            samples,
+        })
+    }
+
+    pub fn preset_index(&self, preset: &PresetInfo) -> Option<usize> {
+        self.presets
+            .iter()
+            .position(|p| p.bank == preset.bank && p.program == preset.program)
+    }
+
+    /// Players for every sample a note of the preset triggers.
+    pub fn players(&self, preset: usize, note: u8, velocity: u8) -> Vec<SamplePlayer> {
+        let mut players = Vec::new();
+        let preset_zones = match self.preset_zones.get(preset) {
+            Some(zones) => zones,
+            None => return players,
+        };
+
+        for (preset_zone, preset_global) in preset_zones.matching(note, velocity) {
+            let instrument = match preset_zone
+                .get(INSTRUMENT)
+                .and_then(|i| self.instruments.get(i as usize))
+            {
+                Some(instrument) => instrument,
+                None => continue,
+            };
+            for (zone, global) in instrument.matching(note, velocity) {
+                let header = match zone
+                    .get(SAMPLE_ID)
+                    .and_then(|s| self.samples.get(s as usize))
+                {
+                    Some(header) => header,
+                    None => continue,
+                };
+                let settings =
+                    ZoneSettings::new(header, (zone, global), (preset_zone, preset_global));
+                players.push(
+                    SamplePlayer::with_frames(
+                        header.sample.clone(),
+                        settings.root,
+                        settings.start as f64,
+                        settings.end as f64,
+                        settings.loop_start as f64,
+                        settings.loop_end as f64,
+                        0.0,
+                        settings.mode,
+                    )
+                    .with_gain(10.0_f64.powf(-settings.attenuation as f64 / 200.0)),
+                );
+            }
+        }
+
+        players
+    }
+}
+
+/// Splits RIFF data into its chunk ids and bodies.
+pub fn chunks(mut data: &[u8]) -> anyhow::Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    while data.len() >= 8 {
+        let id = [data[0], data[1], data[2], data[3]];
+        let size = u32_at(data, 4) as usize;
+        if data.len() < 8 + size {
+            bail!("truncated {} chunk", String::from_utf8_lossy(&id));
+        }
+        chunks.push((id, &data[8..8 + size]));
+        // Chunks are padded to an even size
+        data = &data[(8 + size + (size & 1)).min(data.len())..];
+    }
+    Ok(chunks)
+}
+
+fn records(data: &[u8], size: usize) -> Vec<&[u8]> {
+    data.chunks_exact(size).collect()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+pub fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn name(record: &[u8]) -> String {
+    let name = &record[0..20];
+    let length = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..length]).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(pitch_correction: i8) -> SampleHeader {
+        SampleHeader {
+            sample: Arc::new(Sample {
+                name: "test".to_string(),
+                data: vec![0.0; 100_000],
+                sample_rate: 44100.0,
+                loop_points: None,
+            }),
+            loop_start: 1000,
+            loop_end: 50_000,
+            original_pitch: 60,
+            pitch_correction,
+        }
+    }
+
+    fn zone(generators: &[(u16, i16)]) -> Zone {
+        Zone {
+            generators: generators
+                .iter()
+                .map(|(op, amount)| (*op, *amount as u16))
+                .collect(),
+        }
+    }
+
+    /// Generator amount of a key or velocity range.
+    fn range(low: u8, high: u8) -> i16 {
+        (low as u16 | ((high as u16) << 8)) as i16
+    }
+
+    #[test]
+    fn sample_addresses_come_from_the_instrument_only() {
+        let instrument = zone(&[
+            (START_OFFSET, 10),
+            (START_COARSE_OFFSET, 1),
+            (END_OFFSET, -100),
+            (LOOP_START_COARSE_OFFSET, 1),
+            (LOOP_END_OFFSET, -5),
+        ]);
+        let preset = zone(&[(START_OFFSET, 500), (END_COARSE_OFFSET, -1)]);
+        let settings = ZoneSettings::new(
+            &header(0),
+            (&instrument, &Zone::default()),
+            (&preset, &Zone::default()),
+        );
+        assert_eq!(settings.start, 32_778);
+        assert_eq!(settings.end, 99_900);
+        assert_eq!(settings.loop_start, 33_768);
+        assert_eq!(settings.loop_end, 49_995);
+    }
+
+    #[test]
+    fn addresses_stay_inside_the_sample() {
+        let instrument = zone(&[
+            (START_OFFSET, -10),
+            (END_OFFSET, 100),
+            (LOOP_END_OFFSET, 32_000),
+        ]);
+        let settings = ZoneSettings::new(
+            &header(0),
+            (&instrument, &Zone::default()),
+            (&Zone::default(), &Zone::default()),
+        );
+        assert_eq!(settings.start, 0);
+        assert_eq!(settings.end, 100_000);
+        assert_eq!(settings.loop_end, 82_000);
+    }
+
+    #[test]
+    fn preset_generators_offset_tuning_and_attenuation() {
+        let instrument = zone(&[(FINE_TUNE, -50), (ROOT_KEY, 64), (ATTENUATION, 60)]);
+        let instrument_global = zone(&[(COARSE_TUNE, 2), (ATTENUATION, 10)]);
+        let preset = zone(&[(ATTENUATION, 30)]);
+        let preset_global = zone(&[(COARSE_TUNE, 1), (ATTENUATION, 100)]);
+        let settings = ZoneSettings::new(
+            &header(10),
+            (&instrument, &instrument_global),
+            (&preset, &preset_global),
+        );
+        // 3 semitones coarse, -50 cents fine and 10 cents of pitch correction below key 64
+        assert!((settings.root - 61.4).abs() < 1e-9);
+        // Zones override their global zone before the preset is added to the instrument
+        assert_eq!(settings.attenuation, 90);
+    }
+
+    #[test]
+    fn sample_modes_pick_the_play_mode() {
+        let mode = |modes: i16| {
+            ZoneSettings::new(
+                &header(0),
+                (&zone(&[(SAMPLE_MODES, modes)]), &Zone::default()),
+                (&Zone::default(), &Zone::default()),
+            )
+            .mode
+        };
+        assert_eq!(mode(0), PlayMode::OneShot);
+        assert_eq!(mode(1), PlayMode::Loop);
+        assert_eq!(mode(2), PlayMode::OneShot);
+        assert_eq!(mode(3), PlayMode::LoopSustain);
+    }
+
+    #[test]
+    fn zones_match_key_and_velocity_ranges() {
+        let zones = Zones::new(
+            vec![
+                zone(&[(VELOCITY_RANGE, range(64, 127))]),
+                zone(&[(KEY_RANGE, range(60, 64)), (SAMPLE_ID, 0)]),
+                zone(&[
+                    (KEY_RANGE, range(65, 70)),
+                    (VELOCITY_RANGE, range(0, 127)),
+                    (SAMPLE_ID, 1),
+                ]),
+            ],
+            SAMPLE_ID,
+        );
+        assert_eq!(zones.zones.len(), 2);
+
+        let samples = |note, velocity| -> Vec<u16> {
+            zones
+                .matching(note, velocity)
+                .filter_map(|(zone, _)| zone.get(SAMPLE_ID))
+                .collect()
+        };
+        assert_eq!(samples(62, 100), vec![0]);
+        // The global velocity range applies where a zone has none of its own
+        assert_eq!(samples(62, 10), Vec::<u16>::new());
+        assert_eq!(samples(66, 10), vec![1]);
+        assert_eq!(samples(80, 100), Vec::<u16>::new());
+    }
+
+    #[test]
+    fn zones_without_a_global_zone_keep_every_zone() {
+        let zones = Zones::new(vec![zone(&[(INSTRUMENT, 0)])], INSTRUMENT);
+        assert_eq!(zones.zones.len(), 1);
+        assert!(zones.global.generators.is_empty());
+    }
+
+    #[test]
+    fn splits_padded_chunks() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"abcd");
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(b"xyz\0");
+        data.extend_from_slice(b"efgh");
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(b"hi");
+
+        let chunks = chunks(&data).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].0, b"abcd");
+        assert_eq!(chunks[0].1, b"xyz");
+        assert_eq!(&chunks[1].0, b"efgh");
+        assert_eq!(chunks[1].1, b"hi");
+    }
+
+    #[test]
+    fn rejects_truncated_chunks() {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"abcd");
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(b"xy");
+        assert!(chunks(&data).is_err());
+    }
+
+    #[test]
+    fn reads_records() {
+        let mut record = [0u8; 38];
+        record[..5].copy_from_slice(b"Piano");
+        record[20..22].copy_from_slice(&5u16.to_le_bytes());
+        record[22..24].copy_from_slice(&128u16.to_le_bytes());
+        assert_eq!(name(&record), "Piano");
+        assert_eq!(u16_at(&record, 20), 5);
+        assert_eq!(u16_at(&record, 22), 128);
+        assert_eq!(records(&[0u8; 10], 4).len(), 2);
+    }
+}
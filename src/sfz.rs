@@ -0,0 +1,457 @@
+use crate::sample::{PlayMode, Sample, SamplePlayer};
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A key and velocity zone of an SFZ instrument.
+pub struct SfzRegion {
+    pub sample: Arc<Sample>,
+    pub lokey: u8,
+    pub hikey: u8,
+    pub lovel: u8,
+    pub hivel: u8,
+    /// Index of the header the region was defined under, whose regions share a round robin.
+    pub group: usize,
+    /// Key the sample plays back unaltered at, including `transpose` and `tune`.
+    pub root: f64,
+    pub seq_length: usize,
+    pub seq_position: usize,
+    pub mode: PlayMode,
+    pub offset: f64,
+    pub end: f64,
+    pub loop_start: f64,
+    pub loop_end: f64,
+    pub gain: f64,
+}
+
+impl SfzRegion {
+    fn matches(&self, note: u8, velocity: u8) -> bool {
+        (self.lokey..=self.hikey).contains(&note) && (self.lovel..=self.hivel).contains(&velocity)
+    }
+
+    fn in_sequence(&self, round_robin: usize) -> bool {
+        round_robin % self.seq_length + 1 == self.seq_position
+    }
+
+    fn player(&self) -> SamplePlayer {
+        SamplePlayer::with_frames(
+            self.sample.clone(),
+            self.root,
+            self.offset,
+            self.end,
+            self.loop_start,
+            self.loop_end,
+            0.0,
+            self.mode,
+        )
+        .with_gain(self.gain)
+    }
+}
+
+/// Multi-sample instrument loaded from an SFZ file.
+pub struct SfzInstrument {
+    pub name: String,
+    pub regions: Vec<SfzRegion>,
+    /// Notes played so far of each group and key, which pick the round robin sample.
+    round_robin: Mutex<HashMap<(usize, u8), usize>>,
+}
+
+impl SfzInstrument {
+    pub fn load(path: &str) -> anyhow::Result<SfzInstrument> {
+        let text = std::fs::read_to_string(path)?;
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut samples: HashMap<PathBuf, Arc<Sample>> = HashMap::new();
+        let mut regions = Vec::new();
+        for (group, opcodes) in parse_regions(&text) {
+            let sample_path = opcodes
+                .get("sample")
+                .ok_or_else(|| anyhow!("region without a sample"))?;
+            let default_path = opcodes.get("default_path").map(String::as_str);
+            let sample_path = directory
+                .join(default_path.unwrap_or("").replace('\\', "/"))
+                .join(sample_path.replace('\\', "/"));
+
+            let sample = match samples.get(&sample_path) {
+                Some(sample) => sample.clone(),
+                None => {
+                    let sample = Arc::new(
+                        Sample::load(&sample_path.to_string_lossy())
+                            .with_context(|| format!("loading {}", sample_path.display()))?,
+                    );
+                    samples.insert(sample_path, sample.clone());
+                    sample
+                }
+            };
+            regions.push(region(sample, group, &opcodes));
+        }
+
+        Ok(SfzInstrument {
+            name: path.rsplit(['/', '\\']).next().unwrap_or(path).to_string(),
+            regions,
+            round_robin: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Players for every region triggered by a note, advancing the round robin of each group
+    /// the note plays in.
+    pub fn players(&self, note: u8, velocity: u8) -> Vec<SamplePlayer> {
+        let mut round_robin = self.round_robin.lock().unwrap();
+        let mut positions: HashMap<usize, usize> = HashMap::new();
+        self.regions
+            .iter()
+            .filter(|r| r.matches(note, velocity))
+            .filter(|r| {
+                let position = *positions.entry(r.group).or_insert_with(|| {
+                    let count = round_robin.entry((r.group, note)).or_insert(0);
+                    *count += 1;
+                    *count - 1
+                });
+                r.in_sequence(position)
+            })
+            .map(|r| r.player())
+            .collect()
+    }
+}
+
+fn region(sample: Arc<Sample>, group: usize, opcodes: &HashMap<String, String>) -> SfzRegion {
+    let number = |name: &str| opcodes.get(name).and_then(|v| v.parse::<f64>().ok());
+    let note = |name: &str| opcodes.get(name).and_then(|v| parse_note(v));
+
+    let frames = sample.frames() as f64;
+    let key = note("key");
+    let lokey = note("lokey").or(key).unwrap_or(0);
+    let hikey = note("hikey").or(key).unwrap_or(127);
+    let keycenter = note("pitch_keycenter").or(key).unwrap_or(60);
+    let file_loop = sample.loop_points;
+    let mode = match opcodes.get("loop_mode").map(String::as_str) {
+        Some("loop_continuous") => PlayMode::Loop,
+        Some("loop_sustain") => PlayMode::LoopSustain,
+        Some(_) => PlayMode::OneShot,
+        // Like other SFZ players, loop samples that come with a loop of their own
+        None if file_loop.is_some() => PlayMode::Loop,
+        None => PlayMode::OneShot,
+    };
+    let end = number("end").unwrap_or(frames).min(frames);
+
+    SfzRegion {
+        lokey,
+        hikey,
+        lovel: number("lovel").unwrap_or(0.0) as u8,
+        hivel: number("hivel").unwrap_or(127.0) as u8,
+        group,
+        root: keycenter as f64
+            - number("transpose").unwrap_or(0.0)
+            - number("tune").unwrap_or(0.0) / 100.0,
+        seq_length: number("seq_length").unwrap_or(1.0).max(1.0) as usize,
+        seq_position: number("seq_position").unwrap_or(1.0).max(1.0) as usize,
+        mode,
+        offset: number("offset").unwrap_or(0.0).min(end),
+        end,
+        loop_start: number("loop_start")
+            .or_else(|| number("loopstart"))
+            .or(file_loop.map(|(start, _)| start))
+            .unwrap_or(0.0)
+            .min(end),
+        loop_end: number("loop_end")
+            .or_else(|| number("loopend"))
+            .or(file_loop.map(|(_, end)| end))
+            .unwrap_or(end)
+            .min(end),
+        gain: 10.0_f64.powf(number("volume").unwrap_or(0.0) / 20.0),
+        sample,
+    }
+}
+
+/// Splits an SFZ file into the opcodes of each region, with the opcodes of the enclosing
+/// `<control>`, `<global>`, `<master>` and `<group>` headers already applied. Each region comes
+/// with the index of the last of those headers before it.
+fn parse_regions(text: &str) -> Vec<(usize, HashMap<String, String>)> {
+    let mut scopes: [HashMap<String, String>; 5] = Default::default();
+    let mut current: Option<usize> = None;
+    let mut group = 0;
+    let mut regions: Vec<(usize, HashMap<String, String>)> = Vec::new();
+    let mut last_opcode: Option<String> = None;
+
+    let flush = |scope: Option<usize>,
+                 group: usize,
+                 scopes: &[HashMap<String, String>; 5],
+                 regions: &mut Vec<(usize, HashMap<String, String>)>| {
+        if scope == Some(4) {
+            let mut opcodes = HashMap::new();
+            for scope in scopes {
+                opcodes.extend(scope.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+            regions.push((group, opcodes));
+        }
+    };
+
+    for line in strip_comments(text).lines() {
+        // Headers can be glued to opcodes, so give them room before splitting
+        let line = line.replace('<', " <").replace('>', "> ");
+
+        for token in line.split_whitespace() {
+            if let Some(header) = token.strip_prefix('<').and_then(|t| t.strip_suffix('>')) {
+                flush(current, group, &scopes, &mut regions);
+                let level = match header {
+                    "control" => 0,
+                    "global" => 1,
+                    "master" => 2,
+                    "group" => 3,
+                    "region" => 4,
+                    _ => {
+                        current = None;
+                        continue;
+                    }
+                };
+                for scope in scopes.iter_mut().skip(level) {
+                    scope.clear();
+                }
+                if level < 4 {
+                    group += 1;
+                }
+                current = Some(level);
+                last_opcode = None;
+            } else if let Some(level) = current {
+                match token.split_once('=') {
+                    Some((opcode, value)) => {
+                        scopes[level].insert(opcode.to_string(), value.to_string());
+                        last_opcode = Some(opcode.to_string());
+                    }
+                    // Sample paths may contain spaces
+                    None => {
+                        if let Some(value) = last_opcode
+                            .as_ref()
+                            .and_then(|opcode| scopes[level].get_mut(opcode))
+                        {
+                            value.push(' ');
+                            value.push_str(token);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    flush(current, group, &scopes, &mut regions);
+
+    regions
+}
+
+/// Removes `//` comments up to the end of their line and `/* */` comments, which can span
+/// several lines.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = [rest.find("//"), rest.find("/*")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        stripped.push_str(&rest[..start]);
+        // Keeps the tokens on both sides of the comment apart
+        stripped.push(' ');
+        let comment = &rest[start..];
+        rest = if comment.starts_with("//") {
+            comment.find('\n').map_or("", |end| &comment[end..])
+        } else {
+            // A comment that is never closed runs to the end of the file
+            comment[2..]
+                .find("*/")
+                .map_or("", |end| &comment[end + 4..])
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Parses a MIDI note number or a note name like `c#4`, where `c4` is 60.
+fn parse_note(value: &str) -> Option<u8> {
+    if let Ok(number) = value.parse::<u8>() {
+        return Some(number.min(127));
+    }
+
+    let value = value.to_lowercase();
+    let mut chars = value.chars();
+    let mut semitone: i32 = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let octave = if let Some(rest) = rest.strip_prefix('#') {
+        semitone += 1;
+        rest
+    } else if let Some(rest) = rest.strip_prefix('b') {
+        semitone -= 1;
+        rest
+    } else {
+        rest
+    };
+    let note = (octave.parse::<i32>().ok()? + 1) * 12 + semitone;
+    u8::try_from(note).ok().filter(|n| *n <= 127)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Arc<Sample> {
+        Arc::new(Sample {
+            name: "test.wav".to_string(),
+            data: vec![0.0; 1000],
+            sample_rate: 44100.0,
+            loop_points: None,
+        })
+    }
+
+    fn opcodes(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_note_numbers_and_names() {
+        assert_eq!(parse_note("60"), Some(60));
+        assert_eq!(parse_note("200"), Some(127));
+        assert_eq!(parse_note("c4"), Some(60));
+        assert_eq!(parse_note("C4"), Some(60));
+        assert_eq!(parse_note("c#4"), Some(61));
+        assert_eq!(parse_note("db4"), Some(61));
+        assert_eq!(parse_note("a4"), Some(69));
+        assert_eq!(parse_note("c-1"), Some(0));
+        assert_eq!(parse_note("g9"), Some(127));
+        assert_eq!(parse_note("b9"), None);
+        assert_eq!(parse_note("h4"), None);
+        assert_eq!(parse_note("c"), None);
+    }
+
+    #[test]
+    fn regions_inherit_the_opcodes_of_their_headers() {
+        let text = "<global> volume=-6 // comment
+            <group> lokey=36 hikey=47
+            <region> sample=a.wav
+            <region> sample=b.wav hikey=40
+            <group>key=c5
+            <region> sample=c d.wav";
+        let regions = parse_regions(text);
+        assert_eq!(regions.len(), 3);
+
+        let (group, first) = &regions[0];
+        assert_eq!(first["sample"], "a.wav");
+        assert_eq!(first["volume"], "-6");
+        assert_eq!(first["lokey"], "36");
+        assert_eq!(first["hikey"], "47");
+        assert_eq!(regions[1].0, *group);
+        assert_eq!(regions[1].1["hikey"], "40");
+
+        // A new group replaces the opcodes of the previous one but keeps the global ones
+        let (other_group, last) = &regions[2];
+        assert_ne!(other_group, group);
+        assert_eq!(last["sample"], "c d.wav");
+        assert_eq!(last["key"], "c5");
+        assert_eq!(last["volume"], "-6");
+        assert!(!last.contains_key("lokey"));
+    }
+
+    #[test]
+    fn block_comments_are_skipped() {
+        let text = "<region> sample=a.wav /* lokey=10 */ hikey=50
+            /* <region> sample=b.wav
+            lovel=5 */ <region>/**/sample=c.wav";
+        let regions = parse_regions(text);
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].1["sample"], "a.wav");
+        assert_eq!(regions[0].1["hikey"], "50");
+        assert!(!regions[0].1.contains_key("lokey"));
+        assert!(!regions[0].1.contains_key("lovel"));
+        assert_eq!(regions[1].1["sample"], "c.wav");
+    }
+
+    #[test]
+    fn regions_fall_back_to_the_loop_of_the_file() {
+        let looped = Arc::new(Sample {
+            name: "looped.wav".to_string(),
+            data: vec![0.0; 1000],
+            sample_rate: 44100.0,
+            loop_points: Some((100.0, 900.0)),
+        });
+
+        let default = region(looped.clone(), 0, &HashMap::new());
+        assert_eq!(default.mode, PlayMode::Loop);
+        assert_eq!((default.loop_start, default.loop_end), (100.0, 900.0));
+
+        let sustained = region(
+            looped.clone(),
+            0,
+            &opcodes(&[("loop_mode", "loop_sustain"), ("loop_end", "800")]),
+        );
+        assert_eq!(sustained.mode, PlayMode::LoopSustain);
+        assert_eq!((sustained.loop_start, sustained.loop_end), (100.0, 800.0));
+
+        let one_shot = region(looped, 0, &opcodes(&[("loop_mode", "no_loop")]));
+        assert_eq!(one_shot.mode, PlayMode::OneShot);
+
+        assert_eq!(region(sample(), 0, &HashMap::new()).mode, PlayMode::OneShot);
+    }
+
+    #[test]
+    fn regions_match_their_key_and_velocity_ranges() {
+        let ranged = region(
+            sample(),
+            0,
+            &opcodes(&[
+                ("lokey", "c4"),
+                ("hikey", "62"),
+                ("lovel", "20"),
+                ("hivel", "100"),
+            ]),
+        );
+        assert_eq!((ranged.lokey, ranged.hikey), (60, 62));
+        assert!(ranged.matches(60, 20));
+        assert!(ranged.matches(62, 100));
+        assert!(!ranged.matches(59, 64));
+        assert!(!ranged.matches(63, 64));
+        assert!(!ranged.matches(61, 19));
+        assert!(!ranged.matches(61, 101));
+
+        // A single key sets the range and the root
+        let single = region(
+            sample(),
+            0,
+            &opcodes(&[("key", "c5"), ("transpose", "2"), ("tune", "50")]),
+        );
+        assert_eq!((single.lokey, single.hikey), (72, 72));
+        assert_eq!((single.lovel, single.hivel), (0, 127));
+        assert_eq!(single.root, 69.5);
+    }
+
+    #[test]
+    fn round_robins_count_per_group_and_key() {
+        let second = |group| SfzRegion {
+            group,
+            seq_length: 2,
+            seq_position: 2,
+            ..region(sample(), group, &HashMap::new())
+        };
+        let instrument = SfzInstrument {
+            name: "test.sfz".to_string(),
+            regions: vec![second(1), second(2)],
+            round_robin: Mutex::new(HashMap::new()),
+        };
+
+        // Both groups play their second sample on every other note of the same key
+        assert_eq!(instrument.players(60, 100).len(), 0);
+        assert_eq!(instrument.players(60, 100).len(), 2);
+        assert_eq!(instrument.players(62, 100).len(), 0);
+        assert_eq!(instrument.players(60, 100).len(), 0);
+        assert_eq!(instrument.players(62, 100).len(), 2);
+    }
+}
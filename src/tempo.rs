@@ -0,0 +1,85 @@
+/// Note length that tempo-synced times are set in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteDivision {
+    Whole,
+    Half,
+    Quarter,
+    DottedQuarter,
+    QuarterTriplet,
+    Eighth,
+    DottedEighth,
+    EighthTriplet,
+    Sixteenth,
+    DottedSixteenth,
+    SixteenthTriplet,
+    ThirtySecond,
+}
+
+impl NoteDivision {
+    pub const ALL: [NoteDivision; 12] = [
+        NoteDivision::Whole,
+        NoteDivision::Half,
+        NoteDivision::Quarter,
+        NoteDivision::DottedQuarter,
+        NoteDivision::QuarterTriplet,
+        NoteDivision::Eighth,
+        NoteDivision::DottedEighth,
+        NoteDivision::EighthTriplet,
+        NoteDivision::Sixteenth,
+        NoteDivision::DottedSixteenth,
+        NoteDivision::SixteenthTriplet,
+        NoteDivision::ThirtySecond,
+    ];
+
+    /// Length in quarter notes.
+    pub fn beats(&self) -> f64 {
+        match self {
+            NoteDivision::Whole => 4.0,
+            NoteDivision::Half => 2.0,
+            NoteDivision::Quarter => 1.0,
+            NoteDivision::DottedQuarter => 1.5,
+            NoteDivision::QuarterTriplet => 2.0 / 3.0,
+            NoteDivision::Eighth => 0.5,
+            NoteDivision::DottedEighth => 0.75,
+            NoteDivision::EighthTriplet => 1.0 / 3.0,
+            NoteDivision::Sixteenth => 0.25,
+            NoteDivision::DottedSixteenth => 0.375,
+            NoteDivision::SixteenthTriplet => 1.0 / 6.0,
+            NoteDivision::ThirtySecond => 0.125,
+        }
+    }
+
+    /// Length in seconds at a tempo in beats per minute.
+    pub fn seconds(&self, tempo: f64) -> f64 {
+        self.beats() * 60.0 / tempo
+    }
+}
+
+impl Default for NoteDivision {
+    fn default() -> NoteDivision {
+        NoteDivision::Eighth
+    }
+}
+
+impl std::fmt::Display for NoteDivision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                NoteDivision::Whole => "1/1",
+                NoteDivision::Half => "1/2",
+                NoteDivision::Quarter => "1/4",
+                NoteDivision::DottedQuarter => "1/4 D",
+                NoteDivision::QuarterTriplet => "1/4 T",
+                NoteDivision::Eighth => "1/8",
+                NoteDivision::DottedEighth => "1/8 D",
+                NoteDivision::EighthTriplet => "1/8 T",
+                NoteDivision::Sixteenth => "1/16",
+                NoteDivision::DottedSixteenth => "1/16 D",
+                NoteDivision::SixteenthTriplet => "1/16 T",
+                NoteDivision::ThirtySecond => "1/32",
+            }
+        )
+    }
+}
@@ -20,3 +20,11 @@ pub fn hex_to_color(hex: &str) -> Option<Color> {
 
     None
 }
+
+/// Name of a MIDI note, with middle C (60) as C4.
+pub fn note_name(note: u8) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
@@ -0,0 +1,416 @@
+use crate::audio::{Controllers, Parameters, VoiceControls, FINISHED_TAG};
+use crate::effects::{Distortion, DistortionPlacement};
+use crate::filter::{Filter, FilterRouting, Routing};
+use crate::modulation::{random, Lfo, LfoState, ModSlot, ModSources, Modulation};
+use crate::oscillator::{CrossMod, CrossModulation, Source};
+use crate::sequencer::LockParameter;
+use crate::voicing::Glide;
+use fundsp::hacker::var;
+use fundsp::prelude::{An, AudioUnit64, Var};
+
+/// All oscillators of a single note rendered as one graph, so they can modulate each other.
+pub struct Voice {
+    sources: Vec<Source>,
+    envelopes: Vec<Box<dyn AudioUnit64>>,
+    envelopes_finished: Vec<An<Var<f64>>>,
+    /// Level of each envelope for the current sample.
+    levels: Vec<f64>,
+    filters: Vec<Box<dyn AudioUnit64>>,
+    filter_envelopes: Vec<Box<dyn AudioUnit64>>,
+    /// Envelope depth of each filter in octaves, already scaled by velocity.
+    filter_octaves: Vec<f64>,
+    filter_levels: Vec<f64>,
+    filter_settings: Vec<Filter>,
+    filter_slots: Vec<usize>,
+    routing: Routing,
+    /// Distortion on the inputs of the two filters, when it is placed in the voices.
+    distortion: Option<Distortion>,
+    /// Frequency of each oscillator for the note the voice was started with.
+    frequencies: Vec<f64>,
+    first_note: f64,
+    /// Note the voice plays now, which legato can change while it plays.
+    target: An<Var<f64>>,
+    note: f64,
+    glide: Glide,
+    finished: An<Var<f64>>,
+    releasing: An<Var<f64>>,
+    /// Whether the sources have been told that the key was let go.
+    released: bool,
+    pitch_bend: An<Var<f64>>,
+    pressure: An<Var<f64>>,
+    timbre: An<Var<f64>>,
+    cross_mod: CrossMod,
+    volume: f64,
+    sample_rate: f64,
+    sync_phase: f64,
+    /// State of the noise that excites a self-oscillating ladder filter.
+    noise: u32,
+    lfos: Vec<Lfo>,
+    lfo_states: Vec<LfoState>,
+    matrix: Vec<ModSlot>,
+    /// Values the sequencer steps lock parameters to for this note, NaN where unlocked.
+    locks: Vec<An<Var<f64>>>,
+    controllers: Controllers,
+    /// Modulation sources that stay fixed for the whole note.
+    velocity: f64,
+    random: f64,
+    /// Modulation of the previous sample, which modulates the LFOs without a feedback loop.
+    last_modulation: Modulation,
+}
+
+impl Voice {
+    pub fn new(
+        parameters: &Parameters,
+        note: u8,
+        velocity: u8,
+        controls: VoiceControls,
+        sample_rate: f64,
+    ) -> Voice {
+        let releasing = controls.releasing;
+        let oscillators = parameters.oscillators.read().unwrap().clone();
+        let filters: Vec<Filter> = parameters
+            .filters
+            .iter()
+            .map(|f| f.read().unwrap().clone())
+            .collect();
+
+        let distortion = parameters.distortion.read().unwrap().clone();
+        let voicing = parameters.voicing.read().unwrap().clone();
+        controls.note.set_value(note as f64);
+
+        let lfos: Vec<Lfo> = parameters
+            .lfos
+            .iter()
+            .map(|l| l.read().unwrap().clone())
+            .collect();
+        let seed = parameters.epoch.elapsed().subsec_nanos() ^ note as u32;
+
+        let envelopes_finished: Vec<An<Var<f64>>> =
+            oscillators.iter().map(|_| var(FINISHED_TAG, 0.0)).collect();
+
+        let mut voice = Voice {
+            sources: oscillators
+                .iter()
+                .map(|o| o.generate_source(note, velocity))
+                .collect(),
+            envelopes: oscillators
+                .iter()
+                .zip(&envelopes_finished)
+                .map(|(o, f)| o.generate_envelope(releasing.clone(), f.clone()))
+                .collect(),
+            envelopes_finished,
+            levels: vec![0.0; oscillators.len()],
+            filters: filters.iter().map(|f| f.generate()).collect(),
+            filter_envelopes: filters
+                .iter()
+                .map(|f| f.generate_envelope(releasing.clone()))
+                .collect(),
+            filter_octaves: filters
+                .iter()
+                .map(|f| f.envelope_octaves(velocity))
+                .collect(),
+            filter_levels: vec![0.0; filters.len()],
+            filter_settings: filters,
+            filter_slots: oscillators.iter().map(|o| o.filter.index()).collect(),
+            routing: parameters.routing.read().unwrap().clone(),
+            distortion: match distortion.placement {
+                DistortionPlacement::Voice => Some(Distortion::new(&distortion)),
+                DistortionPlacement::Master => None,
+            },
+            frequencies: oscillators.iter().map(|o| o.frequency(note)).collect(),
+            first_note: note as f64,
+            target: controls.note,
+            note: note as f64,
+            glide: Glide::new(voicing.glide, voicing.glide_curve, sample_rate),
+            finished: controls.finished,
+            releasing,
+            released: false,
+            pitch_bend: controls.pitch_bend,
+            pressure: controls.pressure,
+            timbre: controls.timbre,
+            cross_mod: parameters.cross_mod.read().unwrap().clone(),
+            volume: velocity as f64 / 127.0,
+            sample_rate,
+            sync_phase: 0.0,
+            noise: 0x9e37_79b9 ^ note as u32,
+            lfo_states: lfos
+                .iter()
+                .enumerate()
+                .map(|(i, l)| {
+                    LfoState::new(
+                        l,
+                        parameters.epoch,
+                        seed.wrapping_add(i as u32),
+                        sample_rate,
+                    )
+                })
+                .collect(),
+            lfos,
+            matrix: parameters.matrix.read().unwrap().clone(),
+            locks: controls.locks,
+            controllers: parameters.controllers.clone(),
+            velocity: velocity as f64 / 127.0,
+            random: random(seed),
+            last_modulation: Modulation::default(),
+        };
+
+        if let Some(from) = controls.glide_from {
+            voice.glide.start(from as f64 - note as f64);
+        }
+        for source in voice.sources.iter_mut() {
+            source.reset(sample_rate);
+        }
+        for unit in voice
+            .envelopes
+            .iter_mut()
+            .chain(voice.filters.iter_mut())
+            .chain(voice.filter_envelopes.iter_mut())
+        {
+            unit.reset(Some(sample_rate));
+        }
+
+        voice
+    }
+
+    pub fn next_sample(&mut self) -> (f64, f64) {
+        let target = self.target.value();
+        if target != self.note {
+            // A new note slides from wherever the pitch is now
+            self.glide.start(self.pitch() - target);
+            self.note = target;
+        }
+        self.glide.tick();
+        if !self.released && self.releasing.value() != 0.0 {
+            self.released = true;
+            for source in self.sources.iter_mut() {
+                source.release();
+            }
+        }
+
+        for (level, envelope) in self.levels.iter_mut().zip(self.envelopes.iter_mut()) {
+            *level = envelope.get_mono();
+        }
+        for (level, envelope) in self
+            .filter_levels
+            .iter_mut()
+            .zip(self.filter_envelopes.iter_mut())
+        {
+            *level = envelope.get_mono();
+        }
+
+        let mut modulation = Modulation::default();
+        let mut sources = ModSources {
+            envelope: self.levels.first().copied().unwrap_or(0.0),
+            velocity: self.velocity,
+            note: (self.pitch() - 60.0) / 64.0,
+            mod_wheel: self.controllers.mod_wheel.value(),
+            breath: self.controllers.breath.value(),
+            aftertouch: self.controllers.aftertouch.value(),
+            poly_pressure: self.pressure.value(),
+            timbre: self.timbre.value(),
+            random: self.random,
+            ..ModSources::default()
+        };
+        for (i, level) in self.filter_levels.iter().take(2).enumerate() {
+            sources.filter_envelopes[i] = *level;
+        }
+        for (i, (lfo, state)) in self.lfos.iter().zip(self.lfo_states.iter_mut()).enumerate() {
+            let rate = self.last_modulation.lfo_rate.get(i).copied().unwrap_or(0.0);
+            let depth = self
+                .last_modulation
+                .lfo_depth
+                .get(i)
+                .copied()
+                .unwrap_or(0.0);
+            let value = state.tick(lfo.waveform, rate);
+            if let Some(source) = sources.lfos.get_mut(i) {
+                *source = value;
+            }
+            modulation.add(lfo.destination, value * (lfo.depth + depth).clamp(0.0, 1.0));
+        }
+        for slot in &self.matrix {
+            modulation.add(slot.destination, sources.value(slot.source) * slot.amount);
+        }
+
+        let transpose = ((self.pitch() - self.first_note) / 12.0).exp2();
+        let bend = self.bend() * modulation.frequency_ratio() * transpose;
+        let width = modulation.pulse_width();
+        let gain = self.volume * 2.0 * modulation.gain() * self.controllers.gain();
+        let fm_index = self.locked(LockParameter::FmIndex, self.cross_mod.fm_index);
+        let fm_index = (fm_index + modulation.fm_index).max(0.0);
+        let cross_mod = if self.sources.len() > 1 {
+            self.cross_mod.mode
+        } else {
+            CrossModulation::Off
+        };
+
+        // Oscillators run back to front so oscillator 2 is ready to modulate oscillator 1
+        let mut buses = [0.0; 2];
+        // Loudest envelope on each filter input
+        let mut amps = [0.0f64; 2];
+        let mut modulator = 0.0;
+        for i in (0..self.sources.len()).rev() {
+            let frequency = self.frequencies[i] * bend;
+            let level = self.levels[i];
+            let source = &mut self.sources[i];
+
+            let output = match (cross_mod, i) {
+                (CrossModulation::Fm, 1) => {
+                    modulator = source.tick(frequency, width) * level;
+                    continue;
+                }
+                (CrossModulation::Ring, 1) => {
+                    modulator = source.tick(frequency, width);
+                    continue;
+                }
+                (CrossModulation::Fm, 0) => {
+                    let deviation = fm_index * self.frequencies[1] * bend;
+                    source.tick(frequency + deviation * modulator, width) * level
+                }
+                (CrossModulation::Ring, 0) => source.tick(frequency, width) * modulator * level,
+                (CrossModulation::Sync, 0) => {
+                    let output = source.tick(frequency, width) * level;
+                    self.sync_phase += frequency / self.sample_rate;
+                    if self.sync_phase >= 1.0 {
+                        self.sync_phase = self.sync_phase.fract();
+                        self.sources[1].sync(self.sample_rate);
+                    }
+                    output
+                }
+                _ => source.tick(frequency, width) * level,
+            };
+            let slot = self.filter_slots[i];
+            buses[slot] += output;
+            amps[slot] = amps[slot].max(level);
+        }
+
+        // The filters get the oscillators at full level and the envelopes and gain are
+        // applied after them, like a VCA, so a self-oscillating filter follows them as well
+        let amps = match self.routing.mode {
+            FilterRouting::PerOscillator => amps,
+            FilterRouting::Serial | FilterRouting::Parallel => [amps[0].max(amps[1]); 2],
+        };
+        for (bus, amp) in buses.iter_mut().zip(amps) {
+            *bus = if amp > 0.0 { *bus / amp } else { 0.0 };
+        }
+
+        if let Some(distortion) = self.distortion.as_mut() {
+            let (first, second) = distortion.process((buses[0], buses[1]));
+            buses = [first, second];
+        }
+
+        let out = match self.routing.mode {
+            FilterRouting::PerOscillator => {
+                self.filter(0, buses[0], &modulation) * amps[0]
+                    + self.filter(1, buses[1], &modulation) * amps[1]
+            }
+            FilterRouting::Serial => {
+                let first = self.filter(0, buses[0] + buses[1], &modulation);
+                self.filter(1, first, &modulation) * amps[0]
+            }
+            FilterRouting::Parallel => {
+                let input = buses[0] + buses[1];
+                let balance = self.locked(LockParameter::FilterBalance, self.routing.balance);
+                let balance = (balance + modulation.balance).clamp(0.0, 1.0);
+                (self.filter(0, input, &modulation) * (1.0 - balance)
+                    + self.filter(1, input, &modulation) * balance)
+                    * amps[0]
+            }
+        } * gain;
+
+        if self.envelopes_finished.iter().all(|f| f.value() != 0.0) {
+            self.finished.set_value(1.0);
+        }
+
+        let (left, right) = modulation.pan_gains();
+        self.last_modulation = modulation;
+        (out * left, out * right)
+    }
+
+    fn filter(&mut self, i: usize, input: f64, modulation: &Modulation) -> f64 {
+        let noise = self.next_noise();
+        let mut settings = self.filter_settings[i].clone();
+        let [cutoff, resonance, drive, envelope_amount] = if i == 0 {
+            [
+                LockParameter::Cutoff1,
+                LockParameter::Resonance1,
+                LockParameter::Drive1,
+                LockParameter::EnvelopeAmount1,
+            ]
+        } else {
+            [
+                LockParameter::Cutoff2,
+                LockParameter::Resonance2,
+                LockParameter::Drive2,
+                LockParameter::EnvelopeAmount2,
+            ]
+        };
+        settings.cutoff = self.locked(cutoff, settings.cutoff);
+        settings.resonance = self.locked(resonance, settings.resonance);
+        settings.drive = self.locked(drive, settings.drive);
+        let envelope_octaves = match self.lock(envelope_amount) {
+            Some(amount) => Filter {
+                envelope_amount: amount,
+                ..settings.clone()
+            }
+            .envelope_octaves((self.velocity * 127.0).round() as u8),
+            None => self.filter_octaves[i],
+        };
+        settings.resonance = (settings.resonance + modulation.resonance[i]).clamp(0.0, 100.0);
+        settings.drive = (settings.drive + modulation.drive[i]).clamp(0.0, 1.0);
+
+        // A resonant peak only rings with something to ring on, even when the input is silent
+        let input = settings.saturate(input) + settings.self_oscillation() * noise * 1e-3;
+        // Key tracking follows the bent pitch, not just the key that was struck
+        let note = self.pitch() + 12.0 * self.bend().log2() + modulation.pitch;
+        let octaves = envelope_octaves * self.filter_levels[i]
+            + settings.key_tracking_octaves(note)
+            + modulation.cutoff[i];
+        let cutoff = settings.cutoff * octaves.exp2();
+        let cutoff = cutoff.clamp(10.0, self.sample_rate * 0.45);
+        tick_filter(self.filters[i].as_mut(), input, cutoff, settings.q())
+    }
+
+    /// Value a sequencer step locks a parameter to, if any.
+    fn lock(&self, parameter: LockParameter) -> Option<f64> {
+        let value = self.locks[parameter as usize].value();
+        if value.is_nan() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Value of a parameter of the patch, or the value a sequencer step locks it to.
+    fn locked(&self, parameter: LockParameter, patch: f64) -> f64 {
+        self.lock(parameter).unwrap_or(patch)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.value() != 0.0
+    }
+
+    /// Note the pitch is at on its way to the played note, before any bend.
+    fn pitch(&self) -> f64 {
+        self.note + self.glide.offset()
+    }
+
+    /// Pitch bend of the note combined with the bend of the whole synth, as a frequency ratio.
+    fn bend(&self) -> f64 {
+        self.pitch_bend.value() * self.controllers.pitch_bend.value()
+    }
+
+    /// White noise between -1 and 1 from a xorshift generator.
+    fn next_noise(&mut self) -> f64 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f64 / u32::MAX as f64 * 2.0 - 1.0
+    }
+}
+
+fn tick_filter(unit: &mut dyn AudioUnit64, input: f64, cutoff: f64, q: f64) -> f64 {
+    let mut output = [0.0];
+    unit.tick(&[input, cutoff, q], &mut output);
+    output[0]
+}
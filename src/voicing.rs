@@ -0,0 +1,440 @@
+use crate::audio::{Parameters, VoiceControls};
+use crate::voice::Voice;
+
+/// How new notes are given voices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceMode {
+    /// Every note plays its own voice.
+    Poly,
+    /// One voice, restarted for every new note.
+    Mono,
+    /// One voice, which keeps its envelopes running while notes overlap.
+    Legato,
+}
+
+impl VoiceMode {
+    pub const ALL: [VoiceMode; 3] = [VoiceMode::Poly, VoiceMode::Mono, VoiceMode::Legato];
+}
+
+impl Default for VoiceMode {
+    fn default() -> VoiceMode {
+        VoiceMode::Poly
+    }
+}
+
+impl std::fmt::Display for VoiceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                VoiceMode::Poly => "Poly",
+                VoiceMode::Mono => "Mono",
+                VoiceMode::Legato => "Legato",
+            }
+        )
+    }
+}
+
+/// Which of the held notes the single voice plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotePriority {
+    Last,
+    Low,
+    High,
+}
+
+impl NotePriority {
+    pub const ALL: [NotePriority; 3] = [NotePriority::Last, NotePriority::Low, NotePriority::High];
+}
+
+impl Default for NotePriority {
+    fn default() -> NotePriority {
+        NotePriority::Last
+    }
+}
+
+impl std::fmt::Display for NotePriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                NotePriority::Last => "Last",
+                NotePriority::Low => "Low",
+                NotePriority::High => "High",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlideCurve {
+    /// Constant speed in semitones.
+    Linear,
+    /// Fast at first, settling slowly into the note.
+    Exponential,
+}
+
+impl GlideCurve {
+    pub const ALL: [GlideCurve; 2] = [GlideCurve::Linear, GlideCurve::Exponential];
+
+    /// Share of the distance that is left to slide at `progress` from 0 to 1.
+    pub fn remaining(&self, progress: f64) -> f64 {
+        match self {
+            GlideCurve::Linear => 1.0 - progress,
+            // Scaled so the curve still arrives at the end of the glide time
+            GlideCurve::Exponential => {
+                let end = (-5.0f64).exp();
+                ((-5.0 * progress).exp() - end) / (1.0 - end)
+            }
+        }
+    }
+}
+
+impl Default for GlideCurve {
+    fn default() -> GlideCurve {
+        GlideCurve::Exponential
+    }
+}
+
+impl std::fmt::Display for GlideCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                GlideCurve::Linear => "Linear",
+                GlideCurve::Exponential => "Exponential",
+            }
+        )
+    }
+}
+
+/// Slide of the pitch from the note before to the one that is played.
+pub struct Glide {
+    /// Semitones from the note the pitch started sliding at, with how far the slide is.
+    distance: f64,
+    progress: f64,
+    step: f64,
+    curve: GlideCurve,
+}
+
+impl Glide {
+    pub fn new(time: f64, curve: GlideCurve, sample_rate: f64) -> Glide {
+        Glide {
+            distance: 0.0,
+            progress: 0.0,
+            // No glide time arrives at the note on the first sample
+            step: 1.0 / (time * sample_rate),
+            curve,
+        }
+    }
+
+    /// Starts sliding from `distance` semitones away from the note.
+    pub fn start(&mut self, distance: f64) {
+        self.distance = distance;
+        self.progress = 0.0;
+    }
+
+    /// Advances the slide by one sample.
+    pub fn tick(&mut self) {
+        self.progress = (self.progress + self.step).min(1.0);
+    }
+
+    /// Semitones the pitch is still away from the note.
+    pub fn offset(&self) -> f64 {
+        self.distance * self.curve.remaining(self.progress)
+    }
+}
+
+#[derive(Clone)]
+pub struct VoicingSettings {
+    pub mode: VoiceMode,
+    pub priority: NotePriority,
+    /// Time in seconds the pitch slides from the previous note in the mono modes.
+    pub glide: f64,
+    pub glide_curve: GlideCurve,
+}
+
+impl Default for VoicingSettings {
+    fn default() -> VoicingSettings {
+        VoicingSettings {
+            mode: VoiceMode::Poly,
+            priority: NotePriority::Last,
+            glide: 0.0,
+            glide_curve: GlideCurve::Exponential,
+        }
+    }
+}
+
+/// Note, velocity and controls of a voice to start.
+struct VoiceStart {
+    note: u8,
+    velocity: u8,
+    controls: VoiceControls,
+}
+
+struct MonoNote {
+    /// MIDI channel of a key, or none for the notes of the arpeggiator and the sequencer.
+    channel: Option<u8>,
+    note: u8,
+    velocity: u8,
+    controls: VoiceControls,
+}
+
+/// The single voice of the mono and legato modes, played by the held note with the highest
+/// priority.
+pub struct MonoVoice {
+    /// Notes that are down, in the order they were played.
+    notes: Vec<MonoNote>,
+    /// Channel and note the voice plays with its controls, until the voice is released.
+    playing: Option<(Option<u8>, u8, VoiceControls)>,
+    /// Last note that was played, which the next one glides from.
+    last: Option<u8>,
+    sample_rate: f64,
+}
+
+impl MonoVoice {
+    pub fn new(sample_rate: f64) -> MonoVoice {
+        MonoVoice {
+            notes: Vec::new(),
+            playing: None,
+            last: None,
+            sample_rate,
+        }
+    }
+
+    /// Holds a note, returning the voice to start when the note takes over a new voice.
+    pub fn note_on(
+        &mut self,
+        settings: &VoicingSettings,
+        parameters: &Parameters,
+        channel: Option<u8>,
+        note: u8,
+        velocity: u8,
+        controls: VoiceControls,
+    ) -> Option<Voice> {
+        let start = self.press(settings, channel, note, velocity, controls)?;
+        Some(self.voice(parameters, start))
+    }
+
+    /// Lets go of a note, returning the voice to start when a held note takes over again.
+    pub fn note_off(
+        &mut self,
+        settings: &VoicingSettings,
+        parameters: &Parameters,
+        channel: Option<u8>,
+        note: u8,
+    ) -> Option<Voice> {
+        let start = self.release(settings, channel, note)?;
+        Some(self.voice(parameters, start))
+    }
+
+    fn press(
+        &mut self,
+        settings: &VoicingSettings,
+        channel: Option<u8>,
+        note: u8,
+        velocity: u8,
+        controls: VoiceControls,
+    ) -> Option<VoiceStart> {
+        self.notes
+            .retain(|n| n.note != note || n.channel != channel);
+        self.notes.push(MonoNote {
+            channel,
+            note,
+            velocity,
+            controls,
+        });
+        self.play(settings)
+    }
+
+    fn release(
+        &mut self,
+        settings: &VoicingSettings,
+        channel: Option<u8>,
+        note: u8,
+    ) -> Option<VoiceStart> {
+        self.notes
+            .retain(|n| n.note != note || n.channel != channel);
+        if self.notes.is_empty() {
+            if let Some((_, _, controls)) = self.playing.take() {
+                controls.releasing.set_value(1.0);
+            }
+            return None;
+        }
+        self.play(settings)
+    }
+
+    fn voice(&self, parameters: &Parameters, start: VoiceStart) -> Voice {
+        Voice::new(
+            parameters,
+            start.note,
+            start.velocity,
+            start.controls,
+            self.sample_rate,
+        )
+    }
+
+    /// Channel and note of the playing voice with its controls.
+    pub fn playing(&self) -> Option<(Option<u8>, u8, &VoiceControls)> {
+        self.playing
+            .as_ref()
+            .map(|(channel, note, controls)| (*channel, *note, controls))
+    }
+
+    /// Moves the voice to the held note with the highest priority, returning what to start
+    /// when that takes a new voice.
+    fn play(&mut self, settings: &VoicingSettings) -> Option<VoiceStart> {
+        let next = match settings.priority {
+            NotePriority::Last => self.notes.last(),
+            NotePriority::Low => self.notes.iter().min_by_key(|n| n.note),
+            NotePriority::High => self.notes.iter().max_by_key(|n| n.note),
+        }?;
+
+        match &self.playing {
+            Some((channel, note, _)) if *channel == next.channel && *note == next.note => None,
+            Some((_, _, controls)) if settings.mode == VoiceMode::Legato => {
+                // The voice slides to the new note with its envelopes where they are, and takes
+                // on the expression and the step locks of the new note
+                controls.note.set_value(next.note as f64);
+                controls
+                    .pitch_bend
+                    .set_value(next.controls.pitch_bend.value());
+                controls.pressure.set_value(next.controls.pressure.value());
+                controls.timbre.set_value(next.controls.timbre.value());
+                for (lock, next) in controls.locks.iter().zip(&next.controls.locks) {
+                    lock.set_value(next.value());
+                }
+                self.playing = Some((next.channel, next.note, controls.clone()));
+                self.last = Some(next.note);
+                None
+            }
+            _ => {
+                if let Some((_, _, controls)) = self.playing.take() {
+                    controls.releasing.set_value(1.0);
+                }
+                let controls = VoiceControls {
+                    glide_from: self.last,
+                    ..next.controls.renewed()
+                };
+                self.playing = Some((next.channel, next.note, controls.clone()));
+                self.last = Some(next.note);
+                Some(VoiceStart {
+                    note: next.note,
+                    velocity: next.velocity,
+                    controls,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(mode: VoiceMode, priority: NotePriority) -> VoicingSettings {
+        VoicingSettings {
+            mode,
+            priority,
+            ..VoicingSettings::default()
+        }
+    }
+
+    fn press(mono: &mut MonoVoice, settings: &VoicingSettings, note: u8) -> Option<VoiceStart> {
+        mono.press(settings, Some(0), note, 100, VoiceControls::default())
+    }
+
+    fn playing(mono: &MonoVoice) -> Option<u8> {
+        mono.playing().map(|(_, note, _)| note)
+    }
+
+    /// Note played after holding `notes` in order and letting go of the one that plays.
+    fn after_release(priority: NotePriority, notes: &[u8]) -> Option<u8> {
+        let settings = settings(VoiceMode::Mono, priority);
+        let mut mono = MonoVoice::new(48000.0);
+        for note in notes {
+            press(&mut mono, &settings, *note);
+        }
+        let released = playing(&mono)?;
+        let start = mono.release(&settings, Some(0), released)?;
+        assert_eq!(playing(&mono), Some(start.note));
+        Some(start.note)
+    }
+
+    #[test]
+    fn note_priority_picks_the_note_after_a_release() {
+        assert_eq!(after_release(NotePriority::Last, &[60, 67, 64]), Some(67));
+        assert_eq!(after_release(NotePriority::Low, &[64, 60, 67]), Some(64));
+        assert_eq!(after_release(NotePriority::High, &[60, 67, 64]), Some(64));
+    }
+
+    #[test]
+    fn releasing_the_last_note_releases_the_voice() {
+        let settings = settings(VoiceMode::Mono, NotePriority::Last);
+        let mut mono = MonoVoice::new(48000.0);
+        let start = press(&mut mono, &settings, 60).unwrap();
+        assert!(mono.release(&settings, Some(0), 60).is_none());
+        assert_eq!(playing(&mono), None);
+        assert_eq!(start.controls.releasing.value(), 1.0);
+    }
+
+    #[test]
+    fn legato_moves_the_voice_without_a_new_one() {
+        let settings = settings(VoiceMode::Legato, NotePriority::Last);
+        let mut mono = MonoVoice::new(48000.0);
+        let first = press(&mut mono, &settings, 60).unwrap();
+
+        assert!(press(&mut mono, &settings, 64).is_none());
+        assert_eq!(first.controls.note.value(), 64.0);
+        assert_eq!(first.controls.releasing.value(), 0.0);
+        assert_eq!(playing(&mono), Some(64));
+    }
+
+    #[test]
+    fn mono_restarts_the_voice_and_glides_from_the_last_note() {
+        let settings = settings(VoiceMode::Mono, NotePriority::Last);
+        let mut mono = MonoVoice::new(48000.0);
+        let first = press(&mut mono, &settings, 60).unwrap();
+        assert_eq!(first.controls.glide_from, None);
+
+        let second = press(&mut mono, &settings, 64).unwrap();
+        assert_eq!(first.controls.releasing.value(), 1.0);
+        assert_eq!(second.note, 64);
+        assert_eq!(second.controls.glide_from, Some(60));
+    }
+
+    #[test]
+    fn zero_glide_time_arrives_at_once() {
+        for curve in GlideCurve::ALL {
+            let mut glide = Glide::new(0.0, curve, 48000.0);
+            glide.start(-12.0);
+            glide.tick();
+            assert_eq!(glide.offset(), 0.0);
+        }
+    }
+
+    #[test]
+    fn glide_curves_slide_over_the_glide_time() {
+        let halfway = |curve| {
+            let mut glide = Glide::new(1.0, curve, 10.0);
+            glide.start(12.0);
+            assert_eq!(glide.offset(), 12.0);
+            for _ in 0..5 {
+                glide.tick();
+            }
+            let middle = glide.offset();
+            for _ in 0..6 {
+                glide.tick();
+            }
+            assert_eq!(glide.offset(), 0.0);
+            middle
+        };
+        assert!((halfway(GlideCurve::Linear) - 6.0).abs() < 1e-9);
+        // The exponential curve covers most of the distance early on
+        let exponential = halfway(GlideCurve::Exponential);
+        assert!(exponential > 0.0 && exponential < 1.0);
+    }
+}